@@ -0,0 +1,568 @@
+use chrono::{DateTime, Duration as TimeDelta, Utc};
+use log::{error, info, warn};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::notifiers::{self, Notifier};
+use crate::settings::{AlertingSettings, NotifierSettings};
+use crate::transitions::Status;
+use crate::worker::MonitorResult;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertEvent {
+    /// The monitor crossed the failure thresholds
+    Down,
+    /// A monitor reported down passed a check again
+    Recovered,
+}
+
+/// A change in a monitor's state, sent to every notifier
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Alert {
+    pub event: AlertEvent,
+    pub monitor_id: Uuid,
+    pub monitor_name: String,
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// When the first of the consecutive failed checks ran
+    pub down_since: DateTime<Utc>,
+    /// When the check that raised the alert ran
+    pub timestamp: DateTime<Utc>,
+    /// Consecutive failed checks
+    pub failures: u32,
+    /// Status code and response time of the check that raised the alert
+    pub status_code: Option<u16>,
+    pub response_time_ms: u64,
+    /// Why the latest failed check failed
+    pub error_type: Option<String>,
+    pub error: Option<String>,
+}
+
+impl Alert {
+    /// One-line summary, e.g. for an email subject
+    pub fn summary(&self) -> String {
+        match self.event {
+            AlertEvent::Down => format!("[DOWN] {} ({})", self.monitor_name, self.url),
+            AlertEvent::Recovered => format!("[RECOVERED] {} ({})", self.monitor_name, self.url),
+        }
+    }
+
+    /// The alert's details as labelled values, for notifiers that lay them
+    /// out themselves
+    pub fn facts(&self) -> Vec<(&'static str, String)> {
+        let mut facts = vec![
+            ("Monitor", self.monitor_name.clone()),
+            ("URL", self.url.clone()),
+        ];
+        if let Some(group) = &self.group {
+            facts.push(("Group", group.clone()));
+        }
+
+        match self.event {
+            AlertEvent::Down => {
+                facts.push(("Down since", self.down_since.to_rfc3339()));
+                facts.push(("Consecutive failures", self.failures.to_string()));
+            }
+            AlertEvent::Recovered => {
+                facts.push(("Recovered at", self.timestamp.to_rfc3339()));
+            }
+        }
+        facts.push(("Down for", format_down_for(self.down_for())));
+
+        if let Some(error_type) = &self.error_type {
+            facts.push(("Error type", error_type.clone()));
+        }
+        let status_code = self
+            .status_code
+            .map(|code| code.to_string())
+            .unwrap_or_else(|| "N/A".to_string());
+        facts.push(("Status code", status_code));
+        facts.push(("Response time", format!("{}ms", self.response_time_ms)));
+        if let Some(error) = &self.error {
+            let label = match self.event {
+                AlertEvent::Down => "Error",
+                AlertEvent::Recovered => "Last error",
+            };
+            facts.push((label, error.clone()));
+        }
+        facts
+    }
+
+    /// Plain text description of the alert
+    pub fn details(&self) -> String {
+        let mut details = String::new();
+        for (label, value) in self.facts() {
+            let _ = writeln!(details, "{label}: {value}");
+        }
+        details
+    }
+
+    /// How long the monitor has been failing
+    pub fn down_for(&self) -> Duration {
+        (self.timestamp - self.down_since)
+            .to_std()
+            .unwrap_or_default()
+    }
+}
+
+fn format_down_for(duration: Duration) -> String {
+    crate::duration::format_duration(Duration::from_secs(duration.as_secs()))
+}
+
+/// Failure streak of one monitor
+#[derive(Debug, Default)]
+struct Streak {
+    failures: u32,
+    since: Option<DateTime<Utc>>,
+    error_type: Option<String>,
+    error: Option<String>,
+    /// A down alert was raised for this streak
+    alerted: bool,
+}
+
+/// A notifier and the monitors it's sent alerts for
+struct Route {
+    settings: NotifierSettings,
+    notifier: Arc<dyn Notifier>,
+    /// Alerts waiting to be delivered, started with the first alert
+    queue: Option<mpsc::UnboundedSender<Alert>>,
+}
+
+/// How often, and how patiently, a failed delivery is retried
+#[derive(Debug, Clone, Copy)]
+struct RetryPolicy {
+    retries: u32,
+    backoff: Duration,
+}
+
+/// Deliver queued alerts one at a time, so a recovery never overtakes the
+/// down alert it resolves, retrying failures that may pass
+async fn deliver(
+    notifier: Arc<dyn Notifier>,
+    mut alerts: mpsc::UnboundedReceiver<Alert>,
+    retry: RetryPolicy,
+) {
+    while let Some(alert) = alerts.recv().await {
+        let mut backoff = retry.backoff;
+        let mut attempt = 0;
+        loop {
+            match notifier.notify(&alert).await {
+                Ok(()) => break,
+                Err(e) if e.is_retryable() && attempt < retry.retries => {
+                    attempt += 1;
+                    warn!(
+                        "Failed to send alert via {}: {e}, retrying in {:?} ({attempt}/{})",
+                        notifier.name(),
+                        backoff,
+                        retry.retries
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff = backoff.saturating_mul(2);
+                }
+                Err(e) => {
+                    error!("Failed to send alert via {}: {e}", notifier.name());
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Watches check results for monitors going down and coming back, and
+/// sends alerts through the configured notifiers.
+///
+/// A monitor is reported down once its status is down, i.e. it has failed
+/// its own `failure_threshold` checks in a row, and, if set, it has been
+/// failing for `down_for`. Once reported, it's reported recovered when its
+/// status is up again.
+pub struct Alerting {
+    down_for: Option<TimeDelta>,
+    retry: RetryPolicy,
+    routes: Vec<Route>,
+    streaks: HashMap<Uuid, Streak>,
+}
+
+impl Alerting {
+    pub fn new(settings: &AlertingSettings) -> Self {
+        let mut alerting = Self {
+            down_for: None,
+            retry: RetryPolicy {
+                retries: 0,
+                backoff: Duration::ZERO,
+            },
+            routes: Vec::new(),
+            streaks: HashMap::new(),
+        };
+        alerting.configure(settings);
+        alerting
+    }
+
+    /// Switch to changed settings, keeping each monitor's streak. Alerts
+    /// already queued are still delivered through the old notifiers.
+    pub fn configure(&mut self, settings: &AlertingSettings) {
+        self.down_for = settings
+            .down_for
+            .map(|down_for| TimeDelta::from_std(down_for).unwrap_or(TimeDelta::MAX));
+        self.retry = RetryPolicy {
+            retries: settings.delivery_retries,
+            backoff: settings.delivery_backoff,
+        };
+        self.routes = settings
+            .notifiers
+            .iter()
+            .filter_map(|route| match notifiers::build(&route.notifier) {
+                Ok(notifier) => Some(Route {
+                    settings: route.clone(),
+                    notifier,
+                    queue: None,
+                }),
+                Err(e) => {
+                    error!("Skipping notifier: {e}");
+                    None
+                }
+            })
+            .collect();
+    }
+
+    /// Track a finished check of a monitor in `group` that left it in
+    /// `status`, returning the alert it raises, if any.
+    ///
+    /// Alerts follow the monitor's status: it's only reported down once its
+    /// own thresholds are met too, and recovered once its status is up
+    /// again. No down alerts are raised while it's flapping.
+    pub fn observe(
+        &mut self,
+        result: &MonitorResult,
+        group: Option<&str>,
+        status: Status,
+    ) -> Option<Alert> {
+        let streak = self.streaks.entry(result.monitor_id).or_default();
+
+        if result.success {
+            // Still the same outage until the status says otherwise
+            if !status.up {
+                return None;
+            }
+            let finished = std::mem::take(streak);
+            return finished.alerted.then(|| Alert {
+                event: AlertEvent::Recovered,
+                monitor_id: result.monitor_id,
+                monitor_name: result.monitor_name.clone(),
+                url: result.url.clone(),
+                group: group.map(str::to_string),
+                down_since: finished.since.unwrap_or(result.timestamp),
+                timestamp: result.timestamp,
+                failures: finished.failures,
+                status_code: result.status_code,
+                response_time_ms: result.response_time_ms,
+                error_type: finished.error_type,
+                error: finished.error,
+            });
+        }
+
+        streak.failures += 1;
+        let since = *streak.since.get_or_insert(result.timestamp);
+        streak.error_type = result.error.as_ref().map(|e| e.error_type().to_string());
+        streak.error = result.error.as_ref().map(|e| e.to_string());
+
+        if streak.alerted
+            || status.up
+            || status.flapping
+            || self
+                .down_for
+                .is_some_and(|down_for| result.timestamp - since < down_for)
+        {
+            return None;
+        }
+
+        streak.alerted = true;
+        Some(Alert {
+            event: AlertEvent::Down,
+            monitor_id: result.monitor_id,
+            monitor_name: result.monitor_name.clone(),
+            url: result.url.clone(),
+            group: group.map(str::to_string),
+            down_since: since,
+            timestamp: result.timestamp,
+            failures: streak.failures,
+            status_code: result.status_code,
+            response_time_ms: result.response_time_ms,
+            error_type: streak.error_type.clone(),
+            error: streak.error.clone(),
+        })
+    }
+
+    /// Queue `alert` for every notifier it applies to; each notifier
+    /// delivers its alerts in order in the background
+    pub fn notify(&mut self, alert: Alert) {
+        match alert.event {
+            AlertEvent::Down => warn!("ALERT: {}", alert.summary()),
+            AlertEvent::Recovered => info!("ALERT: {}", alert.summary()),
+        }
+
+        let retry = self.retry;
+        for route in &mut self.routes {
+            if !route
+                .settings
+                .applies_to(alert.monitor_id, alert.group.as_deref())
+            {
+                continue;
+            }
+
+            let queue = route.queue.get_or_insert_with(|| {
+                let (sender, receiver) = mpsc::unbounded_channel();
+                tokio::spawn(deliver(route.notifier.clone(), receiver, retry));
+                sender
+            });
+            let _ = queue.send(alert.clone());
+        }
+    }
+
+    /// Forget a removed monitor
+    pub fn remove(&mut self, monitor_id: Uuid) {
+        self.streaks.remove(&monitor_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::CheckError;
+    use crate::notifiers::NotifyError;
+    use crate::settings::{FlappingSettings, NotifierConfig, WebhookConfig};
+    use crate::transitions::StatusTracker;
+    use async_trait::async_trait;
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    fn result(
+        monitor_id: Uuid,
+        timestamp: DateTime<Utc>,
+        error: Option<CheckError>,
+    ) -> MonitorResult {
+        MonitorResult {
+            monitor_name: "Alert Test".to_string(),
+            timestamp,
+            ..MonitorResult::new(monitor_id, error)
+        }
+    }
+
+    /// Track a check of an ungrouped monitor without thresholds of its own
+    fn observe(alerting: &mut Alerting, result: &MonitorResult) -> Option<Alert> {
+        alerting.observe(result, None, Status::of(result))
+    }
+
+    fn alerting(down_for: Option<Duration>) -> Alerting {
+        Alerting::new(&AlertingSettings {
+            down_for,
+            ..AlertingSettings::default()
+        })
+    }
+
+    #[test]
+    fn test_failure_threshold() {
+        let mut alerting = alerting(None);
+        let mut tracker = StatusTracker::new(&FlappingSettings::default());
+        // Reported down after the monitor's failure_threshold of 3
+        let mut observe = |alerting: &mut Alerting, result: &MonitorResult| {
+            let update = tracker.observe(result, 3, 1);
+            alerting.observe(result, None, update.status)
+        };
+        let monitor_id = Uuid::new_v4();
+        let start = Utc::now();
+        let at = |minutes| start + TimeDelta::minutes(minutes);
+
+        assert!(observe(&mut alerting, &result(monitor_id, at(0), None)).is_none());
+        for minute in 1..3 {
+            let failed = result(monitor_id, at(minute), Some(CheckError::ReadTimeout));
+            assert!(observe(&mut alerting, &failed).is_none());
+        }
+
+        let failed = result(monitor_id, at(3), Some(CheckError::HttpStatus(503)));
+        let alert = observe(&mut alerting, &failed).unwrap();
+        assert_eq!(alert.event, AlertEvent::Down);
+        assert_eq!(alert.failures, 3);
+        assert_eq!(alert.down_since, at(1));
+        assert_eq!(alert.error.as_deref(), Some("HTTP 503"));
+        assert_eq!(alert.error_type.as_deref(), Some("http_error"));
+
+        // Alerted once per outage
+        let failed = result(monitor_id, at(4), Some(CheckError::HttpStatus(503)));
+        assert!(observe(&mut alerting, &failed).is_none());
+
+        let alert = observe(&mut alerting, &result(monitor_id, at(5), None)).unwrap();
+        assert_eq!(alert.event, AlertEvent::Recovered);
+        assert_eq!(alert.down_for(), Duration::from_secs(240));
+        assert!(alert.details().contains("Down for: 4m"));
+
+        assert!(observe(&mut alerting, &result(monitor_id, at(6), None)).is_none());
+    }
+
+    #[test]
+    fn test_short_outage_not_reported() {
+        let mut alerting = alerting(None);
+        let mut tracker = StatusTracker::new(&FlappingSettings::default());
+        let mut observe = |alerting: &mut Alerting, result: &MonitorResult| {
+            let update = tracker.observe(result, 2, 1);
+            alerting.observe(result, None, update.status)
+        };
+        let monitor_id = Uuid::new_v4();
+        let now = Utc::now();
+
+        assert!(observe(&mut alerting, &result(monitor_id, now, None)).is_none());
+        let failed = result(monitor_id, now, Some(CheckError::ReadTimeout));
+        assert!(observe(&mut alerting, &failed).is_none());
+        assert!(observe(&mut alerting, &result(monitor_id, now, None)).is_none());
+        assert!(observe(&mut alerting, &failed).is_none());
+    }
+
+    #[test]
+    fn test_down_for() {
+        let mut alerting = alerting(Some(Duration::from_secs(300)));
+        let monitor_id = Uuid::new_v4();
+        let start = Utc::now();
+
+        for minutes in [0, 2, 4] {
+            let failed = result(
+                monitor_id,
+                start + TimeDelta::minutes(minutes),
+                Some(CheckError::ConnectTimeout),
+            );
+            assert!(observe(&mut alerting, &failed).is_none());
+        }
+
+        let failed = result(
+            monitor_id,
+            start + TimeDelta::minutes(6),
+            Some(CheckError::ConnectTimeout),
+        );
+        let alert = observe(&mut alerting, &failed).unwrap();
+        assert_eq!(alert.event, AlertEvent::Down);
+        assert_eq!(alert.failures, 4);
+        assert!(alert.summary().starts_with("[DOWN] Alert Test"));
+    }
+
+    #[test]
+    fn test_alerts_follow_status() {
+        let mut alerting = alerting(None);
+        let monitor_id = Uuid::new_v4();
+        let now = Utc::now();
+        let failed = result(monitor_id, now, Some(CheckError::ReadTimeout));
+        let passed = result(monitor_id, now, None);
+        let status = |up, flapping| Status { up, flapping };
+
+        // Not down until the monitor's own threshold is met, and not while
+        // flapping
+        assert!(alerting
+            .observe(&failed, None, status(true, false))
+            .is_none());
+        assert!(alerting
+            .observe(&failed, None, status(false, true))
+            .is_none());
+        let alert = alerting
+            .observe(&failed, None, status(false, false))
+            .unwrap();
+        assert_eq!(alert.event, AlertEvent::Down);
+        assert_eq!(alert.failures, 3);
+
+        // A pass that doesn't bring the status back up is the same outage
+        assert!(alerting
+            .observe(&passed, None, status(false, false))
+            .is_none());
+        assert!(alerting
+            .observe(&failed, None, status(false, false))
+            .is_none());
+        let alert = alerting
+            .observe(&passed, None, status(true, false))
+            .unwrap();
+        assert_eq!(alert.event, AlertEvent::Recovered);
+        assert_eq!(alert.failures, 4);
+    }
+
+    /// Fails or succeeds as scripted, succeeding once the script runs out,
+    /// and reports each delivered alert
+    struct ScriptedNotifier {
+        script: Mutex<VecDeque<Option<NotifyError>>>,
+        attempts: Mutex<u32>,
+        delivered: mpsc::UnboundedSender<AlertEvent>,
+    }
+
+    #[async_trait]
+    impl Notifier for ScriptedNotifier {
+        fn name(&self) -> String {
+            "scripted".to_string()
+        }
+
+        async fn notify(&self, alert: &Alert) -> Result<(), NotifyError> {
+            *self.attempts.lock().unwrap() += 1;
+            if let Some(Some(error)) = self.script.lock().unwrap().pop_front() {
+                return Err(error);
+            }
+            self.delivered.send(alert.event).unwrap();
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_delivery_retries() {
+        let (sender, mut delivered) = mpsc::unbounded_channel();
+        let notifier = Arc::new(ScriptedNotifier {
+            script: Mutex::new(VecDeque::from([
+                Some(NotifyError::transient("HTTP 503")),
+                Some(NotifyError::transient("HTTP 429")),
+                None,
+                Some(NotifyError::permanent("HTTP 400")),
+            ])),
+            attempts: Mutex::new(0),
+            delivered: sender,
+        });
+
+        let mut alerting = Alerting::new(&AlertingSettings {
+            delivery_retries: 2,
+            delivery_backoff: Duration::from_millis(10),
+            ..AlertingSettings::default()
+        });
+        alerting.routes.push(Route {
+            settings: NotifierSettings {
+                monitors: Vec::new(),
+                groups: Vec::new(),
+                notifier: NotifierConfig::Webhook(WebhookConfig {
+                    url: "http://localhost/hook".to_string(),
+                    headers: Default::default(),
+                    timeout: None,
+                }),
+            },
+            notifier: notifier.clone(),
+            queue: None,
+        });
+
+        let monitor_id = Uuid::new_v4();
+        let now = Utc::now();
+        let failed = result(monitor_id, now, Some(CheckError::ReadTimeout));
+        let down = observe(&mut alerting, &failed).unwrap();
+        let recovered = observe(&mut alerting, &result(monitor_id, now, None)).unwrap();
+        let down_again = observe(&mut alerting, &failed).unwrap();
+
+        // The first alert gets through on its third attempt, the recovery
+        // queued behind it fails for good and the next alert goes out first
+        // time
+        alerting.notify(down);
+        alerting.notify(recovered);
+        alerting.notify(down_again);
+
+        for _ in 0..2 {
+            let event = tokio::time::timeout(Duration::from_secs(5), delivered.recv())
+                .await
+                .unwrap();
+            assert_eq!(event, Some(AlertEvent::Down));
+        }
+        assert_eq!(*notifier.attempts.lock().unwrap(), 5);
+    }
+}
@@ -0,0 +1,1202 @@
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::{mpsc, watch, Mutex};
+use uuid::Uuid;
+
+use crate::history::{HistoryEntry, HistoryStore};
+use crate::secret::{Secret, REDACTED};
+use crate::settings::{AuthConfig, MonitorConfig, MonitorType, Settings};
+use crate::state::{MonitorState, MonitorStates};
+
+/// JSON API for listing and changing monitors while the worker runs.
+///
+/// Changes are published as new [`Settings`] for the worker to apply, and
+/// written back to the settings file when `api.persist` is set.
+pub struct Api {
+    settings: watch::Sender<Settings>,
+    check_requests: mpsc::Sender<Uuid>,
+    states: Arc<MonitorStates>,
+    history: Option<Arc<HistoryStore>>,
+    /// Where changes are written back to when persisting
+    settings_file: Option<PathBuf>,
+    unsaved: Arc<UnsavedChanges>,
+}
+
+/// Monitor changes made through the API that weren't written to the
+/// settings file, by monitor, with `None` for deleted ones.
+///
+/// They're applied over each reloaded version of the file, so they last
+/// until the process restarts.
+#[derive(Default)]
+pub struct UnsavedChanges {
+    monitors: std::sync::Mutex<HashMap<Uuid, Option<MonitorConfig>>>,
+    /// Held while publishing settings, so changes and reloads published at
+    /// the same time don't overwrite each other
+    publishing: Mutex<()>,
+}
+
+/// A monitor's configuration along with the outcome of its latest check
+#[derive(Debug, Serialize)]
+struct MonitorView {
+    #[serde(flatten)]
+    config: MonitorConfig,
+    state: Option<MonitorState>,
+}
+
+/// Query of `GET /api/monitors/:id/history`
+#[derive(Debug, Deserialize)]
+struct HistoryQuery {
+    /// Start of a time range, inclusive
+    from: Option<DateTime<Utc>>,
+    /// End of a time range, exclusive
+    to: Option<DateTime<Utc>>,
+    /// Number of latest results, when no range is given
+    limit: Option<usize>,
+}
+
+/// Results returned when neither a range nor a limit is given
+const DEFAULT_HISTORY_LIMIT: usize = 100;
+
+/// An API error, returned as `{"error": "..."}`
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+
+    fn not_found(monitor_id: Uuid) -> Self {
+        Self::new(
+            StatusCode::NOT_FOUND,
+            format!("monitor {monitor_id} not found"),
+        )
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = Json(serde_json::json!({ "error": self.message }));
+        (self.status, body).into_response()
+    }
+}
+
+type ApiResult<T> = Result<T, ApiError>;
+
+impl Api {
+    pub fn new(
+        settings: watch::Sender<Settings>,
+        check_requests: mpsc::Sender<Uuid>,
+        states: Arc<MonitorStates>,
+        settings_file: Option<PathBuf>,
+    ) -> Self {
+        Self {
+            settings,
+            check_requests,
+            states,
+            history: None,
+            settings_file,
+            unsaved: Arc::default(),
+        }
+    }
+
+    /// Record changes that aren't persisted in `unsaved`, to be applied over
+    /// reloaded settings. Changes are published under its lock, so they don't
+    /// race the reloads published by [`UnsavedChanges::apply_to_reloads`].
+    pub fn with_unsaved_changes(mut self, unsaved: Arc<UnsavedChanges>) -> Self {
+        self.unsaved = unsaved;
+        self
+    }
+
+    /// Serve stored check results from `history`
+    pub fn with_history(mut self, history: Arc<HistoryStore>) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    /// Routes of the API, to be merged into the server's router
+    pub fn router(self) -> Router {
+        let api = Arc::new(self);
+
+        Router::new()
+            .route("/api/monitors", get(list_monitors).post(create_monitor))
+            .route(
+                "/api/monitors/:id",
+                get(get_monitor).put(update_monitor).delete(delete_monitor),
+            )
+            .route("/api/monitors/:id/enable", post(enable_monitor))
+            .route("/api/monitors/:id/disable", post(disable_monitor))
+            .route("/api/monitors/:id/check", post(check_monitor))
+            .route("/api/monitors/:id/history", get(monitor_history))
+            .route_layer(middleware::from_fn_with_state(api.clone(), require_token))
+            .with_state(api)
+    }
+
+    fn view(&self, monitor: &MonitorConfig) -> MonitorView {
+        MonitorView {
+            config: redacted(monitor),
+            state: self.states.get(monitor.id),
+        }
+    }
+
+    fn find(&self, monitor_id: Uuid) -> ApiResult<MonitorView> {
+        self.settings
+            .borrow()
+            .monitors
+            .iter()
+            .find(|monitor| monitor.id == monitor_id)
+            .map(|monitor| self.view(monitor))
+            .ok_or_else(|| ApiError::not_found(monitor_id))
+    }
+
+    /// Apply `change` to a copy of the current settings and, if the result
+    /// is valid, save and publish it
+    async fn change<T>(&self, change: impl FnOnce(&mut Settings) -> ApiResult<T>) -> ApiResult<T> {
+        let _guard = self.unsaved.publishing.lock().await;
+
+        let current = self.settings.borrow().clone();
+        let mut settings = current.clone();
+        let output = change(&mut settings)?;
+
+        settings
+            .validate()
+            .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e.to_string()))?;
+
+        match (settings.api.persist, &self.settings_file) {
+            (true, Some(path)) => {
+                let path = path.clone();
+                settings = tokio::task::spawn_blocking(move || {
+                    settings.save_monitors(&path).map(|()| settings)
+                })
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|saved| saved.map_err(|e| e.to_string()))
+                .map_err(|e| {
+                    ApiError::new(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        format!("failed to save settings: {e}"),
+                    )
+                })?;
+            }
+            _ => self.unsaved.record(&current, &settings),
+        }
+
+        self.settings.send_replace(settings);
+        Ok(output)
+    }
+
+    /// Set whether a monitor is checked on its schedule
+    async fn set_enabled(&self, monitor_id: Uuid, enabled: bool) -> ApiResult<Json<MonitorView>> {
+        let monitor = self
+            .change(|settings| {
+                let monitor = find_mut(settings, monitor_id)?;
+                monitor.enabled = enabled;
+                Ok(monitor.clone())
+            })
+            .await?;
+
+        info!(
+            "API: {} monitor {} ({})",
+            if enabled { "enabled" } else { "disabled" },
+            monitor.name,
+            monitor.id
+        );
+        Ok(Json(self.view(&monitor)))
+    }
+}
+
+impl UnsavedChanges {
+    /// Remember how the monitors of `after` differ from those of `before`
+    fn record(&self, before: &Settings, after: &Settings) {
+        let mut changes = self.monitors.lock().unwrap();
+
+        for monitor in &after.monitors {
+            if !before.monitors.contains(monitor) {
+                changes.insert(monitor.id, Some(monitor.clone()));
+            }
+        }
+        for monitor in &before.monitors {
+            if !after.monitors.iter().any(|m| m.id == monitor.id) {
+                changes.insert(monitor.id, None);
+            }
+        }
+    }
+
+    /// Apply the changes over `settings`, replacing monitors with the same
+    /// id
+    fn apply(&self, settings: &mut Settings) {
+        for (id, change) in self.monitors.lock().unwrap().iter() {
+            let index = settings.monitors.iter().position(|m| m.id == *id);
+            match (change, index) {
+                (Some(monitor), Some(index)) => settings.monitors[index] = monitor.clone(),
+                (Some(monitor), None) => settings.monitors.push(monitor.clone()),
+                (None, Some(index)) => {
+                    settings.monitors.remove(index);
+                }
+                (None, None) => {}
+            }
+        }
+    }
+
+    /// Publish each version of the settings file from `reloads` on
+    /// `updates` with the changes applied over it. Returns once either
+    /// side is gone.
+    pub async fn apply_to_reloads(
+        self: Arc<Self>,
+        mut reloads: watch::Receiver<Settings>,
+        updates: watch::Sender<Settings>,
+    ) {
+        loop {
+            tokio::select! {
+                changed = reloads.changed() => {
+                    if changed.is_err() {
+                        return;
+                    }
+                }
+                _ = updates.closed() => return,
+            }
+
+            let _guard = self.publishing.lock().await;
+            let mut settings = reloads.borrow_and_update().clone();
+            self.apply(&mut settings);
+            updates.send_replace(settings);
+        }
+    }
+}
+
+/// Reject requests without the configured bearer token, if there is one
+async fn require_token(State(api): State<Arc<Api>>, request: Request, next: Next) -> Response {
+    let token = api.settings.borrow().api.token.clone();
+
+    if let Some(token) = token {
+        let expected = match token.resolve() {
+            Ok(expected) => expected,
+            Err(e) => {
+                return ApiError::new(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("failed to read api.token: {e}"),
+                )
+                .into_response()
+            }
+        };
+
+        let presented = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .unwrap_or_default();
+
+        // Compare in constant time so the token can't be guessed byte by byte
+        let matches = presented.len() == expected.len()
+            && openssl::memcmp::eq(presented.as_bytes(), expected.as_bytes());
+        if !matches {
+            return ApiError::new(StatusCode::UNAUTHORIZED, "missing or invalid token")
+                .into_response();
+        }
+    }
+
+    next.run(request).await
+}
+
+async fn list_monitors(State(api): State<Arc<Api>>) -> Json<Vec<MonitorView>> {
+    let settings = api.settings.borrow().clone();
+    Json(
+        settings
+            .monitors
+            .iter()
+            .map(|monitor| api.view(monitor))
+            .collect(),
+    )
+}
+
+async fn get_monitor(
+    State(api): State<Arc<Api>>,
+    Path(monitor_id): Path<Uuid>,
+) -> ApiResult<Json<MonitorView>> {
+    api.find(monitor_id).map(Json)
+}
+
+async fn create_monitor(
+    State(api): State<Arc<Api>>,
+    Json(mut body): Json<serde_json::Value>,
+) -> ApiResult<(StatusCode, Json<MonitorView>)> {
+    // New monitors get a generated id unless the client picks one
+    if let Some(fields) = body.as_object_mut() {
+        fields
+            .entry("id")
+            .or_insert_with(|| Uuid::new_v4().to_string().into());
+    }
+    let mut monitor = parse_monitor(body)?;
+    unmask_secrets(&mut monitor, None)?;
+    check_references(&monitor, None)?;
+
+    let monitor = api
+        .change(|settings| {
+            if settings.monitors.iter().any(|m| m.id == monitor.id) {
+                return Err(ApiError::new(
+                    StatusCode::CONFLICT,
+                    format!("monitor {} already exists", monitor.id),
+                ));
+            }
+            settings.monitors.push(monitor.clone());
+            Ok(monitor)
+        })
+        .await?;
+
+    info!("API: created monitor {} ({})", monitor.name, monitor.id);
+    Ok((StatusCode::CREATED, Json(api.view(&monitor))))
+}
+
+async fn update_monitor(
+    State(api): State<Arc<Api>>,
+    Path(monitor_id): Path<Uuid>,
+    Json(mut body): Json<serde_json::Value>,
+) -> ApiResult<Json<MonitorView>> {
+    if let Some(fields) = body.as_object_mut() {
+        match fields.get("id") {
+            None => {
+                fields.insert("id".to_string(), monitor_id.to_string().into());
+            }
+            Some(id) if id.as_str() == Some(monitor_id.to_string().as_str()) => {}
+            Some(_) => {
+                return Err(ApiError::new(
+                    StatusCode::BAD_REQUEST,
+                    "id in the body doesn't match the URL",
+                ))
+            }
+        }
+    }
+    let mut monitor = parse_monitor(body)?;
+
+    let monitor = api
+        .change(|settings| {
+            let stored = find_mut(settings, monitor_id)?;
+            unmask_secrets(&mut monitor, Some(stored))?;
+            check_references(&monitor, Some(stored))?;
+            *stored = monitor.clone();
+            Ok(monitor)
+        })
+        .await?;
+
+    info!("API: updated monitor {} ({})", monitor.name, monitor.id);
+    Ok(Json(api.view(&monitor)))
+}
+
+async fn delete_monitor(
+    State(api): State<Arc<Api>>,
+    Path(monitor_id): Path<Uuid>,
+) -> ApiResult<StatusCode> {
+    let monitor = api
+        .change(|settings| {
+            let index = settings
+                .monitors
+                .iter()
+                .position(|monitor| monitor.id == monitor_id)
+                .ok_or_else(|| ApiError::not_found(monitor_id))?;
+            Ok(settings.monitors.remove(index))
+        })
+        .await?;
+
+    info!("API: deleted monitor {} ({})", monitor.name, monitor.id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn enable_monitor(
+    State(api): State<Arc<Api>>,
+    Path(monitor_id): Path<Uuid>,
+) -> ApiResult<Json<MonitorView>> {
+    api.set_enabled(monitor_id, true).await
+}
+
+async fn disable_monitor(
+    State(api): State<Arc<Api>>,
+    Path(monitor_id): Path<Uuid>,
+) -> ApiResult<Json<MonitorView>> {
+    api.set_enabled(monitor_id, false).await
+}
+
+/// Queue an immediate check; its outcome shows up in the monitor's state
+async fn check_monitor(
+    State(api): State<Arc<Api>>,
+    Path(monitor_id): Path<Uuid>,
+) -> ApiResult<StatusCode> {
+    if api.find(monitor_id)?.config.kind == MonitorType::Heartbeat {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            "heartbeat monitors are pinged by their job and can't be checked",
+        ));
+    }
+
+    api.check_requests
+        .send(monitor_id)
+        .await
+        .map_err(|_| ApiError::new(StatusCode::SERVICE_UNAVAILABLE, "worker is not running"))?;
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Stored results of a monitor: those in the `from`..`to` range oldest
+/// first, or otherwise the latest `limit` newest first
+async fn monitor_history(
+    State(api): State<Arc<Api>>,
+    Path(monitor_id): Path<Uuid>,
+    Query(query): Query<HistoryQuery>,
+) -> ApiResult<Json<Vec<HistoryEntry>>> {
+    let history = api
+        .history
+        .clone()
+        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, "history is not enabled"))?;
+
+    let entries = tokio::task::spawn_blocking(move || {
+        if query.from.is_some() || query.to.is_some() {
+            history.between(
+                monitor_id,
+                query.from.unwrap_or(DateTime::<Utc>::MIN_UTC),
+                query.to.unwrap_or(DateTime::<Utc>::MAX_UTC),
+            )
+        } else {
+            history.latest(monitor_id, query.limit.unwrap_or(DEFAULT_HISTORY_LIMIT))
+        }
+    })
+    .await
+    .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(Json(entries))
+}
+
+fn parse_monitor(body: serde_json::Value) -> ApiResult<MonitorConfig> {
+    serde_json::from_value(body)
+        .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, format!("invalid monitor: {e}")))
+}
+
+fn find_mut(settings: &mut Settings, monitor_id: Uuid) -> ApiResult<&mut MonitorConfig> {
+    settings
+        .monitors
+        .iter_mut()
+        .find(|monitor| monitor.id == monitor_id)
+        .ok_or_else(|| ApiError::not_found(monitor_id))
+}
+
+/// A copy of `monitor` safe to return, with inline secret values masked
+fn redacted(monitor: &MonitorConfig) -> MonitorConfig {
+    let mut monitor = monitor.clone();
+
+    for value in monitor.request.headers.values_mut() {
+        *value = value.redacted();
+    }
+
+    match &mut monitor.request.auth {
+        Some(AuthConfig::Basic {
+            password: Some(password),
+            ..
+        }) => *password = password.redacted(),
+        Some(AuthConfig::Bearer { token }) => *token = token.redacted(),
+        _ => {}
+    }
+
+    monitor
+}
+
+/// Put back the stored values of secrets sent as the `"***"` they're
+/// shown as, so a monitor read and written back keeps its secrets
+fn unmask_secrets(monitor: &mut MonitorConfig, stored: Option<&MonitorConfig>) -> ApiResult<()> {
+    let no_stored_value = |field: String| {
+        ApiError::new(
+            StatusCode::BAD_REQUEST,
+            format!("{field} is \"{REDACTED}\" but has no stored value to keep"),
+        )
+    };
+    let stored = stored.map(|stored| &stored.request);
+
+    for (name, value) in &mut monitor.request.headers {
+        if value.is_redacted() {
+            *value = stored
+                .and_then(|stored| stored.headers.get(name))
+                .cloned()
+                .ok_or_else(|| no_stored_value(format!("header {name}")))?;
+        }
+    }
+
+    let stored_auth = stored.and_then(|stored| stored.auth.as_ref());
+    match &mut monitor.request.auth {
+        Some(AuthConfig::Basic {
+            password: Some(password),
+            ..
+        }) if password.is_redacted() => match stored_auth {
+            Some(AuthConfig::Basic {
+                password: Some(stored),
+                ..
+            }) => *password = stored.clone(),
+            _ => return Err(no_stored_value("auth password".to_string())),
+        },
+        Some(AuthConfig::Bearer { token }) if token.is_redacted() => match stored_auth {
+            Some(AuthConfig::Bearer { token: stored }) => *token = stored.clone(),
+            _ => return Err(no_stored_value("auth token".to_string())),
+        },
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Reject references to files and environment variables that the stored
+/// monitor doesn't already make, so API clients can't have the host's files
+/// or environment read into requests. Those are only set in the settings
+/// file.
+fn check_references(monitor: &MonitorConfig, stored: Option<&MonitorConfig>) -> ApiResult<()> {
+    let settings_file_only = |field: &str| {
+        ApiError::new(
+            StatusCode::BAD_REQUEST,
+            format!(
+                "{field} can only refer to files or environment variables in the settings file"
+            ),
+        )
+    };
+    let (request, tls) = (&monitor.request, &monitor.client.tls);
+    let stored_request = stored.map(|stored| &stored.request);
+    let stored_tls = stored.map(|stored| &stored.client.tls);
+
+    for (field, path, stored_path) in [
+        (
+            "body_file",
+            &request.body_file,
+            stored_request.and_then(|stored| stored.body_file.as_ref()),
+        ),
+        (
+            "tls.ca_bundle",
+            &tls.ca_bundle,
+            stored_tls.and_then(|stored| stored.ca_bundle.as_ref()),
+        ),
+        (
+            "tls.client_cert",
+            &tls.client_cert,
+            stored_tls.and_then(|stored| stored.client_cert.as_ref()),
+        ),
+        (
+            "tls.client_key",
+            &tls.client_key,
+            stored_tls.and_then(|stored| stored.client_key.as_ref()),
+        ),
+    ] {
+        if path.is_some() && path.as_ref() != stored_path {
+            return Err(settings_file_only(field));
+        }
+    }
+
+    let is_reference = |secret: &Secret| !matches!(secret, Secret::Plain(_));
+
+    for (name, value) in &request.headers {
+        let stored_value = stored_request.and_then(|stored| stored.headers.get(name));
+        if is_reference(value) && stored_value != Some(value) {
+            return Err(settings_file_only(&format!("header {name}")));
+        }
+    }
+
+    if let Some((field, secret)) = auth_secret(request.auth.as_ref()) {
+        let stored_secret = auth_secret(stored_request.and_then(|stored| stored.auth.as_ref()));
+        if is_reference(secret) && stored_secret != Some((field, secret)) {
+            return Err(settings_file_only(field));
+        }
+    }
+
+    Ok(())
+}
+
+/// The secret of `auth`, if it has one, along with its field
+fn auth_secret(auth: Option<&AuthConfig>) -> Option<(&'static str, &Secret)> {
+    match auth {
+        Some(AuthConfig::Basic {
+            password: Some(password),
+            ..
+        }) => Some(("auth password", password)),
+        Some(AuthConfig::Bearer { token }) => Some(("auth token", token)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::HistorySettings;
+    use crate::worker::MonitorResult;
+    use serde_json::{json, Value};
+    use std::str::FromStr;
+    use std::time::Duration;
+    use tempfile::{NamedTempFile, TempDir};
+    use tokio::net::TcpListener;
+
+    const MONITOR_ID: &str = "550e8400-e29b-41d4-a716-446655440000";
+
+    const SETTINGS: &str = r#"
+[[monitors]]
+id = "550e8400-e29b-41d4-a716-446655440000"
+name = "Existing"
+url = "https://existing.example.com"
+interval = "1m"
+enabled = true
+
+[monitors.headers]
+X-Api-Key = "hunter2"
+"#;
+
+    struct TestApi {
+        url: String,
+        updates: watch::Receiver<Settings>,
+        check_requests: mpsc::Receiver<Uuid>,
+        client: reqwest::Client,
+    }
+
+    async fn serve(contents: &str, settings_file: Option<PathBuf>) -> TestApi {
+        serve_with(contents, settings_file, |api| api).await
+    }
+
+    async fn serve_with(
+        contents: &str,
+        settings_file: Option<PathBuf>,
+        configure: impl FnOnce(Api) -> Api,
+    ) -> TestApi {
+        let (sender, updates) = watch::channel(Settings::from_str(contents).unwrap());
+        let (check_sender, check_requests) = mpsc::channel(1);
+        let api = configure(Api::new(
+            sender,
+            check_sender,
+            Arc::new(MonitorStates::new()),
+            settings_file,
+        ));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/api/monitors", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, api.router()).await });
+
+        TestApi {
+            url,
+            updates,
+            check_requests,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    async fn json(response: reqwest::Response) -> Value {
+        response.json().await.expect("JSON body")
+    }
+
+    #[tokio::test]
+    async fn test_list_and_get_monitors() {
+        let api = serve(SETTINGS, None).await;
+
+        let response = api.client.get(&api.url).send().await.unwrap();
+        assert_eq!(response.status(), 200);
+        let monitors = json(response).await;
+        assert_eq!(monitors.as_array().unwrap().len(), 1);
+        assert_eq!(monitors[0]["name"], "Existing");
+        assert_eq!(monitors[0]["interval"], "1m");
+        assert_eq!(monitors[0]["state"], Value::Null);
+        // Inline secrets are masked
+        assert_eq!(monitors[0]["headers"]["X-Api-Key"], "***");
+
+        let response = api
+            .client
+            .get(format!("{}/{MONITOR_ID}", api.url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(json(response).await["id"], MONITOR_ID);
+
+        let response = api
+            .client
+            .get(format!("{}/{}", api.url, Uuid::new_v4()))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+        assert!(json(response).await["error"]
+            .as_str()
+            .unwrap()
+            .contains("not found"));
+    }
+
+    #[tokio::test]
+    async fn test_create_update_delete_monitor() {
+        let mut api = serve(SETTINGS, None).await;
+
+        let response = api
+            .client
+            .post(&api.url)
+            .json(&json!({
+                "name": "Created",
+                "url": "https://created.example.com",
+                "interval": "30s",
+                "enabled": true,
+                "assert": { "status": [200] }
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 201);
+        let created = json(response).await;
+        let id: Uuid = created["id"].as_str().unwrap().parse().unwrap();
+
+        assert!(api.updates.has_changed().unwrap());
+        let settings = api.updates.borrow_and_update().clone();
+        assert_eq!(settings.monitors.len(), 2);
+        assert_eq!(settings.monitors[1].interval, Duration::from_secs(30));
+        assert_eq!(settings.monitors[1].assertions.status, vec![200]);
+
+        let response = api
+            .client
+            .put(format!("{}/{id}", api.url))
+            .json(&json!({
+                "name": "Renamed",
+                "url": "https://created.example.com",
+                "interval": "30s",
+                "enabled": true
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(json(response).await["name"], "Renamed");
+        assert_eq!(api.updates.borrow_and_update().monitors[1].name, "Renamed");
+
+        let response = api
+            .client
+            .post(format!("{}/{id}/disable", api.url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(json(response).await["enabled"], false);
+        assert!(!api.updates.borrow_and_update().monitors[1].enabled);
+
+        let response = api
+            .client
+            .delete(format!("{}/{id}", api.url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 204);
+        assert_eq!(api.updates.borrow_and_update().monitors.len(), 1);
+
+        let response = api
+            .client
+            .delete(format!("{}/{id}", api.url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+    }
+
+    #[tokio::test]
+    async fn test_masked_secrets_kept() {
+        let contents =
+            format!("{SETTINGS}\n[monitors.auth]\ntype = \"bearer\"\ntoken = \"t0ken\"\n");
+        let mut api = serve(&contents, None).await;
+        let url = format!("{}/{MONITOR_ID}", api.url);
+
+        // Read a monitor and write it back with a changed name
+        let mut monitor = json(api.client.get(&url).send().await.unwrap()).await;
+        assert_eq!(monitor["headers"]["X-Api-Key"], "***");
+        assert_eq!(monitor["auth"]["token"], "***");
+        monitor["name"] = json!("Renamed");
+
+        let response = api.client.put(&url).json(&monitor).send().await.unwrap();
+        assert_eq!(response.status(), 200);
+
+        let settings = api.updates.borrow_and_update().clone();
+        let request = &settings.monitors[0].request;
+        assert_eq!(settings.monitors[0].name, "Renamed");
+        assert_eq!(
+            request.headers["X-Api-Key"],
+            Secret::Plain("hunter2".to_string())
+        );
+        assert_eq!(
+            request.auth,
+            Some(AuthConfig::Bearer {
+                token: Secret::Plain("t0ken".to_string())
+            })
+        );
+
+        // Masked values of secrets the monitor didn't have are rejected
+        monitor["headers"]["X-Other"] = json!("***");
+        let response = api.client.put(&url).json(&monitor).send().await.unwrap();
+        assert_eq!(response.status(), 400);
+
+        let mut created = monitor.clone();
+        created.as_object_mut().unwrap().remove("id");
+        created["headers"] = json!({ "X-Api-Key": "***" });
+        let response = api
+            .client
+            .post(&api.url)
+            .json(&created)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+        assert!(!api.updates.has_changed().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_file_references_rejected() {
+        let file = NamedTempFile::new().expect("Failed to create temp file");
+        let path = file.path().display().to_string();
+        let contents = format!(
+            "{SETTINGS}\n[monitors.auth]\ntype = \"bearer\"\ntoken = {{ file = \"{path}\" }}\n"
+        );
+        let mut api = serve(&contents, None).await;
+        let url = format!("{}/{MONITOR_ID}", api.url);
+        let monitor = json(api.client.get(&url).send().await.unwrap()).await;
+
+        let mut created = monitor.clone();
+        created.as_object_mut().unwrap().remove("id");
+        created.as_object_mut().unwrap().remove("auth");
+        created["headers"] = json!({});
+        for (field, value) in [
+            ("body_file", json!(path)),
+            ("tls", json!({ "ca_bundle": path })),
+            ("tls", json!({ "client_cert": path, "client_key": path })),
+            ("headers", json!({ "X-Secret": { "file": path } })),
+            ("headers", json!({ "X-Secret": { "env": "HOME" } })),
+            (
+                "auth",
+                json!({ "type": "bearer", "token": { "file": path } }),
+            ),
+        ] {
+            let mut body = created.clone();
+            body[field] = value;
+            let response = api.client.post(&api.url).json(&body).send().await.unwrap();
+            assert_eq!(response.status(), 400, "{field} should be rejected");
+            assert!(json(response).await["error"]
+                .as_str()
+                .unwrap()
+                .contains("settings file"));
+        }
+
+        // References the monitor already makes may be sent back
+        let response = api.client.put(&url).json(&monitor).send().await.unwrap();
+        assert_eq!(response.status(), 200);
+        api.updates.borrow_and_update();
+
+        let mut changed = monitor.clone();
+        changed["auth"]["token"] = json!({ "file": "/etc/shadow" });
+        let response = api.client.put(&url).json(&changed).send().await.unwrap();
+        assert_eq!(response.status(), 400);
+        assert!(!api.updates.has_changed().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_changes_rejected() {
+        let mut api = serve(SETTINGS, None).await;
+
+        // Zero interval fails validation
+        let response = api
+            .client
+            .post(&api.url)
+            .json(&json!({
+                "name": "Invalid",
+                "url": "https://invalid.example.com",
+                "interval": "0s",
+                "enabled": true
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+
+        // Missing fields
+        let response = api
+            .client
+            .post(&api.url)
+            .json(&json!({ "name": "Incomplete" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+
+        let response = api
+            .client
+            .post(&api.url)
+            .json(&json!({
+                "id": MONITOR_ID,
+                "name": "Duplicate",
+                "url": "https://duplicate.example.com",
+                "interval": "1m",
+                "enabled": true
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 409);
+
+        let response = api
+            .client
+            .put(format!("{}/{MONITOR_ID}", api.url))
+            .json(&json!({
+                "id": Uuid::new_v4(),
+                "name": "Mismatched",
+                "url": "https://existing.example.com",
+                "interval": "1m",
+                "enabled": true
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+
+        assert!(!api.updates.has_changed().unwrap());
+        assert_eq!(api.updates.borrow_and_update().monitors.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_check_monitor() {
+        let mut api = serve(SETTINGS, None).await;
+
+        let response = api
+            .client
+            .post(format!("{}/{MONITOR_ID}/check", api.url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 202);
+        assert_eq!(
+            api.check_requests.recv().await,
+            Some(MONITOR_ID.parse().unwrap())
+        );
+
+        let response = api
+            .client
+            .post(format!("{}/{}/check", api.url, Uuid::new_v4()))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+
+        let heartbeat_id = Uuid::new_v4();
+        let mut api = serve(
+            &format!(
+                "[[monitors]]\nid = \"{heartbeat_id}\"\nname = \"Backup\"\ntype = \"heartbeat\"\ninterval = \"1d\"\nenabled = true\n"
+            ),
+            None,
+        )
+        .await;
+        let response = api
+            .client
+            .post(format!("{}/{heartbeat_id}/check", api.url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 409);
+        assert!(api.check_requests.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_monitor_history() {
+        let api = serve(SETTINGS, None).await;
+        let response = api
+            .client
+            .get(format!("{}/{MONITOR_ID}/history", api.url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+
+        let dir = TempDir::new().unwrap();
+        let history = Arc::new(
+            HistoryStore::open(&HistorySettings::new(dir.path().join("history.db"))).unwrap(),
+        );
+        let start: DateTime<Utc> = "2024-01-01T10:00:00Z".parse().unwrap();
+        for minute in 0..3 {
+            history
+                .record(&MonitorResult {
+                    response_time_ms: 100 + minute,
+                    status_code: Some(200),
+                    timestamp: start + chrono::Duration::minutes(minute as i64),
+                    ..MonitorResult::new(MONITOR_ID.parse().unwrap(), None)
+                })
+                .unwrap();
+        }
+
+        let api = serve_with(SETTINGS, None, |api| api.with_history(history)).await;
+        let latest = json(
+            api.client
+                .get(format!("{}/{MONITOR_ID}/history?limit=2", api.url))
+                .send()
+                .await
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(latest.as_array().unwrap().len(), 2);
+        assert_eq!(latest[0]["response_time_ms"], 102);
+
+        let range = json(
+            api.client
+                .get(format!(
+                    "{}/{MONITOR_ID}/history?from=2024-01-01T10:01:00Z&to=2024-01-01T11:00:00Z",
+                    api.url
+                ))
+                .send()
+                .await
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(range.as_array().unwrap().len(), 2);
+        assert_eq!(range[0]["response_time_ms"], 101);
+        assert_eq!(range[0]["checks"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_token_required() {
+        let api = serve(
+            &format!("{SETTINGS}\n[api]\nenabled = true\ntoken = \"s3cret\"\n"),
+            None,
+        )
+        .await;
+
+        let response = api.client.get(&api.url).send().await.unwrap();
+        assert_eq!(response.status(), 401);
+
+        let response = api
+            .client
+            .get(&api.url)
+            .bearer_auth("wrong")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 401);
+
+        let response = api
+            .client
+            .get(&api.url)
+            .bearer_auth("s3cret")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+    }
+
+    #[tokio::test]
+    async fn test_unsaved_changes_survive_reload() {
+        let unsaved = Arc::new(UnsavedChanges::default());
+        let mut api = serve_with(SETTINGS, None, |api| {
+            api.with_unsaved_changes(unsaved.clone())
+        })
+        .await;
+
+        let response = api
+            .client
+            .post(format!("{}/{MONITOR_ID}/disable", api.url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let response = api
+            .client
+            .post(&api.url)
+            .json(&json!({
+                "name": "Created",
+                "url": "https://created.example.com",
+                "interval": "30s",
+                "enabled": true
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 201);
+        api.updates.borrow_and_update();
+
+        // The file is reloaded with a new monitor added to it
+        let added_id = Uuid::new_v4();
+        let reloaded = format!(
+            "{SETTINGS}\n[[monitors]]\nid = \"{added_id}\"\nname = \"Added\"\nurl = \"https://added.example.com\"\ninterval = \"1m\"\nenabled = true\n"
+        );
+        let (reload_sender, reloads) = watch::channel(Settings::from_str(SETTINGS).unwrap());
+        let (update_sender, mut updates) = watch::channel(Settings::from_str(SETTINGS).unwrap());
+        tokio::spawn(unsaved.apply_to_reloads(reloads, update_sender));
+        reload_sender.send_replace(Settings::from_str(&reloaded).unwrap());
+
+        updates.changed().await.unwrap();
+        let settings = updates.borrow().clone();
+        let names: Vec<_> = settings.monitors.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["Existing", "Added", "Created"]);
+        assert!(!settings.monitors[0].enabled);
+    }
+
+    #[tokio::test]
+    async fn test_reload_during_change_not_lost() {
+        let unsaved = Arc::new(UnsavedChanges::default());
+        let (sender, mut updates) = watch::channel(Settings::from_str(SETTINGS).unwrap());
+        let (reload_sender, reloads) = watch::channel(Settings::from_str(SETTINGS).unwrap());
+        let (check_sender, _check_requests) = mpsc::channel(1);
+        let api = Api::new(
+            sender.clone(),
+            check_sender,
+            Arc::new(MonitorStates::new()),
+            None,
+        )
+        .with_unsaved_changes(unsaved.clone());
+        tokio::spawn(unsaved.clone().apply_to_reloads(reloads, sender));
+
+        // The file is reloaded while a change is being published
+        let publishing = unsaved.publishing.lock().await;
+        let reloaded = format!(
+            "{SETTINGS}\n[[monitors]]\nid = \"{}\"\nname = \"Added\"\nurl = \"https://added.example.com\"\ninterval = \"1m\"\nenabled = true\n",
+            Uuid::new_v4()
+        );
+        reload_sender.send_replace(Settings::from_str(&reloaded).unwrap());
+        let change = tokio::spawn(async move {
+            api.set_enabled(MONITOR_ID.parse().unwrap(), false)
+                .await
+                .map(|_| ())
+        });
+        tokio::task::yield_now().await;
+        drop(publishing);
+        change.await.unwrap().unwrap();
+
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            updates
+                .wait_for(|settings| settings.monitors.len() == 2 && !settings.monitors[0].enabled),
+        )
+        .await
+        .expect("both the reload and the change are published")
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_changes_persisted() {
+        let contents = format!("{SETTINGS}\n[api]\npersist = true\n");
+        let file = NamedTempFile::new().expect("Failed to create temp file");
+        std::fs::write(file.path(), &contents).unwrap();
+
+        let api = serve(&contents, Some(file.path().to_path_buf())).await;
+
+        let response = api
+            .client
+            .post(format!("{}/{MONITOR_ID}/disable", api.url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+
+        let saved = Settings::load(&file.path().to_path_buf()).unwrap();
+        assert!(!saved.monitors[0].enabled);
+        // The inline secret is written back as it was, not masked
+        assert!(std::fs::read_to_string(file.path())
+            .unwrap()
+            .contains("hunter2"));
+    }
+}
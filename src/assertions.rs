@@ -0,0 +1,522 @@
+use regex::Regex;
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+/// Checks applied to a response before a monitor counts as healthy.
+///
+/// Configured per monitor under `[monitors.assert]`. Without any assertions
+/// a response is healthy when its status is 2xx.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ResponseAssertions {
+    /// Accepted status codes, replacing the default 2xx check
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub status: Vec<u16>,
+    /// Substrings that must all appear in the body
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub body_contains: Vec<String>,
+    /// Substrings that must not appear in the body
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub body_not_contains: Vec<String>,
+    /// Regular expression the body must match
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body_regex: Option<BodyRegex>,
+    /// Checks against values in a JSON body
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub json: Vec<JsonAssertion>,
+    /// Checks against response headers
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub headers: Vec<HeaderAssertion>,
+}
+
+/// A `body_regex`, compiled once when the settings load rather than on
+/// every check
+#[derive(Debug, Clone)]
+pub struct BodyRegex(Regex);
+
+impl BodyRegex {
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        Regex::new(pattern)
+            .map(Self)
+            .map_err(|e| format!("invalid body_regex: {e}"))
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+
+    fn is_match(&self, body: &str) -> bool {
+        self.0.is_match(body)
+    }
+}
+
+impl PartialEq for BodyRegex {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Serialize for BodyRegex {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for BodyRegex {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let pattern = String::deserialize(deserializer)?;
+        BodyRegex::new(&pattern).map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct JsonAssertion {
+    /// Path to the value, e.g. `$.status` or `$.items[0].name`
+    pub path: String,
+    /// Expected value; when omitted the path only has to exist
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub equals: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct HeaderAssertion {
+    /// Header name, matched case-insensitively
+    pub name: String,
+    /// Exact expected value
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub equals: Option<String>,
+    /// Substring the value must contain
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contains: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssertionKind {
+    Status,
+    Body,
+    Json,
+    Header,
+}
+
+/// A response that did not satisfy a monitor's assertions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssertionFailure {
+    pub kind: AssertionKind,
+    pub message: String,
+}
+
+impl AssertionFailure {
+    fn new(kind: AssertionKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    /// Value of the `error_type` metric label for this failure
+    pub fn error_type(&self) -> &'static str {
+        match self.kind {
+            AssertionKind::Status => "assertion_failed_status",
+            AssertionKind::Body => "assertion_failed_body",
+            AssertionKind::Json => "assertion_failed_json",
+            AssertionKind::Header => "assertion_failed_header",
+        }
+    }
+}
+
+impl fmt::Display for AssertionFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl ResponseAssertions {
+    /// Whether no assertions are configured
+    pub fn is_empty(&self) -> bool {
+        *self == ResponseAssertions::default()
+    }
+
+    /// Check that every JSON path can be used; `body_regex` is checked as
+    /// it's compiled
+    pub fn validate(&self) -> Result<(), String> {
+        for assertion in &self.json {
+            parse_json_path(&assertion.path)?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether the status list replaces the default 2xx check
+    pub fn has_status(&self) -> bool {
+        !self.status.is_empty()
+    }
+
+    /// Whether any assertion needs the response body
+    pub fn needs_body(&self) -> bool {
+        !self.body_contains.is_empty()
+            || !self.body_not_contains.is_empty()
+            || self.body_regex.is_some()
+            || !self.json.is_empty()
+    }
+
+    pub fn check_status(&self, status: StatusCode) -> Result<(), AssertionFailure> {
+        if self.status.contains(&status.as_u16()) {
+            return Ok(());
+        }
+
+        Err(AssertionFailure::new(
+            AssertionKind::Status,
+            format!(
+                "HTTP {}, expected one of {:?}",
+                status.as_u16(),
+                self.status
+            ),
+        ))
+    }
+
+    pub fn check_headers(&self, headers: &HeaderMap) -> Result<(), AssertionFailure> {
+        for assertion in &self.headers {
+            let Some(value) = headers.get(assertion.name.as_str()) else {
+                return Err(AssertionFailure::new(
+                    AssertionKind::Header,
+                    format!("header {} missing", assertion.name),
+                ));
+            };
+            let value = value.to_str().unwrap_or_default();
+
+            if let Some(expected) = &assertion.equals {
+                if value != expected {
+                    return Err(AssertionFailure::new(
+                        AssertionKind::Header,
+                        format!(
+                            "header {} is \"{value}\", expected \"{expected}\"",
+                            assertion.name
+                        ),
+                    ));
+                }
+            }
+
+            if let Some(expected) = &assertion.contains {
+                if !value.contains(expected.as_str()) {
+                    return Err(AssertionFailure::new(
+                        AssertionKind::Header,
+                        format!(
+                            "header {} is \"{value}\", expected it to contain \"{expected}\"",
+                            assertion.name
+                        ),
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn check_body(&self, body: &str) -> Result<(), AssertionFailure> {
+        for expected in &self.body_contains {
+            if !body.contains(expected.as_str()) {
+                return Err(AssertionFailure::new(
+                    AssertionKind::Body,
+                    format!("body does not contain \"{expected}\""),
+                ));
+            }
+        }
+
+        for forbidden in &self.body_not_contains {
+            if body.contains(forbidden.as_str()) {
+                return Err(AssertionFailure::new(
+                    AssertionKind::Body,
+                    format!("body contains \"{forbidden}\""),
+                ));
+            }
+        }
+
+        if let Some(regex) = &self.body_regex {
+            if !regex.is_match(body) {
+                return Err(AssertionFailure::new(
+                    AssertionKind::Body,
+                    format!("body does not match /{}/", regex.as_str()),
+                ));
+            }
+        }
+
+        if !self.json.is_empty() {
+            self.check_json(body)?;
+        }
+
+        Ok(())
+    }
+
+    fn check_json(&self, body: &str) -> Result<(), AssertionFailure> {
+        let document: serde_json::Value = serde_json::from_str(body).map_err(|e| {
+            AssertionFailure::new(AssertionKind::Json, format!("body is not valid JSON: {e}"))
+        })?;
+
+        for assertion in &self.json {
+            let value = lookup_json_path(&document, &assertion.path)
+                .map_err(|e| AssertionFailure::new(AssertionKind::Json, e))?;
+
+            let Some(value) = value else {
+                return Err(AssertionFailure::new(
+                    AssertionKind::Json,
+                    format!("{} not found", assertion.path),
+                ));
+            };
+
+            if let Some(expected) = &assertion.equals {
+                if value != expected {
+                    return Err(AssertionFailure::new(
+                        AssertionKind::Json,
+                        format!("{} is {value}, expected {expected}", assertion.path),
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// Parse a JSONPath-style expression made of `.key`, `[index]` and
+/// `["key"]` steps, optionally starting at the root `$`.
+fn parse_json_path(path: &str) -> Result<Vec<PathSegment>, String> {
+    let invalid = |reason: &str| format!("invalid JSON path \"{path}\": {reason}");
+
+    let mut rest = path.trim();
+    rest = rest.strip_prefix('$').unwrap_or(rest);
+
+    let mut segments = Vec::new();
+    let mut first = true;
+
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']').ok_or_else(|| invalid("unclosed ["))?;
+            let inner = after[..end].trim();
+
+            let segment = if let Some(key) = inner
+                .strip_prefix('"')
+                .and_then(|inner| inner.strip_suffix('"'))
+            {
+                PathSegment::Key(key.to_string())
+            } else {
+                let index = inner
+                    .parse()
+                    .map_err(|_| invalid("array index must be a number"))?;
+                PathSegment::Index(index)
+            };
+
+            segments.push(segment);
+            rest = &after[end + 1..];
+        } else {
+            let after = match rest.strip_prefix('.') {
+                Some(after) => after,
+                None if first => rest,
+                None => return Err(invalid("expected . or [")),
+            };
+
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            if end == 0 {
+                return Err(invalid("empty key"));
+            }
+
+            segments.push(PathSegment::Key(after[..end].to_string()));
+            rest = &after[end..];
+        }
+
+        first = false;
+    }
+
+    Ok(segments)
+}
+
+/// Find the value at `path` in `document`, or `None` if it doesn't exist
+fn lookup_json_path<'a>(
+    document: &'a serde_json::Value,
+    path: &str,
+) -> Result<Option<&'a serde_json::Value>, String> {
+    let mut current = document;
+
+    for segment in parse_json_path(path)? {
+        let next = match segment {
+            PathSegment::Key(key) => current.get(key.as_str()),
+            PathSegment::Index(index) => current.get(index),
+        };
+
+        match next {
+            Some(value) => current = value,
+            None => return Ok(None),
+        }
+    }
+
+    Ok(Some(current))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::{HeaderValue, CONTENT_TYPE};
+    use serde_json::json;
+
+    #[test]
+    fn test_default_assertions_need_nothing() {
+        let assertions = ResponseAssertions::default();
+
+        assert!(!assertions.has_status());
+        assert!(!assertions.needs_body());
+        assert!(assertions.check_body("anything").is_ok());
+        assert!(assertions.check_headers(&HeaderMap::new()).is_ok());
+    }
+
+    #[test]
+    fn test_status_assertion() {
+        let assertions = ResponseAssertions {
+            status: vec![301, 401],
+            ..Default::default()
+        };
+
+        assert!(assertions.check_status(StatusCode::UNAUTHORIZED).is_ok());
+        assert!(assertions
+            .check_status(StatusCode::MOVED_PERMANENTLY)
+            .is_ok());
+
+        let failure = assertions.check_status(StatusCode::OK).unwrap_err();
+        assert_eq!(failure.error_type(), "assertion_failed_status");
+    }
+
+    #[test]
+    fn test_body_substring_assertions() {
+        let assertions = ResponseAssertions {
+            body_contains: vec!["healthy".to_string()],
+            body_not_contains: vec!["error".to_string()],
+            ..Default::default()
+        };
+
+        assert!(assertions.needs_body());
+        assert!(assertions.check_body("status: healthy").is_ok());
+
+        let failure = assertions.check_body("status: degraded").unwrap_err();
+        assert_eq!(failure.error_type(), "assertion_failed_body");
+        assert!(failure.message.contains("healthy"));
+
+        let failure = assertions
+            .check_body("healthy, error in cache")
+            .unwrap_err();
+        assert_eq!(failure.error_type(), "assertion_failed_body");
+        assert!(failure.message.contains("error"));
+    }
+
+    #[test]
+    fn test_body_regex_assertion() {
+        let assertions = ResponseAssertions {
+            body_regex: Some(BodyRegex::new(r#""version":\s*"\d+\.\d+""#).unwrap()),
+            ..Default::default()
+        };
+
+        assert!(assertions.validate().is_ok());
+        assert!(assertions.check_body(r#"{"version": "1.2"}"#).is_ok());
+        assert_eq!(
+            assertions
+                .check_body(r#"{"version": "dev"}"#)
+                .unwrap_err()
+                .error_type(),
+            "assertion_failed_body"
+        );
+    }
+
+    #[test]
+    fn test_invalid_regex_rejected() {
+        assert!(BodyRegex::new("(unclosed")
+            .unwrap_err()
+            .contains("invalid body_regex"));
+
+        let error =
+            serde_json::from_value::<ResponseAssertions>(json!({ "body_regex": "(unclosed" }))
+                .unwrap_err();
+        assert!(error.to_string().contains("invalid body_regex"));
+    }
+
+    #[test]
+    fn test_json_assertions() {
+        let assertions = ResponseAssertions {
+            json: vec![
+                JsonAssertion {
+                    path: "$.status".to_string(),
+                    equals: Some(json!("ok")),
+                },
+                JsonAssertion {
+                    path: "$.checks[1].name".to_string(),
+                    equals: Some(json!("db")),
+                },
+                JsonAssertion {
+                    path: "uptime".to_string(),
+                    equals: None,
+                },
+            ],
+            ..Default::default()
+        };
+
+        let body =
+            r#"{"status": "ok", "uptime": 12, "checks": [{"name": "cache"}, {"name": "db"}]}"#;
+        assert!(assertions.check_body(body).is_ok());
+
+        let body = r#"{"status": "down", "uptime": 12, "checks": []}"#;
+        let failure = assertions.check_body(body).unwrap_err();
+        assert_eq!(failure.error_type(), "assertion_failed_json");
+        assert!(failure.message.contains("$.status"));
+
+        let failure = assertions.check_body("<html>").unwrap_err();
+        assert_eq!(failure.error_type(), "assertion_failed_json");
+    }
+
+    #[test]
+    fn test_json_path_parsing() {
+        assert_eq!(
+            parse_json_path("$.items[2][\"odd key\"]"),
+            Ok(vec![
+                PathSegment::Key("items".to_string()),
+                PathSegment::Index(2),
+                PathSegment::Key("odd key".to_string()),
+            ])
+        );
+        assert_eq!(parse_json_path("$"), Ok(vec![]));
+        assert!(parse_json_path("$.items[x]").is_err());
+        assert!(parse_json_path("$.items[0").is_err());
+        assert!(parse_json_path("$..items").is_err());
+    }
+
+    #[test]
+    fn test_header_assertions() {
+        let assertions = ResponseAssertions {
+            headers: vec![HeaderAssertion {
+                name: "Content-Type".to_string(),
+                equals: None,
+                contains: Some("application/json".to_string()),
+            }],
+            ..Default::default()
+        };
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/json; charset=utf-8"),
+        );
+        assert!(assertions.check_headers(&headers).is_ok());
+
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/html"));
+        let failure = assertions.check_headers(&headers).unwrap_err();
+        assert_eq!(failure.error_type(), "assertion_failed_header");
+
+        let failure = assertions.check_headers(&HeaderMap::new()).unwrap_err();
+        assert!(failure.message.contains("missing"));
+    }
+}
@@ -0,0 +1,339 @@
+use chrono::Utc;
+use hickory_resolver::config::{NameServerConfigGroup, ResolverConfig, ResolverOpts};
+use hickory_resolver::error::ResolveErrorKind;
+use hickory_resolver::proto::op::ResponseCode;
+use hickory_resolver::proto::rr::{RData, RecordType};
+use hickory_resolver::{system_conf, Name, TokioAsyncResolver};
+use log::info;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::time::Instant;
+use tokio::time::timeout;
+
+use crate::error::CheckError;
+use crate::settings::{DnsRecordType, MonitorConfig};
+use crate::timing::PhaseTimings;
+use crate::worker::MonitorResult;
+
+/// Resolve a DNS monitor's query, passing when the answer has records and
+/// includes every one of `answers`
+pub async fn check(monitor: &MonitorConfig) -> MonitorResult {
+    let started = Instant::now();
+    let timestamp = Utc::now();
+
+    info!(
+        "Checking monitor: {} ({} {:?})",
+        monitor.name,
+        monitor.target(),
+        monitor.dns.record()
+    );
+
+    let mut timings = PhaseTimings::default();
+    let error = resolve(monitor, &mut timings).await.err();
+
+    MonitorResult {
+        monitor_id: monitor.id,
+        monitor_name: monitor.name.clone(),
+        url: monitor.target().to_string(),
+        success: error.is_none(),
+        response_time_ms: started.elapsed().as_millis() as u64,
+        status_code: None,
+        error,
+        timings,
+        tls: None,
+        degraded: false,
+        attempts: 1,
+        in_maintenance: false,
+        ping: None,
+        timestamp,
+    }
+}
+
+async fn resolve(monitor: &MonitorConfig, timings: &mut PhaseTimings) -> Result<(), CheckError> {
+    let query = monitor.target();
+    let record = monitor.dns.record();
+    let resolver = resolver(monitor)?;
+
+    let mut name = Name::from_str(&query)
+        .map_err(|e| CheckError::Request(format!("Invalid query {query}: {e}")))?;
+    // Search domains don't apply to the monitored name
+    name.set_fqdn(true);
+
+    let resolving = Instant::now();
+    let lookup = timeout(
+        monitor.request.timeout(),
+        resolver.lookup(name, record_type(record)),
+    )
+    .await
+    .map_err(|_| CheckError::ReadTimeout)?;
+
+    let received: Vec<String> = match lookup {
+        Ok(lookup) => lookup
+            .iter()
+            .filter(|data| data.record_type() == record_type(record))
+            .filter_map(record_value)
+            .collect(),
+        Err(e) => match e.kind() {
+            ResolveErrorKind::NoRecordsFound {
+                response_code: ResponseCode::NXDomain,
+                ..
+            } => {
+                timings.dns = Some(resolving.elapsed());
+                return Err(CheckError::NxDomain(query.to_string()));
+            }
+            ResolveErrorKind::NoRecordsFound { .. } => Vec::new(),
+            ResolveErrorKind::Timeout => return Err(CheckError::ReadTimeout),
+            _ => return Err(CheckError::Dns(format!("{query}: {e}"))),
+        },
+    };
+    timings.dns = Some(resolving.elapsed());
+
+    let missing: Vec<String> = monitor
+        .dns
+        .answers
+        .iter()
+        .filter(|expected| {
+            !received
+                .iter()
+                .any(|value| matches(record, expected, value))
+        })
+        .cloned()
+        .collect();
+
+    if received.is_empty() || !missing.is_empty() {
+        return Err(CheckError::UnexpectedAnswer { missing, received });
+    }
+
+    Ok(())
+}
+
+/// A resolver asking the monitor's name server, or the system's
+fn resolver(monitor: &MonitorConfig) -> Result<TokioAsyncResolver, CheckError> {
+    let (config, mut options) = match monitor
+        .dns
+        .resolver_address()
+        .map_err(CheckError::Request)?
+    {
+        Some(address) => (
+            ResolverConfig::from_parts(
+                None,
+                Vec::new(),
+                NameServerConfigGroup::from_ips_clear(&[address.ip()], address.port(), true),
+            ),
+            ResolverOpts::default(),
+        ),
+        None => system_conf::read_system_conf().map_err(|e| {
+            CheckError::Request(format!("Failed to read the system's resolvers: {e}"))
+        })?,
+    };
+
+    // Every check asks the name server, rather than a cache or /etc/hosts
+    options.cache_size = 0;
+    options.use_hosts_file = false;
+    options.timeout = monitor.request.timeout();
+    options.attempts = 1;
+
+    Ok(TokioAsyncResolver::tokio(config, options))
+}
+
+fn record_type(record: DnsRecordType) -> RecordType {
+    match record {
+        DnsRecordType::A => RecordType::A,
+        DnsRecordType::Aaaa => RecordType::AAAA,
+        DnsRecordType::Cname => RecordType::CNAME,
+        DnsRecordType::Mx => RecordType::MX,
+        DnsRecordType::Txt => RecordType::TXT,
+    }
+}
+
+/// A record's value as configured in `answers`: the address, the target
+/// or mail server name without its trailing dot, or the joined text
+fn record_value(data: &RData) -> Option<String> {
+    let name = |name: &Name| name.to_string().trim_end_matches('.').to_string();
+
+    match data {
+        RData::A(address) => Some(address.to_string()),
+        RData::AAAA(address) => Some(address.to_string()),
+        RData::CNAME(target) => Some(name(target)),
+        RData::MX(mx) => Some(name(mx.exchange())),
+        RData::TXT(txt) => Some(
+            txt.txt_data()
+                .iter()
+                .map(|text| String::from_utf8_lossy(text))
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+/// Whether an expected value is the received one, comparing addresses by
+/// value and names case-insensitively
+fn matches(record: DnsRecordType, expected: &str, value: &str) -> bool {
+    match record {
+        DnsRecordType::A | DnsRecordType::Aaaa => {
+            match (expected.parse::<IpAddr>(), value.parse::<IpAddr>()) {
+                (Ok(expected), Ok(value)) => expected == value,
+                _ => expected == value,
+            }
+        }
+        DnsRecordType::Cname | DnsRecordType::Mx => {
+            expected.trim_end_matches('.').eq_ignore_ascii_case(value)
+        }
+        DnsRecordType::Txt => expected == value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::{DnsConfig, MonitorType, RequestConfig};
+    use hickory_resolver::proto::op::{Message, MessageType, OpCode};
+    use hickory_resolver::proto::rr::rdata::{A, CNAME, MX, TXT};
+    use hickory_resolver::proto::rr::Record;
+    use std::net::SocketAddr;
+    use std::time::Duration;
+    use tokio::net::UdpSocket;
+
+    /// Answer queries for example.test with fixed records, and for any
+    /// other name with NXDOMAIN
+    async fn name_server() -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let address = socket.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let mut buffer = [0; 512];
+            while let Ok((read, from)) = socket.recv_from(&mut buffer).await {
+                let request = Message::from_vec(&buffer[..read]).unwrap();
+                let query = request.queries()[0].clone();
+
+                let mut response = Message::new();
+                response
+                    .set_id(request.id())
+                    .set_message_type(MessageType::Response)
+                    .set_op_code(OpCode::Query)
+                    .set_recursion_desired(request.recursion_desired())
+                    .set_recursion_available(true)
+                    .add_query(query.clone());
+
+                if query.name().to_ascii() == "example.test." {
+                    let answers = match query.query_type() {
+                        RecordType::A => vec![
+                            RData::A(A::new(192, 0, 2, 1)),
+                            RData::A(A::new(192, 0, 2, 2)),
+                        ],
+                        RecordType::MX => vec![RData::MX(MX::new(
+                            10,
+                            Name::from_str("Mail.Example.Test.").unwrap(),
+                        ))],
+                        RecordType::TXT => vec![RData::TXT(TXT::new(vec![
+                            "v=spf1 ".to_string(),
+                            "-all".to_string(),
+                        ]))],
+                        RecordType::CNAME => vec![RData::CNAME(CNAME(
+                            Name::from_str("www.example.test.").unwrap(),
+                        ))],
+                        _ => Vec::new(),
+                    };
+                    for answer in answers {
+                        response.add_answer(Record::from_rdata(query.name().clone(), 60, answer));
+                    }
+                } else {
+                    response.set_response_code(ResponseCode::NXDomain);
+                }
+
+                let _ = socket.send_to(&response.to_vec().unwrap(), from).await;
+            }
+        });
+
+        address
+    }
+
+    fn dns_monitor(
+        resolver: SocketAddr,
+        query: &str,
+        record: DnsRecordType,
+        answers: &[&str],
+    ) -> MonitorConfig {
+        MonitorConfig {
+            request: RequestConfig {
+                timeout: Some(Duration::from_secs(5)),
+                ..RequestConfig::default()
+            },
+            dns: DnsConfig {
+                query: Some(query.to_string()),
+                record: Some(record),
+                resolver: Some(resolver.to_string()),
+                answers: answers.iter().map(|answer| answer.to_string()).collect(),
+            },
+            ..MonitorConfig::new("DNS Test", MonitorType::Dns, Duration::from_secs(60))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_expected_answers() {
+        let resolver = name_server().await;
+
+        for (record, answers) in [
+            (DnsRecordType::A, vec!["192.0.2.2"]),
+            (DnsRecordType::A, vec![]),
+            (DnsRecordType::Mx, vec!["mail.example.test."]),
+            (DnsRecordType::Txt, vec!["v=spf1 -all"]),
+            (DnsRecordType::Cname, vec!["www.example.test"]),
+        ] {
+            let monitor = dns_monitor(resolver, "example.test", record, &answers);
+            let result = check(&monitor).await;
+            assert!(result.success, "{record:?}: {:?}", result.error);
+            assert_eq!(result.url, "example.test");
+            assert!(result.timings.dns.is_some());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unexpected_answer() {
+        let resolver = name_server().await;
+
+        let monitor = dns_monitor(
+            resolver,
+            "example.test",
+            DnsRecordType::A,
+            &["192.0.2.1", "192.0.2.9"],
+        );
+        let error = check(&monitor).await.error.unwrap();
+        assert_eq!(error.error_type(), "unexpected_answer");
+        assert_eq!(
+            error.to_string(),
+            "Expected 192.0.2.9 in the answer, got 192.0.2.1, 192.0.2.2"
+        );
+
+        // A name without records of the type fails even with no answers set
+        let monitor = dns_monitor(resolver, "example.test", DnsRecordType::Aaaa, &[]);
+        let error = check(&monitor).await.error.unwrap();
+        assert_eq!(error.error_type(), "unexpected_answer");
+        assert_eq!(error.to_string(), "No records in the answer");
+    }
+
+    #[tokio::test]
+    async fn test_nxdomain() {
+        let resolver = name_server().await;
+
+        let monitor = dns_monitor(resolver, "missing.test", DnsRecordType::A, &[]);
+        let result = check(&monitor).await;
+        assert!(!result.success);
+        assert_eq!(
+            result.error,
+            Some(CheckError::NxDomain("missing.test".to_string()))
+        );
+        assert!(result.timings.dns.is_some());
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(matches(DnsRecordType::Aaaa, "2001:DB8::1", "2001:db8::1"));
+        assert!(matches(
+            DnsRecordType::Mx,
+            "MAIL.example.com.",
+            "mail.example.com"
+        ));
+        assert!(!matches(DnsRecordType::Txt, "V=SPF1", "v=spf1"));
+    }
+}
@@ -0,0 +1,351 @@
+use chrono::Utc;
+use log::info;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::time::{Duration, Instant};
+use tokio::net::{lookup_host, UdpSocket};
+use tokio::time::{timeout, timeout_at};
+
+use crate::error::CheckError;
+use crate::settings::{MonitorConfig, DEFAULT_PING_TIMEOUT};
+use crate::timing::PhaseTimings;
+use crate::worker::MonitorResult;
+
+const ECHO_REQUEST_V4: u8 = 8;
+const ECHO_REPLY_V4: u8 = 0;
+const ECHO_REQUEST_V6: u8 = 128;
+const ECHO_REPLY_V6: u8 = 129;
+
+/// Carried by every echo request and expected back in the reply
+const PAYLOAD: &[u8] = b"sammy_monitor echo request";
+
+/// Identifier of the next check's echo requests on raw sockets, which see
+/// the replies to everyone's
+static NEXT_IDENTIFIER: AtomicU16 = AtomicU16::new(0);
+
+/// Round-trip times and losses of a ping check's echo requests
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PingStats {
+    pub sent: u32,
+    pub received: u32,
+    /// Round-trip times of the answered requests, `None` when none were
+    pub min: Option<Duration>,
+    pub avg: Option<Duration>,
+    pub max: Option<Duration>,
+}
+
+impl PingStats {
+    fn new(sent: u32, round_trips: &[Duration]) -> Self {
+        let received = round_trips.len() as u32;
+
+        Self {
+            sent,
+            received,
+            min: round_trips.iter().min().copied(),
+            avg: (received > 0).then(|| round_trips.iter().sum::<Duration>() / received),
+            max: round_trips.iter().max().copied(),
+        }
+    }
+
+    /// Percentage of echo requests left unanswered
+    pub fn packet_loss(&self) -> f64 {
+        if self.sent == 0 {
+            return 0.0;
+        }
+        f64::from(self.sent - self.received) * 100.0 / f64::from(self.sent)
+    }
+}
+
+/// Send a ping monitor's host `count` echo requests, one after the other,
+/// waiting up to `timeout` for each reply
+pub async fn check(monitor: &MonitorConfig) -> MonitorResult {
+    let started = Instant::now();
+    let timestamp = Utc::now();
+
+    info!("Checking monitor: {} ({})", monitor.name, monitor.target());
+
+    let mut timings = PhaseTimings::default();
+    let (stats, error) = match ping(monitor, &mut timings).await {
+        Ok(stats) => {
+            let error = loss_error(monitor, &stats);
+            (Some(stats), error)
+        }
+        Err(error) => (None, Some(error)),
+    };
+
+    // The average round trip says more than the time spent waiting on
+    // requests that were never answered
+    let response_time = stats
+        .as_ref()
+        .and_then(|stats| stats.avg)
+        .unwrap_or_else(|| started.elapsed());
+
+    MonitorResult {
+        monitor_id: monitor.id,
+        monitor_name: monitor.name.clone(),
+        url: monitor.target().to_string(),
+        success: error.is_none(),
+        response_time_ms: response_time.as_millis() as u64,
+        status_code: None,
+        error,
+        timings,
+        tls: None,
+        degraded: false,
+        attempts: 1,
+        in_maintenance: false,
+        ping: stats,
+        timestamp,
+    }
+}
+
+async fn ping(
+    monitor: &MonitorConfig,
+    timings: &mut PhaseTimings,
+) -> Result<PingStats, CheckError> {
+    let host = monitor.target();
+    let wait = monitor.request.timeout.unwrap_or(DEFAULT_PING_TIMEOUT);
+
+    let resolving = Instant::now();
+    let address = timeout(wait, lookup_host((&*host, 0)))
+        .await
+        .map_err(|_| CheckError::Dns(format!("{host}: timed out resolving")))?
+        .map_err(|e| CheckError::Dns(format!("{host}: {e}")))?
+        .next()
+        .ok_or_else(|| CheckError::Dns(format!("{host}: no addresses found")))?;
+    timings.dns = Some(resolving.elapsed());
+
+    let socket = EchoSocket::open(address.ip()).map_err(|e| match e.kind() {
+        io::ErrorKind::PermissionDenied => CheckError::Request(format!(
+            "Not allowed to open an ICMP socket ({e}), allow unprivileged ones with the \
+             net.ipv4.ping_group_range sysctl or grant CAP_NET_RAW"
+        )),
+        _ => CheckError::Request(format!("Failed to open an ICMP socket: {e}")),
+    })?;
+
+    let count = monitor.ping.count();
+    let mut round_trips = Vec::new();
+    for sequence in 0..count as u16 {
+        let sent = Instant::now();
+        socket
+            .send(sequence)
+            .await
+            .map_err(|e| CheckError::from_io(&e))?;
+
+        // Unanswered requests only count as lost
+        if let Ok(reply) = timeout_at((sent + wait).into(), socket.reply(sequence)).await {
+            reply.map_err(|e| CheckError::from_io(&e))?;
+            round_trips.push(sent.elapsed());
+        }
+    }
+
+    Ok(PingStats::new(count, &round_trips))
+}
+
+/// Fail checks with every request lost, or more lost than `max_packet_loss`
+fn loss_error(monitor: &MonitorConfig, stats: &PingStats) -> Option<CheckError> {
+    let too_many = monitor
+        .ping
+        .max_packet_loss
+        .is_some_and(|max| stats.packet_loss() > max);
+
+    (stats.received == 0 || too_many).then_some(CheckError::PacketLoss {
+        sent: stats.sent,
+        received: stats.received,
+    })
+}
+
+/// An ICMP socket exchanging echo requests and replies with one address
+struct EchoSocket {
+    socket: UdpSocket,
+    target: SocketAddr,
+    identifier: u16,
+    /// Raw sockets see every ICMP packet the host receives, and on IPv4
+    /// with the IP header in front
+    raw: bool,
+}
+
+impl EchoSocket {
+    /// Open an unprivileged ICMP socket where the kernel allows them, or a
+    /// raw one otherwise
+    fn open(ip: IpAddr) -> io::Result<Self> {
+        let (domain, protocol) = match ip {
+            IpAddr::V4(_) => (Domain::IPV4, Protocol::ICMPV4),
+            IpAddr::V6(_) => (Domain::IPV6, Protocol::ICMPV6),
+        };
+
+        let (socket, raw) = match Socket::new(domain, Type::DGRAM, Some(protocol)) {
+            Ok(socket) => (socket, false),
+            Err(error) => match Socket::new(domain, Type::RAW, Some(protocol)) {
+                Ok(socket) => (socket, true),
+                Err(_) => return Err(error),
+            },
+        };
+        socket.set_nonblocking(true)?;
+
+        Ok(Self {
+            socket: UdpSocket::from_std(socket.into())?,
+            target: SocketAddr::new(ip, 0),
+            identifier: (std::process::id() as u16)
+                ^ NEXT_IDENTIFIER.fetch_add(1, Ordering::Relaxed),
+            raw,
+        })
+    }
+
+    async fn send(&self, sequence: u16) -> io::Result<()> {
+        let kind = match self.target {
+            SocketAddr::V4(_) => ECHO_REQUEST_V4,
+            SocketAddr::V6(_) => ECHO_REQUEST_V6,
+        };
+
+        // Unprivileged sockets replace the identifier with their own
+        let mut packet = vec![kind, 0, 0, 0];
+        packet.extend_from_slice(&self.identifier.to_be_bytes());
+        packet.extend_from_slice(&sequence.to_be_bytes());
+        packet.extend_from_slice(PAYLOAD);
+
+        // The kernel fills in ICMPv6 checksums, which cover the IP addresses
+        if self.target.is_ipv4() {
+            let checksum = checksum(&packet);
+            packet[2..4].copy_from_slice(&checksum.to_be_bytes());
+        }
+
+        self.socket.send_to(&packet, self.target).await?;
+        Ok(())
+    }
+
+    /// Wait for the reply to the request with `sequence`
+    async fn reply(&self, sequence: u16) -> io::Result<()> {
+        let mut buffer = [0; 1500];
+
+        loop {
+            let (read, from) = self.socket.recv_from(&mut buffer).await?;
+            if from.ip() == self.target.ip() && self.is_reply(&buffer[..read], sequence) {
+                return Ok(());
+            }
+        }
+    }
+
+    fn is_reply(&self, packet: &[u8], sequence: u16) -> bool {
+        let (packet, kind) = match self.target {
+            SocketAddr::V4(_) if self.raw => {
+                let header = usize::from(packet.first().copied().unwrap_or_default() & 0x0f) * 4;
+                (packet.get(header..).unwrap_or_default(), ECHO_REPLY_V4)
+            }
+            SocketAddr::V4(_) => (packet, ECHO_REPLY_V4),
+            SocketAddr::V6(_) => (packet, ECHO_REPLY_V6),
+        };
+
+        packet.len() >= 8
+            && packet[0] == kind
+            && (!self.raw || packet[4..6] == self.identifier.to_be_bytes())
+            && packet[6..8] == sequence.to_be_bytes()
+            && &packet[8..] == PAYLOAD
+    }
+}
+
+/// The Internet checksum of an ICMP message
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|pair| {
+            u32::from(u16::from_be_bytes([
+                pair[0],
+                pair.get(1).copied().unwrap_or(0),
+            ]))
+        })
+        .sum();
+
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::{MonitorType, PingConfig};
+
+    fn ping_monitor(host: &str, max_packet_loss: Option<f64>) -> MonitorConfig {
+        MonitorConfig {
+            ping: PingConfig {
+                host: Some(host.to_string()),
+                count: Some(3),
+                max_packet_loss,
+            },
+            ..MonitorConfig::new("Ping Test", MonitorType::Ping, Duration::from_secs(60))
+        }
+    }
+
+    #[test]
+    fn test_checksum() {
+        // Echo request with identifier 1, sequence 1 and no payload
+        assert_eq!(checksum(&[8, 0, 0, 0, 0, 1, 0, 1]), 0xf7fd);
+        // Odd lengths are padded with a zero byte
+        assert_eq!(checksum(&[0xff]), 0x00ff);
+    }
+
+    #[test]
+    fn test_stats() {
+        let stats = PingStats::new(
+            4,
+            &[
+                Duration::from_millis(10),
+                Duration::from_millis(30),
+                Duration::from_millis(20),
+            ],
+        );
+        assert_eq!(stats.received, 3);
+        assert_eq!(stats.min, Some(Duration::from_millis(10)));
+        assert_eq!(stats.avg, Some(Duration::from_millis(20)));
+        assert_eq!(stats.max, Some(Duration::from_millis(30)));
+        assert_eq!(stats.packet_loss(), 25.0);
+
+        let lost = PingStats::new(3, &[]);
+        assert_eq!(lost.avg, None);
+        assert_eq!(lost.packet_loss(), 100.0);
+    }
+
+    #[test]
+    fn test_loss_error() {
+        let stats = PingStats::new(4, &[Duration::from_millis(10); 3]);
+
+        // Without max_packet_loss only losing every request fails
+        assert_eq!(loss_error(&ping_monitor("localhost", None), &stats), None);
+        assert_eq!(
+            loss_error(&ping_monitor("localhost", None), &PingStats::new(4, &[])),
+            Some(CheckError::PacketLoss {
+                sent: 4,
+                received: 0
+            })
+        );
+
+        assert_eq!(
+            loss_error(&ping_monitor("localhost", Some(25.0)), &stats),
+            None
+        );
+        assert_eq!(
+            loss_error(&ping_monitor("localhost", Some(10.0)), &stats),
+            Some(CheckError::PacketLoss {
+                sent: 4,
+                received: 3
+            })
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs ICMP sockets: net.ipv4.ping_group_range covering the user, or CAP_NET_RAW"]
+    async fn test_ping_localhost() {
+        let result = check(&ping_monitor("127.0.0.1", None)).await;
+
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(result.url, "127.0.0.1");
+        let stats = result.ping.unwrap();
+        assert_eq!((stats.sent, stats.received), (3, 3));
+        assert!(stats.min <= stats.avg && stats.avg <= stats.max);
+        assert_eq!(stats.packet_loss(), 0.0);
+        assert!(result.timings.dns.is_some());
+    }
+}
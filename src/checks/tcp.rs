@@ -0,0 +1,208 @@
+use chrono::Utc;
+use log::info;
+use std::net::SocketAddr;
+use std::time::Instant;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{lookup_host, TcpStream};
+use tokio::time::timeout_at;
+
+use crate::error::CheckError;
+use crate::settings::MonitorConfig;
+use crate::timing::PhaseTimings;
+use crate::worker::MonitorResult;
+
+/// Most of a reply read while looking for the expected text
+const REPLY_LIMIT: usize = 64 * 1024;
+
+/// Longest part of an unexpected reply kept for the error
+const REPLY_PREVIEW: usize = 200;
+
+/// Connect to a TCP monitor's address, then send its `send` text and wait
+/// for its `expect` text when set
+pub async fn check(monitor: &MonitorConfig) -> MonitorResult {
+    let started = Instant::now();
+    let timestamp = Utc::now();
+
+    info!("Checking monitor: {} ({})", monitor.name, monitor.target());
+
+    let mut timings = PhaseTimings::default();
+    let error = exchange(monitor, started, &mut timings).await.err();
+
+    MonitorResult {
+        monitor_id: monitor.id,
+        monitor_name: monitor.name.clone(),
+        url: monitor.target().to_string(),
+        success: error.is_none(),
+        response_time_ms: started.elapsed().as_millis() as u64,
+        status_code: None,
+        error,
+        timings,
+        tls: None,
+        degraded: false,
+        attempts: 1,
+        in_maintenance: false,
+        ping: None,
+        timestamp,
+    }
+}
+
+async fn exchange(
+    monitor: &MonitorConfig,
+    started: Instant,
+    timings: &mut PhaseTimings,
+) -> Result<(), CheckError> {
+    let address = monitor.target();
+    let deadline = started + monitor.request.timeout();
+    let connect_deadline = monitor
+        .client
+        .connect_timeout
+        .map_or(deadline, |timeout| deadline.min(started + timeout));
+
+    let resolving = Instant::now();
+    let addresses: Vec<SocketAddr> = timeout_at(connect_deadline.into(), lookup_host(&*address))
+        .await
+        .map_err(|_| CheckError::ConnectTimeout)?
+        .map_err(|e| CheckError::Dns(format!("{address}: {e}")))?
+        .collect();
+    timings.dns = Some(resolving.elapsed());
+
+    let connecting = Instant::now();
+    let mut stream = timeout_at(
+        connect_deadline.into(),
+        TcpStream::connect(addresses.as_slice()),
+    )
+    .await
+    .map_err(|_| CheckError::ConnectTimeout)?
+    .map_err(|e| CheckError::from_io(&e))?;
+    timings.connect = Some(connecting.elapsed());
+
+    let sent = Instant::now();
+    if let Some(send) = &monitor.tcp.send {
+        timeout_at(deadline.into(), stream.write_all(send.as_bytes()))
+            .await
+            .map_err(|_| CheckError::ReadTimeout)?
+            .map_err(|e| CheckError::from_io(&e))?;
+    }
+
+    let Some(expected) = &monitor.tcp.expect else {
+        return Ok(());
+    };
+
+    // Read until the expected text turns up, the server stops sending or
+    // the time is up
+    let mut received = Vec::new();
+    let mut buffer = [0; 4096];
+    while received.len() < REPLY_LIMIT {
+        let read = match timeout_at(deadline.into(), stream.read(&mut buffer)).await {
+            Ok(read) => read.map_err(|e| CheckError::from_io(&e))?,
+            Err(_) if received.is_empty() => return Err(CheckError::ReadTimeout),
+            Err(_) => break,
+        };
+        if read == 0 {
+            break;
+        }
+
+        timings.ttfb.get_or_insert(sent.elapsed());
+        received.extend_from_slice(&buffer[..read]);
+        if String::from_utf8_lossy(&received).contains(expected.as_str()) {
+            return Ok(());
+        }
+    }
+
+    Err(CheckError::UnexpectedBanner {
+        expected: expected.clone(),
+        received: String::from_utf8_lossy(&received)
+            .chars()
+            .take(REPLY_PREVIEW)
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::{MonitorType, RequestConfig, TcpConfig};
+    use std::time::Duration;
+    use tokio::net::TcpListener;
+
+    fn monitor(address: String, send: Option<&str>, expect: Option<&str>) -> MonitorConfig {
+        MonitorConfig {
+            request: RequestConfig {
+                timeout: Some(Duration::from_millis(500)),
+                ..RequestConfig::default()
+            },
+            tcp: TcpConfig {
+                address: Some(address),
+                send: send.map(str::to_string),
+                expect: expect.map(str::to_string),
+            },
+            ..MonitorConfig::new("TCP Test", MonitorType::Tcp, Duration::from_secs(60))
+        }
+    }
+
+    /// Greet each connection with `banner`, then echo back what's sent
+    async fn echo_server(banner: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    stream.write_all(banner.as_bytes()).await.unwrap();
+                    let mut buffer = [0; 1024];
+                    while let Ok(read) = stream.read(&mut buffer).await {
+                        if read == 0 || stream.write_all(&buffer[..read]).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        address
+    }
+
+    #[tokio::test]
+    async fn test_connect() {
+        let address = echo_server("").await;
+        let result = check(&monitor(address.clone(), None, None)).await;
+
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(result.url, address);
+        assert!(result.timings.connect.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_send_and_expect() {
+        let address = echo_server("SSH-2.0-OpenSSH_9.6\r\n").await;
+
+        let result = check(&monitor(address.clone(), None, Some("SSH-2.0"))).await;
+        assert!(result.success, "{:?}", result.error);
+        assert!(result.timings.ttfb.is_some());
+
+        let result = check(&monitor(address.clone(), Some("PING\r\n"), Some("PING"))).await;
+        assert!(result.success, "{:?}", result.error);
+
+        // The server goes quiet without sending what's expected
+        let result = check(&monitor(address, None, Some("220 "))).await;
+        let error = result.error.unwrap();
+        assert_eq!(error.error_type(), "unexpected_banner");
+        assert!(error.to_string().contains("SSH-2.0-OpenSSH_9.6"));
+    }
+
+    #[tokio::test]
+    async fn test_connection_refused() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        drop(listener);
+
+        let result = check(&monitor(address, None, None)).await;
+        assert!(!result.success);
+        assert_eq!(result.error.unwrap().error_type(), "connection_refused");
+    }
+
+    #[tokio::test]
+    async fn test_nothing_sent() {
+        let address = echo_server("").await;
+        let result = check(&monitor(address, None, Some("220 "))).await;
+        assert_eq!(result.error, Some(CheckError::ReadTimeout));
+    }
+}
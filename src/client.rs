@@ -0,0 +1,216 @@
+use reqwest::redirect::Policy;
+use reqwest::{Certificate, Client, Identity};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::sync::{Arc, Mutex};
+
+use crate::settings::{ClientConfig, TlsConfig};
+use crate::timing::TrackingResolver;
+
+/// Redirects followed when a monitor doesn't set `max_redirects`
+pub const DEFAULT_MAX_REDIRECTS: usize = 10;
+
+/// HTTP clients shared between monitors with the same client options.
+///
+/// Connection, redirect and TLS settings are fixed when a `reqwest::Client`
+/// is built, so monitors that need different settings need different
+/// clients. Building one per check would throw away connection reuse, so
+/// clients are built once per distinct set of client options and reused.
+#[derive(Default)]
+pub struct ClientCache {
+    clients: Mutex<HashMap<ClientConfig, Client>>,
+}
+
+impl ClientCache {
+    pub fn new() -> Self {
+        Self {
+            clients: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Get the client for `config`, building it on first use
+    pub fn get(&self, config: &ClientConfig) -> Result<Client, String> {
+        let key = client_options(config);
+        let mut clients = self.clients.lock().unwrap();
+
+        if let Some(client) = clients.get(&key) {
+            return Ok(client.clone());
+        }
+
+        let client = build_client(&key)?;
+        clients.insert(key, client.clone());
+        Ok(client)
+    }
+
+    /// Drop the clients none of `configs` use, along with their idle
+    /// connections
+    pub fn retain<'a>(&self, configs: impl IntoIterator<Item = &'a ClientConfig>) {
+        let used: HashSet<ClientConfig> = configs.into_iter().map(client_options).collect();
+        self.clients
+            .lock()
+            .unwrap()
+            .retain(|options, _| used.contains(options));
+    }
+
+    /// Number of distinct clients built so far
+    pub fn len(&self) -> usize {
+        self.clients.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The options of `config` a client is built with. The certificate expiry
+/// threshold only matters to inspections, so it's left out.
+fn client_options(config: &ClientConfig) -> ClientConfig {
+    ClientConfig {
+        tls: TlsConfig {
+            cert_expiry_threshold: None,
+            ..config.tls.clone()
+        },
+        ..config.clone()
+    }
+}
+
+/// Build a client with the connection, redirect and TLS options in `config`
+pub fn build_client(config: &ClientConfig) -> Result<Client, String> {
+    let redirect_policy = if config.follow_redirects.unwrap_or(true) {
+        Policy::limited(config.max_redirects.unwrap_or(DEFAULT_MAX_REDIRECTS))
+    } else {
+        Policy::none()
+    };
+
+    let mut builder = Client::builder()
+        .user_agent(format!(
+            "{}/{}",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION")
+        ))
+        .redirect(redirect_policy)
+        .dns_resolver(Arc::new(TrackingResolver))
+        .danger_accept_invalid_certs(config.tls.accept_invalid_certs);
+
+    if let Some(connect_timeout) = config.connect_timeout {
+        builder = builder.connect_timeout(connect_timeout);
+    }
+
+    if let Some(path) = &config.tls.ca_bundle {
+        let pem = fs::read(path)
+            .map_err(|e| format!("Failed to read ca_bundle {}: {e}", path.display()))?;
+        let certificates = Certificate::from_pem_bundle(&pem)
+            .map_err(|e| format!("Invalid ca_bundle {}: {e}", path.display()))?;
+
+        for certificate in certificates {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+
+    if let (Some(cert_path), Some(key_path)) = (&config.tls.client_cert, &config.tls.client_key) {
+        let cert = fs::read(cert_path)
+            .map_err(|e| format!("Failed to read client_cert {}: {e}", cert_path.display()))?;
+        let key = fs::read(key_path)
+            .map_err(|e| format!("Failed to read client_key {}: {e}", key_path.display()))?;
+        let identity = Identity::from_pkcs8_pem(&cert, &key)
+            .map_err(|e| format!("Invalid client certificate: {e}"))?;
+
+        builder = builder.identity(identity);
+    }
+
+    builder
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::TlsConfig;
+    use std::io::Write;
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_build_default_client() {
+        assert!(build_client(&ClientConfig::default()).is_ok());
+    }
+
+    #[test]
+    fn test_client_cache_reuses_clients() {
+        let cache = ClientCache::new();
+        assert!(cache.is_empty());
+
+        let default_config = ClientConfig::default();
+        let no_redirects = ClientConfig {
+            follow_redirects: Some(false),
+            ..Default::default()
+        };
+
+        cache.get(&default_config).expect("default client");
+        cache.get(&default_config).expect("default client");
+        assert_eq!(cache.len(), 1);
+
+        cache.get(&no_redirects).expect("no-redirect client");
+        assert_eq!(cache.len(), 2);
+
+        let short_connect = ClientConfig {
+            connect_timeout: Some(Duration::from_secs(2)),
+            ..Default::default()
+        };
+        cache.get(&short_connect).expect("short connect client");
+        assert_eq!(cache.len(), 3);
+
+        // The expiry threshold doesn't change the client
+        let short_threshold = ClientConfig {
+            tls: TlsConfig {
+                cert_expiry_threshold: Some(Duration::from_secs(86_400)),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        cache.get(&short_threshold).expect("default client");
+        assert_eq!(cache.len(), 3);
+
+        cache.retain([&short_threshold, &no_redirects]);
+        assert_eq!(cache.len(), 2);
+        cache.get(&default_config).expect("default client");
+        assert_eq!(cache.len(), 2);
+
+        cache.retain([]);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_build_client_missing_ca_bundle() {
+        let config = ClientConfig {
+            tls: TlsConfig {
+                ca_bundle: Some(PathBuf::from("/path/that/does/not/exist.pem")),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let error = build_client(&config).unwrap_err();
+        assert!(error.contains("ca_bundle"));
+    }
+
+    #[test]
+    fn test_build_client_invalid_ca_bundle() {
+        let mut temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        temp_file
+            .write_all(b"-----BEGIN CERTIFICATE-----\nnot base64\n-----END CERTIFICATE-----\n")
+            .expect("Failed to write to temp file");
+
+        let config = ClientConfig {
+            tls: TlsConfig {
+                ca_bundle: Some(temp_file.path().to_path_buf()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        assert!(build_client(&config).is_err());
+    }
+}
@@ -0,0 +1,231 @@
+use serde::de::{self, Deserializer, Visitor};
+use serde::Serializer;
+use std::fmt;
+use std::time::Duration;
+
+/// Parse a human-readable duration such as `"30s"`, `"5m"`, `"1h"` or
+/// `"1h30m"`.
+///
+/// Supported units are `ms`, `s`, `m`, `h` and `d`. Every number must carry a
+/// unit; bare numbers are rejected because their unit is ambiguous.
+pub fn parse_duration(input: &str) -> Result<Duration, String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err("duration is empty".to_string());
+    }
+
+    if input.chars().all(|c| c.is_ascii_digit()) {
+        return Err(ambiguous_integer_message(input));
+    }
+
+    let mut total = Duration::ZERO;
+    let mut rest = input;
+
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        if digits == 0 {
+            return Err(format!("invalid duration \"{input}\": expected a number"));
+        }
+
+        let value: u64 = rest[..digits]
+            .parse()
+            .map_err(|_| format!("invalid duration \"{input}\": number out of range"))?;
+        rest = &rest[digits..];
+
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let unit = &rest[..unit_len];
+        rest = &rest[unit_len..];
+
+        let out_of_range = || format!("invalid duration \"{input}\": out of range");
+        let seconds = |size: u64| {
+            value
+                .checked_mul(size)
+                .map(Duration::from_secs)
+                .ok_or_else(out_of_range)
+        };
+
+        let part = match unit {
+            "ms" => Duration::from_millis(value),
+            "s" => Duration::from_secs(value),
+            "m" => seconds(60)?,
+            "h" => seconds(60 * 60)?,
+            "d" => seconds(60 * 60 * 24)?,
+            "" => {
+                return Err(format!(
+                    "invalid duration \"{input}\": missing unit after {value}"
+                ))
+            }
+            other => {
+                return Err(format!(
+                    "invalid duration \"{input}\": unknown unit \"{other}\" (use ms, s, m, h or d)"
+                ))
+            }
+        };
+
+        total = total.checked_add(part).ok_or_else(out_of_range)?;
+    }
+
+    Ok(total)
+}
+
+/// Format a duration in the same notation accepted by [`parse_duration`]
+pub fn format_duration(duration: Duration) -> String {
+    let mut millis = duration.as_millis();
+    if millis == 0 {
+        return "0s".to_string();
+    }
+
+    let mut output = String::new();
+    for (unit, size) in [
+        ("d", 86_400_000),
+        ("h", 3_600_000),
+        ("m", 60_000),
+        ("s", 1_000),
+        ("ms", 1),
+    ] {
+        if millis >= size {
+            output.push_str(&format!("{}{unit}", millis / size));
+            millis %= size;
+        }
+    }
+
+    output
+}
+
+fn ambiguous_integer_message(value: &str) -> String {
+    format!(
+        "bare number {value} is ambiguous; durations need a unit, \
+         e.g. \"{value}s\" for seconds or \"{value}m\" for minutes"
+    )
+}
+
+struct DurationVisitor;
+
+impl Visitor<'_> for DurationVisitor {
+    type Value = Duration;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a duration string such as \"30s\", \"5m\" or \"1h\"")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Duration, E> {
+        parse_duration(value).map_err(E::custom)
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Duration, E> {
+        Err(E::custom(ambiguous_integer_message(&value.to_string())))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Duration, E> {
+        Err(E::custom(ambiguous_integer_message(&value.to_string())))
+    }
+}
+
+/// Deserialize a [`Duration`] from a human-readable string, for use with
+/// `#[serde(deserialize_with = "crate::duration::deserialize")]`
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_any(DurationVisitor)
+}
+
+/// Deserialize an optional [`Duration`], for use with
+/// `#[serde(default, deserialize_with = "crate::duration::deserialize_option")]`
+pub fn deserialize_option<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize(deserializer).map(Some)
+}
+
+/// Serialize a [`Duration`] in the notation [`deserialize`] reads, for use
+/// with `#[serde(serialize_with = "crate::duration::serialize")]`
+pub fn serialize<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&format_duration(*duration))
+}
+
+/// Serialize an optional [`Duration`], for use with `serialize_with`
+/// alongside `skip_serializing_if = "Option::is_none"`
+pub fn serialize_option<S>(duration: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match duration {
+        Some(duration) => serialize(duration, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration_units() {
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("5m"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_duration("1h"), Ok(Duration::from_secs(3600)));
+        assert_eq!(parse_duration("2d"), Ok(Duration::from_secs(172_800)));
+    }
+
+    #[test]
+    fn test_parse_duration_compound() {
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("1m30s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration(" 10s "), Ok(Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn test_parse_duration_rejects_bare_numbers() {
+        let error = parse_duration("60").unwrap_err();
+        assert!(error.contains("ambiguous"));
+        assert!(error.contains("\"60s\""));
+    }
+
+    #[test]
+    fn test_parse_duration_rejects_invalid() {
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration("5x").is_err());
+        assert!(parse_duration("5 minutes").is_err());
+        assert!(parse_duration("1h30").is_err());
+    }
+
+    #[test]
+    fn test_parse_duration_rejects_out_of_range() {
+        let max = u64::MAX;
+        assert!(parse_duration(&format!("{max}m"))
+            .unwrap_err()
+            .contains("out of range"));
+        assert!(parse_duration(&format!("{}d", max / 1000))
+            .unwrap_err()
+            .contains("out of range"));
+        assert!(parse_duration(&format!("{max}s{max}s"))
+            .unwrap_err()
+            .contains("out of range"));
+        assert_eq!(
+            parse_duration(&format!("{max}s")),
+            Ok(Duration::from_secs(max))
+        );
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::ZERO), "0s");
+        assert_eq!(format_duration(Duration::from_millis(500)), "500ms");
+        assert_eq!(format_duration(Duration::from_secs(300)), "5m");
+        assert_eq!(format_duration(Duration::from_secs(5400)), "1h30m");
+
+        let duration = Duration::from_millis(90_061_001);
+        assert_eq!(parse_duration(&format_duration(duration)), Ok(duration));
+    }
+}
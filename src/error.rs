@@ -0,0 +1,390 @@
+use chrono::{DateTime, Utc};
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use crate::assertions::AssertionFailure;
+use crate::tls::TlsVerifyError;
+
+/// Why a check failed.
+///
+/// Each variant maps to the `error_type` label of
+/// `http_monitor_failures_total`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CheckError {
+    /// The request couldn't be built, e.g. a secret didn't resolve
+    Request(String),
+    /// The host name didn't resolve
+    Dns(String),
+    /// Nothing is listening on the host and port
+    ConnectionRefused(String),
+    /// The connection was reset or closed mid-request
+    ConnectionReset(String),
+    /// Any other failure to connect or exchange the request
+    Connection(String),
+    /// No connection within `connect_timeout`
+    ConnectTimeout,
+    /// No complete response within `timeout`
+    ReadTimeout,
+    /// The TLS handshake failed, with the verification failure when known
+    Tls {
+        message: String,
+        verify_error: Option<TlsVerifyError>,
+    },
+    /// The redirect limit was reached or a redirect loop was detected
+    TooManyRedirects,
+    /// The response body exceeded `max_body_size`
+    BodyTooLarge { limit: usize },
+    /// The response body couldn't be read
+    BodyRead(String),
+    /// A non-2xx status with no status assertion configured
+    HttpStatus(u16),
+    /// The response failed an assertion
+    Assertion(AssertionFailure),
+    /// A TCP server didn't send back the expected text
+    UnexpectedBanner { expected: String, received: String },
+    /// Too few echo requests of a ping check were answered
+    PacketLoss { sent: u32, received: u32 },
+    /// The name a DNS monitor resolves doesn't exist
+    NxDomain(String),
+    /// A DNS answer lacked expected values, or had no records at all
+    UnexpectedAnswer {
+        missing: Vec<String>,
+        received: Vec<String>,
+    },
+    /// A heartbeat monitor's job didn't ping within its interval and grace,
+    /// with when it last did
+    MissedHeartbeat { last: Option<DateTime<Utc>> },
+    /// A heartbeat monitor's job reported a failure, with the message it
+    /// sent along
+    JobFailed(Option<String>),
+}
+
+impl CheckError {
+    /// Classify a request error by walking its source chain.
+    ///
+    /// The URL is left out of the message, as it may carry a token and
+    /// messages end up on the public status page.
+    pub fn from_reqwest(error: reqwest::Error) -> Self {
+        let error = error.without_url();
+
+        if error.is_redirect() {
+            return CheckError::TooManyRedirects;
+        }
+
+        if error.is_timeout() {
+            return if error.is_connect() {
+                CheckError::ConnectTimeout
+            } else {
+                CheckError::ReadTimeout
+            };
+        }
+
+        let message = describe(&error);
+        let mut source = error.source();
+
+        while let Some(cause) = source {
+            if let Some(tls_error) = cause.downcast_ref::<native_tls::Error>() {
+                return CheckError::Tls {
+                    message,
+                    verify_error: TlsVerifyError::from_handshake_message(&tls_error.to_string()),
+                };
+            }
+
+            if let Some(hyper_error) = cause.downcast_ref::<hyper::Error>() {
+                if hyper_error.is_incomplete_message() {
+                    return CheckError::ConnectionReset(message);
+                }
+            }
+
+            if let Some(io_error) = cause.downcast_ref::<io::Error>() {
+                if let Some(error) = classify_io(io_error.kind(), &message) {
+                    return error;
+                }
+            }
+
+            // hyper's connect error type is private; resolver failures are
+            // only recognisable by its message
+            if cause.to_string().starts_with("dns error") {
+                return CheckError::Dns(message);
+            }
+
+            source = cause.source();
+        }
+
+        if error.is_body() || error.is_decode() {
+            return CheckError::BodyRead(message);
+        }
+
+        CheckError::Connection(message)
+    }
+
+    /// Classify a failure to connect or exchange data over a socket
+    pub fn from_io(error: &io::Error) -> Self {
+        let message = error.to_string();
+        classify_io(error.kind(), &message).unwrap_or(CheckError::Connection(message))
+    }
+
+    /// Value of the `error_type` metrics label for this failure
+    pub fn error_type(&self) -> &'static str {
+        match self {
+            CheckError::Request(_) => "request_error",
+            CheckError::Dns(_) => "dns_error",
+            CheckError::ConnectionRefused(_) => "connection_refused",
+            CheckError::ConnectionReset(_) => "connection_reset",
+            CheckError::Connection(_) => "connection_error",
+            CheckError::ConnectTimeout => "connect_timeout",
+            CheckError::ReadTimeout => "read_timeout",
+            CheckError::Tls {
+                verify_error: Some(verify_error),
+                ..
+            } => verify_error.error_type(),
+            CheckError::Tls { .. } => "tls_error",
+            CheckError::TooManyRedirects => "too_many_redirects",
+            CheckError::BodyTooLarge { .. } => "body_too_large",
+            CheckError::BodyRead(_) => "body_read_error",
+            CheckError::HttpStatus(_) => "http_error",
+            CheckError::Assertion(failure) => failure.error_type(),
+            CheckError::UnexpectedBanner { .. } => "unexpected_banner",
+            CheckError::PacketLoss { .. } => "packet_loss",
+            CheckError::NxDomain(_) => "nxdomain",
+            CheckError::UnexpectedAnswer { .. } => "unexpected_answer",
+            CheckError::MissedHeartbeat { .. } => "missed_heartbeat",
+            CheckError::JobFailed(_) => "job_failed",
+        }
+    }
+}
+
+impl fmt::Display for CheckError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckError::Request(message) => write!(f, "{message}"),
+            CheckError::Dns(message)
+            | CheckError::ConnectionRefused(message)
+            | CheckError::ConnectionReset(message)
+            | CheckError::Connection(message) => write!(f, "{message}"),
+            CheckError::ConnectTimeout => write!(f, "Timed out connecting"),
+            CheckError::ReadTimeout => write!(f, "Timed out waiting for the response"),
+            CheckError::Tls {
+                verify_error: Some(verify_error),
+                ..
+            } => write!(f, "{verify_error}"),
+            CheckError::Tls { message, .. } => write!(f, "{message}"),
+            CheckError::TooManyRedirects => write!(f, "Too many redirects"),
+            CheckError::BodyTooLarge { limit } => {
+                write!(f, "Response body larger than {limit} bytes")
+            }
+            CheckError::BodyRead(message) => write!(f, "Failed to read body: {message}"),
+            CheckError::HttpStatus(code) => write!(f, "HTTP {code}"),
+            CheckError::Assertion(failure) => write!(f, "{failure}"),
+            CheckError::UnexpectedBanner { expected, received } => {
+                write!(f, "Expected {expected:?} from the server, got {received:?}")
+            }
+            CheckError::PacketLoss { sent, received } => {
+                write!(f, "{received} of {sent} echo requests answered")
+            }
+            CheckError::NxDomain(name) => write!(f, "{name} doesn't exist"),
+            CheckError::UnexpectedAnswer { received, .. } if received.is_empty() => {
+                write!(f, "No records in the answer")
+            }
+            CheckError::UnexpectedAnswer { missing, received } => write!(
+                f,
+                "Expected {} in the answer, got {}",
+                missing.join(", "),
+                received.join(", ")
+            ),
+            CheckError::MissedHeartbeat { last: Some(last) } => {
+                write!(f, "No heartbeat since {}", last.to_rfc3339())
+            }
+            CheckError::MissedHeartbeat { last: None } => {
+                write!(f, "No heartbeat received since starting")
+            }
+            CheckError::JobFailed(Some(message)) => write!(f, "Job failed: {message}"),
+            CheckError::JobFailed(None) => write!(f, "Job failed"),
+        }
+    }
+}
+
+/// The failures recognisable from an I/O error's kind alone
+fn classify_io(kind: io::ErrorKind, message: &str) -> Option<CheckError> {
+    match kind {
+        io::ErrorKind::ConnectionRefused => {
+            Some(CheckError::ConnectionRefused(message.to_string()))
+        }
+        io::ErrorKind::ConnectionReset
+        | io::ErrorKind::ConnectionAborted
+        | io::ErrorKind::BrokenPipe
+        | io::ErrorKind::UnexpectedEof => Some(CheckError::ConnectionReset(message.to_string())),
+        _ => None,
+    }
+}
+
+/// Join an error with its sources, since reqwest's own message rarely says
+/// what went wrong
+fn describe(error: &dyn Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+
+    while let Some(cause) = source {
+        let text = cause.to_string();
+        if !message.contains(&text) {
+            message.push_str(": ");
+            message.push_str(&text);
+        }
+        source = cause.source();
+    }
+
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assertions::AssertionKind;
+    use crate::tls::test_support::{self_signed, serve};
+    use std::time::Duration;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpListener;
+
+    async fn request_error(url: &str, client: reqwest::Client) -> CheckError {
+        let error = client
+            .get(url)
+            .send()
+            .await
+            .expect_err("request should fail");
+        CheckError::from_reqwest(error)
+    }
+
+    #[tokio::test]
+    async fn test_classify_connection_refused() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+
+        let error =
+            request_error(&format!("http://127.0.0.1:{port}/"), reqwest::Client::new()).await;
+
+        assert_eq!(error.error_type(), "connection_refused");
+    }
+
+    #[tokio::test]
+    async fn test_classify_dns_error() {
+        let error = request_error("http://sammy-monitor.invalid/", reqwest::Client::new()).await;
+
+        assert_eq!(error.error_type(), "dns_error");
+    }
+
+    #[tokio::test]
+    async fn test_classify_read_timeout() {
+        // Accepts connections but never responds
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let mut connections = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                connections.push(stream);
+            }
+        });
+
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(200))
+            .build()
+            .unwrap();
+        let error = request_error(&format!("http://127.0.0.1:{port}/"), client).await;
+
+        assert_eq!(error, CheckError::ReadTimeout);
+    }
+
+    #[tokio::test]
+    async fn test_classify_connection_reset() {
+        // Closes every connection without responding
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let _ = stream.shutdown().await;
+            }
+        });
+
+        let error =
+            request_error(&format!("http://127.0.0.1:{port}/"), reqwest::Client::new()).await;
+
+        assert_eq!(error.error_type(), "connection_reset", "{error:?}");
+    }
+
+    #[tokio::test]
+    async fn test_classify_tls_error() {
+        // Speaks plain HTTP where TLS is expected
+        let mut server = mockito::Server::new_async().await;
+        let url = server.url().replace("http://", "https://");
+        let _mock = server.mock("GET", "/").create_async().await;
+
+        let error = request_error(&url, reqwest::Client::new()).await;
+
+        assert_eq!(error.error_type(), "tls_error");
+    }
+
+    #[tokio::test]
+    async fn test_classify_untrusted_certificate() {
+        let now = chrono::Utc::now().timestamp();
+        let (cert, key) = self_signed("localhost", now - 60, now + 3600);
+        let port = serve(&cert, &key).await;
+
+        let error = request_error(
+            &format!("https://localhost:{port}/"),
+            reqwest::Client::new(),
+        )
+        .await;
+
+        assert_eq!(error.error_type(), "tls_untrusted", "{error:?}");
+    }
+
+    #[test]
+    fn test_error_types() {
+        let failure = AssertionFailure {
+            kind: AssertionKind::Body,
+            message: "missing \"ok\"".to_string(),
+        };
+
+        assert_eq!(
+            CheckError::Assertion(failure).error_type(),
+            "assertion_failed_body"
+        );
+        assert_eq!(CheckError::HttpStatus(503).error_type(), "http_error");
+        assert_eq!(CheckError::HttpStatus(503).to_string(), "HTTP 503");
+        assert_eq!(
+            CheckError::BodyTooLarge { limit: 10 }.error_type(),
+            "body_too_large"
+        );
+        assert_eq!(
+            CheckError::TooManyRedirects.error_type(),
+            "too_many_redirects"
+        );
+        assert_eq!(CheckError::ConnectTimeout.error_type(), "connect_timeout");
+        assert_eq!(
+            CheckError::PacketLoss {
+                sent: 3,
+                received: 1
+            }
+            .to_string(),
+            "1 of 3 echo requests answered"
+        );
+        assert_eq!(
+            CheckError::UnexpectedAnswer {
+                missing: vec!["192.0.2.1".to_string()],
+                received: vec!["192.0.2.2".to_string(), "192.0.2.3".to_string()],
+            }
+            .to_string(),
+            "Expected 192.0.2.1 in the answer, got 192.0.2.2, 192.0.2.3"
+        );
+        assert_eq!(
+            CheckError::MissedHeartbeat {
+                last: Some("2024-01-01T03:00:00Z".parse().unwrap())
+            }
+            .to_string(),
+            "No heartbeat since 2024-01-01T03:00:00+00:00"
+        );
+        assert_eq!(CheckError::JobFailed(None).to_string(), "Job failed");
+        assert_eq!(CheckError::JobFailed(None).error_type(), "job_failed");
+    }
+}
@@ -0,0 +1,353 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::Router;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, watch};
+use uuid::Uuid;
+
+use crate::error::CheckError;
+use crate::settings::{MonitorConfig, MonitorType, Settings};
+use crate::timing::PhaseTimings;
+use crate::worker::MonitorResult;
+
+/// Longest part of a fail ping's body kept as the failure message
+const MESSAGE_LIMIT: usize = 200;
+
+/// What a job's ping reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    /// A run started, so its duration can be measured
+    Start,
+    /// A run finished successfully
+    Success,
+    /// A run failed
+    Fail,
+}
+
+/// A ping from a heartbeat monitor's job
+#[derive(Debug, Clone)]
+pub struct Heartbeat {
+    pub monitor_id: Uuid,
+    pub signal: Signal,
+    /// Sent along with a fail ping, e.g. the job's error output
+    pub message: Option<String>,
+    pub received: Instant,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl Heartbeat {
+    /// A ping received just now
+    pub fn new(monitor_id: Uuid, signal: Signal) -> Self {
+        Self {
+            monitor_id,
+            signal,
+            message: None,
+            received: Instant::now(),
+            timestamp: Utc::now(),
+        }
+    }
+}
+
+/// Endpoints the jobs of heartbeat monitors ping, passing each ping on to
+/// the worker.
+///
+/// A job pings `/heartbeat/{id}` when a run succeeds, and optionally
+/// `/heartbeat/{id}/start` when it starts and `/heartbeat/{id}/fail` when it
+/// fails. Both GET and POST are accepted, so a plain `curl` will do.
+pub struct HeartbeatEndpoint {
+    settings: watch::Receiver<Settings>,
+    heartbeats: mpsc::Sender<Heartbeat>,
+}
+
+impl HeartbeatEndpoint {
+    pub fn new(settings: watch::Receiver<Settings>, heartbeats: mpsc::Sender<Heartbeat>) -> Self {
+        Self {
+            settings,
+            heartbeats,
+        }
+    }
+
+    /// Routes of the endpoint, to be merged into the server's router
+    pub fn router(self) -> Router {
+        Router::new()
+            .route("/heartbeat/:id", get(success).post(success))
+            .route("/heartbeat/:id/start", get(start).post(start))
+            .route("/heartbeat/:id/fail", get(fail).post(fail))
+            .with_state(Arc::new(self))
+    }
+
+    async fn receive(&self, heartbeat: Heartbeat) -> (StatusCode, String) {
+        let known = self.settings.borrow().monitors.iter().any(|monitor| {
+            monitor.id == heartbeat.monitor_id && monitor.kind == MonitorType::Heartbeat
+        });
+        if !known {
+            return (
+                StatusCode::NOT_FOUND,
+                format!("heartbeat monitor {} not found\n", heartbeat.monitor_id),
+            );
+        }
+
+        match self.heartbeats.send(heartbeat).await {
+            Ok(()) => (StatusCode::OK, "OK\n".to_string()),
+            Err(_) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "worker is not running\n".to_string(),
+            ),
+        }
+    }
+}
+
+async fn success(
+    State(endpoint): State<Arc<HeartbeatEndpoint>>,
+    Path(monitor_id): Path<Uuid>,
+) -> (StatusCode, String) {
+    endpoint
+        .receive(Heartbeat::new(monitor_id, Signal::Success))
+        .await
+}
+
+async fn start(
+    State(endpoint): State<Arc<HeartbeatEndpoint>>,
+    Path(monitor_id): Path<Uuid>,
+) -> (StatusCode, String) {
+    endpoint
+        .receive(Heartbeat::new(monitor_id, Signal::Start))
+        .await
+}
+
+/// Take the request body, when there is one, as the failure message
+async fn fail(
+    State(endpoint): State<Arc<HeartbeatEndpoint>>,
+    Path(monitor_id): Path<Uuid>,
+    body: String,
+) -> (StatusCode, String) {
+    let message = body.trim();
+    let heartbeat = Heartbeat {
+        message: (!message.is_empty()).then(|| message.chars().take(MESSAGE_LIMIT).collect()),
+        ..Heartbeat::new(monitor_id, Signal::Fail)
+    };
+
+    endpoint.receive(heartbeat).await
+}
+
+/// A run of a job that pinged that it finished
+pub struct FinishedRun {
+    pub result: MonitorResult,
+    /// How long the run took, when it pinged that it started
+    pub duration: Option<Duration>,
+}
+
+/// Follows the runs of heartbeat monitors' jobs
+#[derive(Debug, Default)]
+pub struct HeartbeatTracker {
+    /// When each monitor's current run started
+    started: HashMap<Uuid, Instant>,
+    /// When each monitor's job last finished a run
+    last: HashMap<Uuid, DateTime<Utc>>,
+}
+
+impl HeartbeatTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Note a ping, returning the run it finished, if any
+    pub fn receive(
+        &mut self,
+        monitor: &MonitorConfig,
+        heartbeat: Heartbeat,
+    ) -> Option<FinishedRun> {
+        let error = match heartbeat.signal {
+            Signal::Start => {
+                self.started.insert(monitor.id, heartbeat.received);
+                return None;
+            }
+            Signal::Success => None,
+            Signal::Fail => Some(CheckError::JobFailed(heartbeat.message)),
+        };
+
+        let duration = self
+            .started
+            .remove(&monitor.id)
+            .map(|started| heartbeat.received.duration_since(started));
+        self.last.insert(monitor.id, heartbeat.timestamp);
+
+        Some(FinishedRun {
+            result: result(monitor, error, duration, heartbeat.timestamp),
+            duration,
+        })
+    }
+
+    /// The failed result of a monitor whose job hasn't pinged in time
+    pub fn missed(&self, monitor: &MonitorConfig) -> MonitorResult {
+        let error = CheckError::MissedHeartbeat {
+            last: self.last.get(&monitor.id).copied(),
+        };
+        result(monitor, Some(error), None, Utc::now())
+    }
+
+    /// Forget a removed monitor
+    pub fn remove(&mut self, monitor_id: Uuid) {
+        self.started.remove(&monitor_id);
+        self.last.remove(&monitor_id);
+    }
+}
+
+/// A heartbeat monitor's result, timed by the run's duration when known
+fn result(
+    monitor: &MonitorConfig,
+    error: Option<CheckError>,
+    duration: Option<Duration>,
+    timestamp: DateTime<Utc>,
+) -> MonitorResult {
+    MonitorResult {
+        monitor_id: monitor.id,
+        monitor_name: monitor.name.clone(),
+        url: monitor.target().into_owned(),
+        success: error.is_none(),
+        response_time_ms: duration.map_or(0, |duration| duration.as_millis() as u64),
+        status_code: None,
+        error,
+        timings: PhaseTimings::default(),
+        tls: None,
+        degraded: false,
+        attempts: 1,
+        in_maintenance: false,
+        ping: None,
+        timestamp,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn heartbeat_monitor() -> MonitorConfig {
+        MonitorConfig::new(
+            "Nightly Backup",
+            MonitorType::Heartbeat,
+            Duration::from_secs(86_400),
+        )
+    }
+
+    #[test]
+    fn test_runs() {
+        let monitor = heartbeat_monitor();
+        let mut tracker = HeartbeatTracker::new();
+
+        let started = Heartbeat::new(monitor.id, Signal::Start);
+        let received = started.received;
+        assert!(tracker.receive(&monitor, started).is_none());
+
+        let finished = Heartbeat {
+            received: received + Duration::from_secs(90),
+            ..Heartbeat::new(monitor.id, Signal::Success)
+        };
+        let run = tracker.receive(&monitor, finished).unwrap();
+        assert!(run.result.success);
+        assert_eq!(run.duration, Some(Duration::from_secs(90)));
+        assert_eq!(run.result.response_time_ms, 90_000);
+        assert_eq!(run.result.url, format!("/heartbeat/{}", monitor.id));
+
+        // Without a start ping the duration is unknown
+        let failed = Heartbeat {
+            message: Some("disk full".to_string()),
+            ..Heartbeat::new(monitor.id, Signal::Fail)
+        };
+        let run = tracker.receive(&monitor, failed).unwrap();
+        assert!(!run.result.success);
+        assert_eq!(run.duration, None);
+        assert_eq!(
+            run.result.error.unwrap().to_string(),
+            "Job failed: disk full"
+        );
+    }
+
+    #[test]
+    fn test_missed() {
+        let monitor = heartbeat_monitor();
+        let mut tracker = HeartbeatTracker::new();
+
+        let missed = tracker.missed(&monitor);
+        assert!(!missed.success);
+        assert_eq!(
+            missed.error,
+            Some(CheckError::MissedHeartbeat { last: None })
+        );
+
+        let heartbeat = Heartbeat::new(monitor.id, Signal::Success);
+        let timestamp = heartbeat.timestamp;
+        tracker.receive(&monitor, heartbeat);
+        assert_eq!(
+            tracker.missed(&monitor).error,
+            Some(CheckError::MissedHeartbeat {
+                last: Some(timestamp)
+            })
+        );
+        assert_eq!(
+            tracker.missed(&monitor).error.unwrap().error_type(),
+            "missed_heartbeat"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_endpoint() {
+        let monitor = heartbeat_monitor();
+        let monitor_id = monitor.id;
+        let settings = Settings::from_str(&format!(
+            "[[monitors]]\nid = \"{monitor_id}\"\nname = \"Backup\"\ntype = \"heartbeat\"\ninterval = \"1d\"\nenabled = true\n"
+        ))
+        .unwrap();
+
+        let (_settings_sender, settings_updates) = watch::channel(settings);
+        let (sender, mut heartbeats) = mpsc::channel(4);
+        let router = HeartbeatEndpoint::new(settings_updates, sender).router();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let client = reqwest::Client::new();
+        let base = format!("http://{address}/heartbeat");
+
+        let response = client
+            .get(format!("{base}/{monitor_id}/start"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(heartbeats.recv().await.unwrap().signal, Signal::Start);
+
+        let response = client
+            .post(format!("{base}/{monitor_id}"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(heartbeats.recv().await.unwrap().signal, Signal::Success);
+
+        let response = client
+            .post(format!("{base}/{monitor_id}/fail"))
+            .body("exit code 2\n")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let heartbeat = heartbeats.recv().await.unwrap();
+        assert_eq!(heartbeat.signal, Signal::Fail);
+        assert_eq!(heartbeat.message.as_deref(), Some("exit code 2"));
+
+        // Only heartbeat monitors are pinged
+        let response = client
+            .get(format!("{base}/{}", Uuid::new_v4()))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+    }
+}
@@ -0,0 +1,503 @@
+use chrono::{DateTime, Duration as TimeDelta, DurationRound, Utc};
+use log::error;
+use rusqlite::{params, Connection, Row};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::sync::Mutex;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::settings::HistorySettings;
+use crate::worker::MonitorResult;
+
+/// How often old results are downsampled and expired
+pub const COMPACT_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// A stored check result, or a summary of several once downsampled
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub monitor_id: Uuid,
+    /// When the check ran, or the start of the downsampled period
+    pub timestamp: DateTime<Utc>,
+    /// Outcome of the latest check summarized
+    pub up: bool,
+    #[serde(default)]
+    pub degraded: bool,
+    /// Response time, averaged once downsampled
+    pub response_time_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_code: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Number of checks summarized, more than one once downsampled
+    pub checks: u32,
+    /// How many of those checks succeeded
+    pub successes: u32,
+}
+
+impl From<&MonitorResult> for HistoryEntry {
+    fn from(result: &MonitorResult) -> Self {
+        Self {
+            monitor_id: result.monitor_id,
+            timestamp: result.timestamp,
+            up: result.success,
+            degraded: result.degraded,
+            response_time_ms: result.response_time_ms,
+            status_code: result.status_code,
+            error_type: result.error.as_ref().map(|e| e.error_type().to_string()),
+            error: result.error.as_ref().map(|e| e.to_string()),
+            checks: 1,
+            successes: u32::from(result.success),
+        }
+    }
+}
+
+impl HistoryEntry {
+    /// Fold a later entry of the same period into this one
+    fn merge(&mut self, later: HistoryEntry) {
+        let checks = u64::from(self.checks) + u64::from(later.checks);
+        self.response_time_ms = (self.response_time_ms * u64::from(self.checks)
+            + later.response_time_ms * u64::from(later.checks))
+            / checks;
+        self.checks += later.checks;
+        self.successes += later.successes;
+        self.up = later.up;
+        self.degraded = later.degraded;
+        self.status_code = later.status_code;
+        self.error_type = later.error_type;
+        self.error = later.error;
+    }
+}
+
+/// Columns of an entry, in the order [`from_row`] reads them
+const COLUMNS: &str = "monitor_id, timestamp, up, degraded, response_time_ms, status_code, \
+                       error_type, error, checks, successes";
+
+/// Check results kept in a SQLite database.
+///
+/// Queries go through an index on monitor and time, so they don't depend on
+/// how much history is kept. [`HistoryStore::compact`] expires results past
+/// the retention period and downsamples old ones. Every method blocks on
+/// disk, so async callers should run them on a blocking thread.
+pub struct HistoryStore {
+    settings: HistorySettings,
+    connection: Mutex<Connection>,
+}
+
+impl HistoryStore {
+    /// Open the history database, creating it if needed, and compact it
+    pub fn open(settings: &HistorySettings) -> io::Result<Self> {
+        if let Some(parent) = settings.path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+
+        let connection = Connection::open(&settings.path).map_err(sql_error)?;
+        connection
+            .execute_batch(
+                "PRAGMA journal_mode = WAL;
+                 CREATE TABLE IF NOT EXISTS history (
+                     monitor_id TEXT NOT NULL,
+                     timestamp INTEGER NOT NULL,
+                     up INTEGER NOT NULL,
+                     degraded INTEGER NOT NULL,
+                     response_time_ms INTEGER NOT NULL,
+                     status_code INTEGER,
+                     error_type TEXT,
+                     error TEXT,
+                     checks INTEGER NOT NULL,
+                     successes INTEGER NOT NULL
+                 );
+                 CREATE INDEX IF NOT EXISTS history_monitor_time
+                     ON history (monitor_id, timestamp);",
+            )
+            .map_err(sql_error)?;
+
+        let store = Self {
+            settings: settings.clone(),
+            connection: Mutex::new(connection),
+        };
+        store.compact(Utc::now())?;
+        Ok(store)
+    }
+
+    /// Store the result of a finished check
+    pub fn record(&self, result: &MonitorResult) -> io::Result<()> {
+        let connection = self.connection.lock().unwrap();
+        insert(&connection, &HistoryEntry::from(result)).map_err(sql_error)
+    }
+
+    /// The latest entry of a monitor
+    pub fn last(&self, monitor_id: Uuid) -> Option<HistoryEntry> {
+        self.latest(monitor_id, 1).pop()
+    }
+
+    /// The latest `limit` entries of a monitor, newest first
+    pub fn latest(&self, monitor_id: Uuid, limit: usize) -> Vec<HistoryEntry> {
+        let connection = self.connection.lock().unwrap();
+        query(
+            &connection,
+            &format!(
+                "SELECT {COLUMNS} FROM history WHERE monitor_id = ?1 \
+                 ORDER BY timestamp DESC LIMIT ?2"
+            ),
+            params![
+                monitor_id.to_string(),
+                i64::try_from(limit).unwrap_or(i64::MAX)
+            ],
+        )
+    }
+
+    /// Entries of a monitor from `from` up to, but not including, `to`,
+    /// oldest first
+    pub fn between(
+        &self,
+        monitor_id: Uuid,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Vec<HistoryEntry> {
+        let connection = self.connection.lock().unwrap();
+        query(
+            &connection,
+            &format!(
+                "SELECT {COLUMNS} FROM history \
+                 WHERE monitor_id = ?1 AND timestamp >= ?2 AND timestamp < ?3 \
+                 ORDER BY timestamp"
+            ),
+            params![monitor_id.to_string(), micros(from), micros(to)],
+        )
+    }
+
+    /// Drop entries older than the retention period and merge those older
+    /// than `downsample_after`.
+    ///
+    /// Only whole periods are downsampled, so each is merged once. Entries
+    /// that already summarize their period are left alone.
+    pub fn compact(&self, now: DateTime<Utc>) -> io::Result<()> {
+        let expire_before = now - delta(self.settings.retention);
+        let period = delta(self.settings.downsample_interval);
+        let downsample_before = now - delta(self.settings.downsample_after);
+        let downsample_before = downsample_before
+            .duration_trunc(period)
+            .unwrap_or(downsample_before);
+
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction().map_err(sql_error)?;
+        transaction
+            .execute(
+                "DELETE FROM history WHERE timestamp < ?1",
+                [micros(expire_before)],
+            )
+            .map_err(sql_error)?;
+
+        // Single checks in periods that still have one not at the start of
+        // the period, i.e. that weren't downsampled yet
+        let pending = "timestamp < ?1 AND checks = 1 \
+             AND (monitor_id, timestamp - timestamp % ?2) IN ( \
+                 SELECT monitor_id, timestamp - timestamp % ?2 FROM history \
+                 WHERE timestamp < ?1 AND checks = 1 AND timestamp % ?2 != 0)";
+        let bounds = params![
+            micros(downsample_before),
+            period.num_microseconds().unwrap_or(i64::MAX).max(1)
+        ];
+
+        let old = query(
+            &transaction,
+            &format!(
+                "SELECT {COLUMNS} FROM history WHERE {pending} ORDER BY monitor_id, timestamp"
+            ),
+            bounds,
+        );
+        transaction
+            .execute(&format!("DELETE FROM history WHERE {pending}"), bounds)
+            .map_err(sql_error)?;
+
+        let mut merged: Vec<HistoryEntry> = Vec::new();
+        for entry in old {
+            let start = entry
+                .timestamp
+                .duration_trunc(period)
+                .unwrap_or(entry.timestamp);
+            match merged.last_mut() {
+                Some(last) if last.monitor_id == entry.monitor_id && last.timestamp == start => {
+                    last.merge(entry)
+                }
+                _ => merged.push(HistoryEntry {
+                    timestamp: start,
+                    ..entry
+                }),
+            }
+        }
+        for entry in &merged {
+            insert(&transaction, entry).map_err(sql_error)?;
+        }
+
+        transaction.commit().map_err(sql_error)
+    }
+}
+
+fn insert(connection: &Connection, entry: &HistoryEntry) -> rusqlite::Result<()> {
+    connection.execute(
+        &format!(
+            "INSERT INTO history ({COLUMNS}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)"
+        ),
+        params![
+            entry.monitor_id.to_string(),
+            micros(entry.timestamp),
+            entry.up,
+            entry.degraded,
+            i64::try_from(entry.response_time_ms).unwrap_or(i64::MAX),
+            entry.status_code,
+            entry.error_type,
+            entry.error,
+            entry.checks,
+            entry.successes,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Entries selected by `sql`, skipping rows that can't be read
+fn query(connection: &Connection, sql: &str, params: impl rusqlite::Params) -> Vec<HistoryEntry> {
+    let result = connection.prepare_cached(sql).and_then(|mut statement| {
+        statement
+            .query_map(params, from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()
+    });
+
+    result.unwrap_or_else(|e| {
+        error!("Failed to query history: {e}");
+        Vec::new()
+    })
+}
+
+fn from_row(row: &Row) -> rusqlite::Result<HistoryEntry> {
+    let monitor_id: String = row.get(0)?;
+    let timestamp: i64 = row.get(1)?;
+    let response_time_ms: i64 = row.get(4)?;
+
+    Ok(HistoryEntry {
+        monitor_id: monitor_id.parse().unwrap_or_default(),
+        timestamp: DateTime::from_timestamp_micros(timestamp).unwrap_or_default(),
+        up: row.get(2)?,
+        degraded: row.get(3)?,
+        response_time_ms: u64::try_from(response_time_ms).unwrap_or(0),
+        status_code: row.get(5)?,
+        error_type: row.get(6)?,
+        error: row.get(7)?,
+        checks: row.get(8)?,
+        successes: row.get(9)?,
+    })
+}
+
+/// Stored form of a timestamp, microseconds since the epoch
+fn micros(timestamp: DateTime<Utc>) -> i64 {
+    timestamp
+        .timestamp()
+        .saturating_mul(1_000_000)
+        .saturating_add(i64::from(timestamp.timestamp_subsec_micros()))
+}
+
+fn sql_error(error: rusqlite::Error) -> io::Error {
+    io::Error::other(error)
+}
+
+fn delta(duration: Duration) -> TimeDelta {
+    TimeDelta::from_std(duration).unwrap_or(TimeDelta::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::CheckError;
+    use tempfile::TempDir;
+
+    fn result(monitor_id: Uuid, timestamp: DateTime<Utc>, response_time_ms: u64) -> MonitorResult {
+        MonitorResult {
+            response_time_ms,
+            status_code: Some(200),
+            timestamp,
+            ..MonitorResult::new(monitor_id, None)
+        }
+    }
+
+    fn settings(dir: &TempDir) -> HistorySettings {
+        HistorySettings::new(dir.path().join("history.db"))
+    }
+
+    fn at(timestamp: &str) -> DateTime<Utc> {
+        timestamp.parse().unwrap()
+    }
+
+    #[test]
+    fn test_record_and_query() {
+        let dir = TempDir::new().unwrap();
+        let store = HistoryStore::open(&settings(&dir)).unwrap();
+        let monitor_id = Uuid::new_v4();
+        let start = Utc::now();
+
+        for minute in 0..5 {
+            store
+                .record(&result(
+                    monitor_id,
+                    start + TimeDelta::minutes(minute),
+                    100 + minute as u64,
+                ))
+                .unwrap();
+        }
+
+        let latest = store.latest(monitor_id, 2);
+        assert_eq!(latest.len(), 2);
+        assert_eq!(latest[0].response_time_ms, 104);
+        assert_eq!(latest[1].response_time_ms, 103);
+
+        let between = store.between(
+            monitor_id,
+            start + TimeDelta::minutes(1),
+            start + TimeDelta::minutes(3),
+        );
+        assert_eq!(
+            between
+                .iter()
+                .map(|entry| entry.response_time_ms)
+                .collect::<Vec<_>>(),
+            vec![101, 102]
+        );
+
+        assert!(store.latest(Uuid::new_v4(), 10).is_empty());
+        assert_eq!(store.last(monitor_id).unwrap().response_time_ms, 104);
+    }
+
+    #[test]
+    fn test_reopen_restores_entries() {
+        let dir = TempDir::new().unwrap();
+        let monitor_id = Uuid::new_v4();
+
+        {
+            let store = HistoryStore::open(&settings(&dir)).unwrap();
+            let mut failed = result(monitor_id, Utc::now(), 250);
+            failed.success = false;
+            failed.status_code = Some(503);
+            failed.error = Some(CheckError::HttpStatus(503));
+            store.record(&failed).unwrap();
+        }
+
+        let store = HistoryStore::open(&settings(&dir)).unwrap();
+        let last = store.last(monitor_id).unwrap();
+        assert!(!last.up);
+        assert_eq!(last.status_code, Some(503));
+        assert_eq!(last.error_type.as_deref(), Some("http_error"));
+    }
+
+    #[test]
+    fn test_compact_downsamples_and_expires() {
+        let dir = TempDir::new().unwrap();
+        let mut settings = settings(&dir);
+        settings.retention = Duration::from_secs(2 * 86_400);
+        let store = HistoryStore::open(&settings).unwrap();
+        let monitor_id = Uuid::new_v4();
+
+        // Expired
+        store
+            .record(&result(monitor_id, at("2024-01-01T09:00:00Z"), 100))
+            .unwrap();
+        // Downsampled into 10:00 and 11:00
+        store
+            .record(&result(monitor_id, at("2024-01-02T10:10:00Z"), 100))
+            .unwrap();
+        let mut failed = result(monitor_id, at("2024-01-02T10:40:00Z"), 300);
+        failed.success = false;
+        store.record(&failed).unwrap();
+        store
+            .record(&result(monitor_id, at("2024-01-02T11:05:00Z"), 50))
+            .unwrap();
+        // Kept as is
+        store
+            .record(&result(monitor_id, at("2024-01-03T10:30:00Z"), 80))
+            .unwrap();
+
+        store.compact(at("2024-01-03T12:00:00Z")).unwrap();
+
+        let entries = store.between(
+            monitor_id,
+            DateTime::<Utc>::MIN_UTC,
+            DateTime::<Utc>::MAX_UTC,
+        );
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].timestamp, at("2024-01-02T10:00:00Z"));
+        assert_eq!(entries[0].checks, 2);
+        assert_eq!(entries[0].successes, 1);
+        assert_eq!(entries[0].response_time_ms, 200);
+        assert!(!entries[0].up);
+        assert_eq!(entries[1].timestamp, at("2024-01-02T11:00:00Z"));
+        assert_eq!(entries[1].checks, 1);
+        assert_eq!(entries[2].timestamp, at("2024-01-03T10:30:00Z"));
+
+        // Compacting again leaves the downsampled entries alone
+        store.compact(at("2024-01-03T12:00:00Z")).unwrap();
+        assert_eq!(
+            store.between(
+                monitor_id,
+                DateTime::<Utc>::MIN_UTC,
+                DateTime::<Utc>::MAX_UTC
+            ),
+            entries
+        );
+    }
+
+    #[test]
+    fn test_compact_merges_each_period_once() {
+        let dir = TempDir::new().unwrap();
+        let store = HistoryStore::open(&settings(&dir)).unwrap();
+        let monitor_id = Uuid::new_v4();
+        let rowids = || -> Vec<i64> {
+            let connection = store.connection.lock().unwrap();
+            let mut statement = connection
+                .prepare("SELECT rowid FROM history ORDER BY rowid")
+                .unwrap();
+            let rowids = statement
+                .query_map([], |row| row.get(0))
+                .unwrap()
+                .collect::<rusqlite::Result<_>>()
+                .unwrap();
+            rowids
+        };
+
+        for timestamp in ["2024-01-02T10:10:00Z", "2024-01-02T10:40:00Z"] {
+            store
+                .record(&result(monitor_id, at(timestamp), 100))
+                .unwrap();
+        }
+        store
+            .record(&result(monitor_id, at("2024-01-02T11:05:00Z"), 100))
+            .unwrap();
+
+        // 11:00 isn't over a day old yet, so it waits until it all is
+        store.compact(at("2024-01-03T11:30:00Z")).unwrap();
+        let entries = store.between(
+            monitor_id,
+            DateTime::<Utc>::MIN_UTC,
+            DateTime::<Utc>::MAX_UTC,
+        );
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].timestamp, at("2024-01-02T10:00:00Z"));
+        assert_eq!(entries[0].checks, 2);
+        assert_eq!(entries[1].timestamp, at("2024-01-02T11:05:00Z"));
+
+        store.compact(at("2024-01-03T12:00:00Z")).unwrap();
+        let downsampled = rowids();
+        assert_eq!(
+            store.last(monitor_id).unwrap().timestamp,
+            at("2024-01-02T11:00:00Z")
+        );
+
+        // Downsampled periods aren't read back and written again
+        store.compact(at("2024-01-03T13:00:00Z")).unwrap();
+        store.compact(at("2024-01-04T12:00:00Z")).unwrap();
+        assert_eq!(rowids(), downsampled);
+    }
+}
@@ -1,3 +1,24 @@
+pub mod alerting;
+pub mod api;
+pub mod assertions;
+pub mod checks;
+pub mod client;
+pub mod duration;
+pub mod error;
+pub mod heartbeat;
+pub mod history;
+pub mod maintenance;
 pub mod metrics;
+pub mod notifiers;
+pub mod recorder;
+pub mod reload;
+pub mod scheduler;
+pub mod secret;
+pub mod server;
 pub mod settings;
+pub mod state;
+pub mod status;
+pub mod timing;
+pub mod tls;
+pub mod transitions;
 pub mod worker;
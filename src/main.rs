@@ -1,48 +1,78 @@
 use axum::{routing::get, Router};
 use clap::{arg, Command};
-use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use sammy_monitor::api::{Api, UnsavedChanges};
+use sammy_monitor::heartbeat::HeartbeatEndpoint;
+use sammy_monitor::history::{HistoryStore, COMPACT_INTERVAL};
 use sammy_monitor::metrics::init_metrics;
-use sammy_monitor::settings::Settings;
+use sammy_monitor::recorder::{MetricsHandle, MetricsRecorder};
+use sammy_monitor::reload::{watch_settings, DEFAULT_POLL_INTERVAL};
+use sammy_monitor::server::Server;
+use sammy_monitor::settings::{ServerSettings, ServerTlsConfig, Settings};
+use sammy_monitor::status::StatusPage;
 use sammy_monitor::worker::Worker;
+use std::net::IpAddr;
 use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::{mpsc, watch};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 const APP_NAME: &str = "sammy_monitor";
 const APP_VERSION: &str = "0.1.0";
 
-fn setup_metrics_recorder() -> PrometheusHandle {
-    let handle = PrometheusBuilder::new()
+fn setup_metrics_recorder() -> MetricsHandle {
+    let handle = MetricsRecorder::builder()
         .add_global_label("app", "sammy_monitor")
-        .set_buckets_for_metric(
-            Matcher::Full("http_monitor_response_time_seconds".to_string()),
+        .set_buckets(
+            "http_monitor_response_time_seconds",
             &[0.05, 0.1, 0.2, 0.5, 1.0, 2.0, 5.0, 10.0, 20.0, 30.0],
         )
-        .expect("Failed to set histogram buckets")
-        .install_recorder()
-        .expect("Failed to install Prometheus recorder");
+        .set_buckets(
+            "http_monitor_phase_duration_seconds",
+            &[
+                0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+            ],
+        )
+        .set_buckets(
+            "http_monitor_dns_resolution_seconds",
+            &[
+                0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+            ],
+        )
+        .install()
+        .expect("Failed to install metrics recorder");
 
     init_metrics();
     handle
 }
 
-fn create_app() -> Router {
-    let handle = setup_metrics_recorder();
+fn create_app(handle: MetricsHandle) -> Router {
     Router::new().route("/metrics", get(move || async move { handle.render() }))
 }
 
-async fn start_server() {
-    let app = create_app();
+async fn start_server(settings: ServerSettings, app: Router) {
+    let server = Server::bind(&settings)
+        .await
+        .expect("failed to bind metrics server");
+    server.serve(app).await.unwrap();
+}
+
+async fn compact_history(history: Arc<HistoryStore>) {
+    let mut interval = tokio::time::interval(COMPACT_INTERVAL);
+    // The store was compacted when opened
+    interval.tick().await;
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
-    tracing::info!(
-        "Metrics server listening on {}",
-        listener.local_addr().unwrap()
-    );
-    axum::serve(listener, app).await.unwrap();
+    loop {
+        interval.tick().await;
+        let history = history.clone();
+        match tokio::task::spawn_blocking(move || history.compact(chrono::Utc::now())).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => tracing::error!("Failed to compact history: {e}"),
+            Err(e) => tracing::error!("History compaction failed: {e}"),
+        }
+    }
 }
 
-async fn start_worker(settings: Settings) {
-    let mut worker = Worker::new(settings);
+async fn start_worker(mut worker: Worker) {
     worker.start().await;
 }
 
@@ -57,6 +87,50 @@ fn cli() -> clap::Command {
                 .default_value("./settings.toml")
                 .help("Path to the settings file"),
         )
+        .arg(
+            arg!(--bind <ADDR> "Address for the metrics server to listen on")
+                .value_parser(clap::value_parser!(IpAddr)),
+        )
+        .arg(
+            arg!(--port <PORT> "Port for the metrics server to listen on")
+                .value_parser(clap::value_parser!(u16)),
+        )
+        .arg(
+            arg!(--"unix-socket" <PATH> "Serve metrics on a Unix domain socket instead of TCP")
+                .value_parser(clap::value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--"tls-cert" <PATH> "PEM certificate chain for serving metrics over HTTPS")
+                .value_parser(clap::value_parser!(PathBuf))
+                .requires("tls-key"),
+        )
+        .arg(
+            arg!(--"tls-key" <PATH> "PEM private key for --tls-cert")
+                .value_parser(clap::value_parser!(PathBuf))
+                .requires("tls-cert"),
+        )
+}
+
+/// Apply server flags given on the command line over the settings file
+fn apply_server_args(matches: &clap::ArgMatches, server: &mut ServerSettings) {
+    if let Some(bind) = matches.get_one::<IpAddr>("bind") {
+        server.bind_address = *bind;
+    }
+    if let Some(port) = matches.get_one::<u16>("port") {
+        server.port = *port;
+    }
+    if let Some(path) = matches.get_one::<PathBuf>("unix-socket") {
+        server.unix_socket = Some(path.clone());
+    }
+    if let (Some(cert), Some(key)) = (
+        matches.get_one::<PathBuf>("tls-cert"),
+        matches.get_one::<PathBuf>("tls-key"),
+    ) {
+        server.tls = Some(ServerTlsConfig {
+            cert: cert.clone(),
+            key: key.clone(),
+        });
+    }
 }
 
 #[tokio::main]
@@ -67,8 +141,13 @@ async fn main() -> anyhow::Result<()> {
         .get_one::<String>("settings")
         .expect("settings is required");
 
-    let settings =
-        Settings::load(&PathBuf::from(settings_path.as_str())).expect("failed to load settings");
+    let settings_path = PathBuf::from(settings_path.as_str());
+    let mut settings = Settings::load(&settings_path).expect("failed to load settings");
+
+    apply_server_args(&matches, &mut settings.server);
+    if let Err(e) = settings.server.validate() {
+        panic!("invalid server settings: {e}");
+    }
 
     tracing_subscriber::registry()
         .with(
@@ -79,7 +158,72 @@ async fn main() -> anyhow::Result<()> {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
-    let (_server, _worker) = tokio::join!(start_server(), start_worker(settings));
+    let handle = setup_metrics_recorder();
+
+    let (settings_sender, settings_updates) = watch::channel(settings.clone());
+    let (check_sender, check_requests) = mpsc::channel(16);
+    let (heartbeat_sender, heartbeat_pings) = mpsc::channel(64);
+
+    // Reloaded settings are published with the API's unsaved changes
+    // applied over them
+    let unsaved = Arc::new(UnsavedChanges::default());
+    let (reload_sender, reloads) = watch::channel(settings.clone());
+    let reload_path = settings_path.clone();
+    tokio::spawn(async move {
+        if let Err(e) = watch_settings(reload_path, DEFAULT_POLL_INTERVAL, reload_sender).await {
+            tracing::error!("Settings reload disabled: {e}");
+        }
+    });
+    tokio::spawn(
+        unsaved
+            .clone()
+            .apply_to_reloads(reloads, settings_sender.clone()),
+    );
+
+    let history = settings.history.as_ref().map(|history_settings| {
+        let history =
+            Arc::new(HistoryStore::open(history_settings).expect("failed to open history"));
+        tokio::spawn(compact_history(history.clone()));
+        history
+    });
+
+    let mut worker = Worker::new(settings.clone())
+        .with_settings_updates(settings_updates)
+        .with_check_requests(check_requests)
+        .with_heartbeats(heartbeat_pings);
+    if let Some(history) = &history {
+        worker = worker.with_history(history.clone());
+    }
+
+    let mut app = create_app(handle)
+        .merge(HeartbeatEndpoint::new(settings_sender.subscribe(), heartbeat_sender).router());
+
+    if settings.status_page.enabled {
+        let mut status_page = StatusPage::new(settings_sender.subscribe(), worker.states());
+        if let Some(history) = &history {
+            status_page = status_page.with_history(history.clone());
+        }
+        app = app.merge(status_page.router());
+    }
+
+    if settings.api.enabled {
+        let mut api = Api::new(
+            settings_sender,
+            check_sender,
+            worker.states(),
+            Some(settings_path),
+        )
+        .with_unsaved_changes(unsaved);
+        if let Some(history) = history {
+            api = api.with_history(history);
+        }
+        app = app.merge(api.router());
+    }
+
+    let (_server, _worker) = tokio::join!(
+        start_server(settings.server.clone(), app),
+        start_worker(worker)
+    );
 
     Ok(())
 }
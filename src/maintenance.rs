@@ -0,0 +1,350 @@
+use chrono::{DateTime, NaiveDateTime, TimeDelta, TimeZone, Utc};
+use chrono_tz::Tz;
+use cron::Schedule;
+use log::error;
+use std::str::FromStr;
+
+use crate::settings::{MaintenanceMode, MaintenanceWindow, MonitorConfig};
+
+/// Local time formats accepted for one-off windows besides RFC 3339
+const LOCAL_TIME_FORMATS: [&str; 4] = [
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%dT%H:%M:%S",
+];
+
+/// When a maintenance window is open
+#[derive(Debug, Clone)]
+enum Period {
+    Once {
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    },
+    Recurring {
+        schedule: Box<Schedule>,
+        duration: TimeDelta,
+        timezone: Tz,
+    },
+}
+
+/// A maintenance window, parsed and ready to be checked against the clock
+#[derive(Debug, Clone)]
+pub struct Window {
+    settings: MaintenanceWindow,
+    period: Period,
+}
+
+impl Window {
+    pub fn new(settings: &MaintenanceWindow) -> Result<Self, String> {
+        let timezone = match &settings.timezone {
+            Some(name) => Tz::from_str(name).map_err(|_| format!("unknown timezone \"{name}\""))?,
+            None => Tz::UTC,
+        };
+
+        let period = match (&settings.start, &settings.end, &settings.schedule) {
+            (Some(start), Some(end), None) => {
+                if settings.duration.is_some() {
+                    return Err("duration only applies to windows with a schedule".to_string());
+                }
+
+                let start = parse_time(start, timezone)?;
+                let end = parse_time(end, timezone)?;
+                if end <= start {
+                    return Err("end must be after start".to_string());
+                }
+                Period::Once { start, end }
+            }
+            (None, None, Some(schedule)) => {
+                let duration = settings
+                    .duration
+                    .filter(|duration| !duration.is_zero())
+                    .ok_or("windows with a schedule need a duration greater than 0")?;
+                Period::Recurring {
+                    schedule: Box::new(parse_schedule(schedule)?),
+                    duration: TimeDelta::from_std(duration)
+                        .map_err(|_| "duration is too long".to_string())?,
+                    timezone,
+                }
+            }
+            _ => return Err("set either start and end, or schedule and duration".to_string()),
+        };
+
+        Ok(Self {
+            settings: settings.clone(),
+            period,
+        })
+    }
+
+    /// Whether the window is open at `time`
+    pub fn is_open(&self, time: DateTime<Utc>) -> bool {
+        match &self.period {
+            Period::Once { start, end } => *start <= time && time < *end,
+            Period::Recurring {
+                schedule,
+                duration,
+                timezone,
+            } => {
+                // The window opened most recently at or before `time`
+                let time = time.with_timezone(timezone);
+                schedule
+                    .after(&(time + TimeDelta::seconds(1)))
+                    .next_back()
+                    .is_some_and(|opened| time < opened + *duration)
+            }
+        }
+    }
+}
+
+/// The configured maintenance windows
+#[derive(Debug, Clone, Default)]
+pub struct Maintenance {
+    windows: Vec<Window>,
+}
+
+impl Maintenance {
+    /// Windows that don't parse are logged and left out; settings
+    /// validation rejects them before they get here
+    pub fn new(windows: &[MaintenanceWindow]) -> Self {
+        let windows = windows
+            .iter()
+            .filter_map(|window| match Window::new(window) {
+                Ok(parsed) => Some(parsed),
+                Err(e) => {
+                    error!("Ignoring maintenance window {}: {e}", window.name);
+                    None
+                }
+            })
+            .collect();
+
+        Self { windows }
+    }
+
+    /// How checks of `monitor` are treated at `time`, `None` outside
+    /// maintenance. Skipping wins when open windows disagree.
+    pub fn mode(&self, monitor: &MonitorConfig, time: DateTime<Utc>) -> Option<MaintenanceMode> {
+        self.windows
+            .iter()
+            .filter(|window| window.settings.applies_to(monitor) && window.is_open(time))
+            .map(|window| window.settings.mode)
+            .reduce(|mode, other| {
+                if mode == MaintenanceMode::Skip || other == MaintenanceMode::Skip {
+                    MaintenanceMode::Skip
+                } else {
+                    MaintenanceMode::Mark
+                }
+            })
+    }
+}
+
+/// Parse an RFC 3339 timestamp, or a local time in `timezone`
+fn parse_time(text: &str, timezone: Tz) -> Result<DateTime<Utc>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(text) {
+        return Ok(time.with_timezone(&Utc));
+    }
+
+    let local = LOCAL_TIME_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+        .ok_or_else(|| {
+            format!("invalid time \"{text}\": expected e.g. \"2026-11-01 22:00\" or RFC 3339")
+        })?;
+
+    timezone
+        .from_local_datetime(&local)
+        .earliest()
+        .map(|time| time.with_timezone(&Utc))
+        .ok_or_else(|| format!("time \"{text}\" doesn't exist in {timezone}"))
+}
+
+/// Parse a five-field cron expression: minute, hour, day of month, month
+/// and day of week
+fn parse_schedule(expression: &str) -> Result<Schedule, String> {
+    let fields: Vec<&str> = expression.split_whitespace().collect();
+    let [minute, hour, day, month, weekday] = fields[..] else {
+        return Err(format!(
+            "invalid schedule \"{expression}\": expected five fields, minute hour day month weekday"
+        ));
+    };
+
+    // The cron crate also wants seconds
+    let expression_with_seconds =
+        format!("0 {minute} {hour} {day} {month} {}", weekday_names(weekday));
+    Schedule::from_str(&expression_with_seconds)
+        .map_err(|e| format!("invalid schedule \"{expression}\": {e}"))
+}
+
+/// Spell out numbered weekdays. Cron counts from 0 or 7 for Sunday, while
+/// the cron crate counts from 1 for Sunday; names mean the same to both.
+fn weekday_names(field: &str) -> String {
+    const NAMES: [&str; 8] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT", "SUN"];
+    let name = |day: &str| -> String {
+        day.parse::<usize>()
+            .ok()
+            .and_then(|day| NAMES.get(day))
+            .map_or_else(|| day.to_string(), |name| name.to_string())
+    };
+
+    field
+        .split(',')
+        .map(|item| {
+            let (days, step) = match item.split_once('/') {
+                Some((days, step)) => (days, Some(step)),
+                None => (item, None),
+            };
+            let days = match days.split_once('-') {
+                // A range ending on Sunday as 7 wraps around in the cron crate
+                Some((first, "7")) if step.is_none() && first != "0" && first != "7" => {
+                    format!("{}-SAT,SUN", name(first))
+                }
+                Some((first, last)) => format!("{}-{}", name(first), name(last)),
+                None => name(days),
+            };
+            match step {
+                Some(step) => format!("{days}/{step}"),
+                None => days,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::MonitorType;
+    use std::time::Duration;
+
+    fn window(name: &str) -> MaintenanceWindow {
+        MaintenanceWindow {
+            name: name.to_string(),
+            start: None,
+            end: None,
+            schedule: None,
+            duration: None,
+            timezone: None,
+            monitors: Vec::new(),
+            tags: Vec::new(),
+            mode: MaintenanceMode::Skip,
+        }
+    }
+
+    fn monitor(tags: &[&str]) -> MonitorConfig {
+        MonitorConfig {
+            url: "https://maintenance.example.com".to_string(),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            ..MonitorConfig::new(
+                "Maintenance Test",
+                MonitorType::Http,
+                Duration::from_secs(60),
+            )
+        }
+    }
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time).unwrap().to_utc()
+    }
+
+    #[test]
+    fn test_one_off_window() {
+        let mut settings = window("Migration");
+        settings.start = Some("2026-11-01 22:00".to_string());
+        settings.end = Some("2026-11-01T23:30:00+01:00".to_string());
+        settings.timezone = Some("Europe/Berlin".to_string());
+        let window = Window::new(&settings).unwrap();
+
+        assert!(!window.is_open(at("2026-11-01T20:59:59Z")));
+        assert!(window.is_open(at("2026-11-01T21:00:00Z")));
+        assert!(window.is_open(at("2026-11-01T22:29:59Z")));
+        assert!(!window.is_open(at("2026-11-01T22:30:00Z")));
+    }
+
+    #[test]
+    fn test_recurring_window() {
+        let mut settings = window("Deploys");
+        settings.schedule = Some("30 1 * * 0".to_string());
+        settings.duration = Some(Duration::from_secs(3600));
+        settings.timezone = Some("America/New_York".to_string());
+        let window = Window::new(&settings).unwrap();
+
+        // Sundays at 01:30 New York time, 05:30 UTC in summer
+        assert!(!window.is_open(at("2026-07-05T05:29:59Z")));
+        assert!(window.is_open(at("2026-07-05T05:30:00Z")));
+        assert!(window.is_open(at("2026-07-05T06:29:59Z")));
+        assert!(!window.is_open(at("2026-07-05T06:30:00Z")));
+        // And 06:30 UTC in winter
+        assert!(window.is_open(at("2026-01-04T06:45:00Z")));
+        // Not on Mondays
+        assert!(!window.is_open(at("2026-07-06T05:45:00Z")));
+    }
+
+    #[test]
+    fn test_weekday_names() {
+        assert_eq!(weekday_names("*"), "*");
+        assert_eq!(weekday_names("0"), "SUN");
+        assert_eq!(weekday_names("1-5"), "MON-FRI");
+        assert_eq!(weekday_names("5-7"), "FRI-SAT,SUN");
+        assert_eq!(weekday_names("sat,7"), "sat,SUN");
+        assert_eq!(weekday_names("*/2"), "*/2");
+
+        assert!(parse_schedule("0 22 * * 1-5").is_ok());
+        assert!(parse_schedule("0 0 22 * * 1-5").is_err());
+        assert!(parse_schedule("0 25 * * *").is_err());
+    }
+
+    #[test]
+    fn test_invalid_windows() {
+        let error = |settings: MaintenanceWindow| Window::new(&settings).unwrap_err();
+
+        assert!(error(window("Empty")).contains("either start and end"));
+
+        let mut settings = window("Backwards");
+        settings.start = Some("2026-11-01 22:00".to_string());
+        settings.end = Some("2026-11-01 21:00".to_string());
+        assert!(error(settings).contains("end must be after start"));
+
+        let mut settings = window("No duration");
+        settings.schedule = Some("0 22 * * *".to_string());
+        assert!(error(settings).contains("need a duration"));
+
+        let mut settings = window("Elsewhere");
+        settings.start = Some("2026-11-01 22:00".to_string());
+        settings.end = Some("2026-11-01 23:00".to_string());
+        settings.timezone = Some("Mars/Olympus_Mons".to_string());
+        assert!(error(settings).contains("unknown timezone"));
+    }
+
+    #[test]
+    fn test_mode_per_monitor() {
+        let mut everything = window("Everything");
+        everything.start = Some("2026-11-01 22:00".to_string());
+        everything.end = Some("2026-11-01 23:00".to_string());
+        everything.mode = MaintenanceMode::Mark;
+
+        let mut databases = everything.clone();
+        databases.name = "Databases".to_string();
+        databases.tags = vec!["database".to_string()];
+        databases.mode = MaintenanceMode::Skip;
+
+        let maintenance = Maintenance::new(&[everything.clone(), databases]);
+        let during = at("2026-11-01T22:30:00Z");
+        assert_eq!(
+            maintenance.mode(&monitor(&["database"]), during),
+            Some(MaintenanceMode::Skip)
+        );
+        assert_eq!(
+            maintenance.mode(&monitor(&["web"]), during),
+            Some(MaintenanceMode::Mark)
+        );
+        assert_eq!(
+            maintenance.mode(&monitor(&["web"]), at("2026-11-01T23:00:00Z")),
+            None
+        );
+
+        let web = monitor(&["web"]);
+        everything.monitors = vec![web.id];
+        let maintenance = Maintenance::new(&[everything]);
+        assert!(maintenance.mode(&web, during).is_some());
+        assert!(maintenance.mode(&monitor(&[]), during).is_none());
+    }
+}
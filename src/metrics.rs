@@ -1,9 +1,15 @@
-use metrics::{Counter, Gauge, Histogram, Unit};
+use metrics::{Counter, Gauge, Histogram, Label, Unit};
 use once_cell::sync::Lazy;
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use uuid::Uuid;
 
+use crate::checks::ping::PingStats;
+use crate::settings::MonitorType;
+use crate::timing::{Phase, PhaseTimings};
+use crate::tls::CertificateInfo;
+
 /// Shared metrics registry that can be accessed by both worker and metrics endpoint
 pub static METRICS_REGISTRY: Lazy<Arc<MetricsRegistry>> =
     Lazy::new(|| Arc::new(MetricsRegistry::new()));
@@ -14,27 +20,89 @@ pub struct MetricsRegistry {
     /// Buckets: 50ms, 100ms, 200ms, 500ms, 1s, 2s, 5s, 10s, +Inf
     response_time_histograms: Mutex<HashMap<Uuid, Histogram>>,
 
+    /// Per-phase duration histograms, keyed by monitor and phase
+    phase_histograms: Mutex<HashMap<String, Histogram>>,
+
     /// Total request counters per monitor and status
     request_counters: Mutex<HashMap<String, Counter>>,
 
     /// Failure counters with error type classification
     failure_counters: Mutex<HashMap<String, Counter>>,
 
+    /// Checks that failed at first and were retried, per monitor and
+    /// final outcome
+    retried_check_counters: Mutex<HashMap<String, Counter>>,
+
     /// Current monitor status (1.0 = up, 0.0 = down)
     monitor_status_gauges: Mutex<HashMap<Uuid, Gauge>>,
 
     /// Timestamp of last successful check per monitor
     last_success_timestamps: Mutex<HashMap<Uuid, Gauge>>,
 
+    /// How late the most recent check started relative to its due time
+    schedule_lag_gauges: Mutex<HashMap<Uuid, Gauge>>,
+
+    /// Whether a monitor is up but needs attention (1.0 = degraded)
+    degraded_gauges: Mutex<HashMap<Uuid, Gauge>>,
+
+    /// Whether a monitor keeps going up and down (1.0 = flapping)
+    flapping_gauges: Mutex<HashMap<Uuid, Gauge>>,
+
+    /// Whether a monitor is in a maintenance window (1.0 = in maintenance)
+    maintenance_gauges: Mutex<HashMap<Uuid, Gauge>>,
+
+    /// Resolution time histograms of DNS monitors
+    dns_resolution_histograms: Mutex<HashMap<Uuid, Histogram>>,
+
+    /// How long the latest timed run of each heartbeat monitor's job took
+    job_duration_gauges: Mutex<HashMap<Uuid, Gauge>>,
+
+    /// Round-trip times of ping monitors, keyed by monitor and statistic
+    ping_rtt_gauges: Mutex<HashMap<String, Gauge>>,
+
+    /// Percentage of echo requests lost by ping monitors
+    packet_loss_gauges: Mutex<HashMap<Uuid, Gauge>>,
+
+    /// Expiry timestamps of the certificates a monitor's host presents,
+    /// keyed by monitor and then certificate subject and issuer
+    #[allow(clippy::type_complexity)]
+    tls_cert_expiry_gauges: Mutex<HashMap<Uuid, HashMap<(String, String), Gauge>>>,
+
     /// Monitor metadata for labels
     monitor_metadata: Mutex<HashMap<Uuid, MonitorMetadata>>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct MonitorMetadata {
     pub name: String,
-    pub url: String,
-    pub interval: u64,
+    pub kind: MonitorType,
+    /// What the monitor checks, e.g. its URL
+    pub target: String,
+    pub interval: Duration,
+}
+
+impl MonitorMetadata {
+    /// Labels every series of the monitor carries, with the target under
+    /// the label for its type, e.g. `monitor_url`
+    fn labels(&self, id: Uuid) -> Vec<Label> {
+        vec![
+            Label::new("monitor_id", id.to_string()),
+            Label::new("monitor_name", self.name.clone()),
+            Label::new(self.kind.target_label(), self.target.clone()),
+            Label::new("interval_seconds", self.interval.as_secs().to_string()),
+        ]
+    }
+
+    /// The monitor's labels followed by `extra` ones
+    fn labels_with(&self, id: Uuid, extra: &[(&'static str, String)]) -> Vec<Label> {
+        let mut labels = self.labels(id);
+        labels.extend(
+            extra
+                .iter()
+                .map(|(key, value)| Label::new(*key, value.clone())),
+        );
+        labels
+    }
 }
 
 impl Default for MetricsRegistry {
@@ -47,32 +115,63 @@ impl MetricsRegistry {
     pub fn new() -> Self {
         Self {
             response_time_histograms: Mutex::new(HashMap::new()),
+            phase_histograms: Mutex::new(HashMap::new()),
             request_counters: Mutex::new(HashMap::new()),
             failure_counters: Mutex::new(HashMap::new()),
+            retried_check_counters: Mutex::new(HashMap::new()),
             monitor_status_gauges: Mutex::new(HashMap::new()),
             last_success_timestamps: Mutex::new(HashMap::new()),
+            schedule_lag_gauges: Mutex::new(HashMap::new()),
+            degraded_gauges: Mutex::new(HashMap::new()),
+            flapping_gauges: Mutex::new(HashMap::new()),
+            maintenance_gauges: Mutex::new(HashMap::new()),
+            dns_resolution_histograms: Mutex::new(HashMap::new()),
+            job_duration_gauges: Mutex::new(HashMap::new()),
+            ping_rtt_gauges: Mutex::new(HashMap::new()),
+            packet_loss_gauges: Mutex::new(HashMap::new()),
+            tls_cert_expiry_gauges: Mutex::new(HashMap::new()),
             monitor_metadata: Mutex::new(HashMap::new()),
         }
     }
 
-    /// Register a new monitor for metrics tracking
+    /// Register a monitor for metrics tracking.
+    ///
+    /// Registering a monitor again under the same labels keeps its series.
+    /// Once its name, target or interval changed, series under the old labels
+    /// stop being exported and its metrics start again under the new ones.
     pub fn register_monitor(&self, id: Uuid, metadata: MonitorMetadata) {
+        let previous = self.monitor_metadata.lock().unwrap().get(&id).cloned();
+        match previous {
+            Some(previous) if previous == metadata => return,
+            Some(_) => self.unregister_monitor(id),
+            None => {}
+        }
+
         let mut meta_map = self.monitor_metadata.lock().unwrap();
         meta_map.insert(id, metadata.clone());
         drop(meta_map);
 
         // Initialize response time histogram with appropriate buckets
         let mut histograms = self.response_time_histograms.lock().unwrap();
-        let histogram = metrics::histogram!(
-            "http_monitor_response_time_seconds",
-            "monitor_id" => id.to_string(),
-            "monitor_name" => metadata.name.clone(),
-            "monitor_url" => metadata.url.clone(),
-            "interval_minutes" => metadata.interval.to_string()
-        );
+        let histogram =
+            metrics::histogram!("http_monitor_response_time_seconds", metadata.labels(id));
         histograms.insert(id, histogram);
         drop(histograms);
 
+        // Initialize a phase duration histogram for each phase the monitor's
+        // checks time
+        let mut phase_histograms = self.phase_histograms.lock().unwrap();
+        for phase in metadata.kind.phases() {
+            phase_histograms.insert(
+                format!("{id}:{}", phase.as_str()),
+                metrics::histogram!(
+                    "http_monitor_phase_duration_seconds",
+                    metadata.labels_with(id, &[("phase", phase.as_str().to_string())])
+                ),
+            );
+        }
+        drop(phase_histograms);
+
         // Initialize request counters for success/failure
         let mut counters = self.request_counters.lock().unwrap();
         let success_key = format!("{id}:success");
@@ -82,11 +181,7 @@ impl MetricsRegistry {
             success_key.clone(),
             metrics::counter!(
                 "http_monitor_requests_total",
-                "monitor_id" => id.to_string(),
-                "monitor_name" => metadata.name.clone(),
-                "monitor_url" => metadata.url.clone(),
-                "interval_minutes" => metadata.interval.to_string(),
-                "status" => "success"
+                metadata.labels_with(id, &[("status", "success".to_string())])
             ),
         );
 
@@ -94,45 +189,325 @@ impl MetricsRegistry {
             failure_key.clone(),
             metrics::counter!(
                 "http_monitor_requests_total",
-                "monitor_id" => id.to_string(),
-                "monitor_name" => metadata.name.clone(),
-                "monitor_url" => metadata.url.clone(),
-                "interval_minutes" => metadata.interval.to_string(),
-                "status" => "failure"
+                metadata.labels_with(id, &[("status", "failure".to_string())])
             ),
         );
         drop(counters);
 
+        // Initialize retried check counters for each final outcome
+        let mut retried = self.retried_check_counters.lock().unwrap();
+        for outcome in ["success", "failure"] {
+            retried.insert(
+                format!("{id}:{outcome}"),
+                metrics::counter!(
+                    "http_monitor_retried_checks_total",
+                    metadata.labels_with(id, &[("outcome", outcome.to_string())])
+                ),
+            );
+        }
+        drop(retried);
+
         // Initialize status gauge
         let mut gauges = self.monitor_status_gauges.lock().unwrap();
-        gauges.insert(
-            id,
-            metrics::gauge!(
-                "http_monitor_up",
-                "monitor_id" => id.to_string(),
-                "monitor_name" => metadata.name.clone(),
-                "monitor_url" => metadata.url.clone(),
-                "interval_minutes" => metadata.interval.to_string()
-            ),
-        );
+        gauges.insert(id, metrics::gauge!("http_monitor_up", metadata.labels(id)));
         drop(gauges);
 
         // Initialize last success timestamp
         let mut timestamps = self.last_success_timestamps.lock().unwrap();
         timestamps.insert(
             id,
-            metrics::gauge!(
-                "http_monitor_last_success_timestamp",
-                "monitor_id" => id.to_string(),
-                "monitor_name" => metadata.name.clone(),
-                "monitor_url" => metadata.url.clone(),
-                "interval_minutes" => metadata.interval.to_string()
-            ),
+            metrics::gauge!("http_monitor_last_success_timestamp", metadata.labels(id)),
         );
+        drop(timestamps);
+
+        // Initialize scheduling lag gauge
+        let mut lags = self.schedule_lag_gauges.lock().unwrap();
+        lags.insert(
+            id,
+            metrics::gauge!("http_monitor_schedule_lag_seconds", metadata.labels(id)),
+        );
+        drop(lags);
+
+        // Initialize degraded gauge
+        let mut degraded = self.degraded_gauges.lock().unwrap();
+        degraded.insert(
+            id,
+            metrics::gauge!("http_monitor_degraded", metadata.labels(id)),
+        );
+        drop(degraded);
+
+        // Initialize flapping gauge
+        let mut flapping = self.flapping_gauges.lock().unwrap();
+        flapping.insert(
+            id,
+            metrics::gauge!("http_monitor_flapping", metadata.labels(id)),
+        );
+        drop(flapping);
+
+        // Initialize maintenance gauge
+        let mut maintenance = self.maintenance_gauges.lock().unwrap();
+        maintenance.insert(
+            id,
+            metrics::gauge!("http_monitor_in_maintenance", metadata.labels(id)),
+        );
+        drop(maintenance);
+
+        // Initialize the resolution time histogram of DNS monitors
+        if metadata.kind == MonitorType::Dns {
+            self.dns_resolution_histograms.lock().unwrap().insert(
+                id,
+                metrics::histogram!("http_monitor_dns_resolution_seconds", metadata.labels(id)),
+            );
+        }
+
+        // Initialize the job duration gauge of heartbeat monitors
+        if metadata.kind == MonitorType::Heartbeat {
+            self.job_duration_gauges.lock().unwrap().insert(
+                id,
+                metrics::gauge!(
+                    "http_monitor_heartbeat_job_duration_seconds",
+                    metadata.labels(id)
+                ),
+            );
+        }
+
+        // Initialize round-trip and packet loss gauges of ping monitors
+        if metadata.kind == MonitorType::Ping {
+            let mut round_trips = self.ping_rtt_gauges.lock().unwrap();
+            for stat in ["min", "avg", "max"] {
+                round_trips.insert(
+                    format!("{id}:{stat}"),
+                    metrics::gauge!(
+                        "http_monitor_ping_rtt_seconds",
+                        metadata.labels_with(id, &[("stat", stat.to_string())])
+                    ),
+                );
+            }
+            drop(round_trips);
+
+            self.packet_loss_gauges.lock().unwrap().insert(
+                id,
+                metrics::gauge!("http_monitor_ping_packet_loss_percent", metadata.labels(id)),
+            );
+        }
+    }
+
+    /// Relabel a registered monitor after its name, target, interval or type
+    /// changed. Series under the old labels stop being exported and the
+    /// monitor's metrics start again under the new ones. Unknown monitors are
+    /// ignored.
+    pub fn update_monitor(&self, id: Uuid, metadata: MonitorMetadata) {
+        if self.monitor_metadata.lock().unwrap().contains_key(&id) {
+            self.register_monitor(id, metadata);
+        }
+    }
+
+    /// Stop tracking a monitor.
+    ///
+    /// Dropping its handles stops the recorder from exporting its series.
+    pub fn unregister_monitor(&self, id: Uuid) {
+        if self.monitor_metadata.lock().unwrap().remove(&id).is_none() {
+            return;
+        }
+
+        self.response_time_histograms.lock().unwrap().remove(&id);
+        self.monitor_status_gauges.lock().unwrap().remove(&id);
+        self.last_success_timestamps.lock().unwrap().remove(&id);
+        self.schedule_lag_gauges.lock().unwrap().remove(&id);
+        self.tls_cert_expiry_gauges.lock().unwrap().remove(&id);
+        self.degraded_gauges.lock().unwrap().remove(&id);
+        self.flapping_gauges.lock().unwrap().remove(&id);
+        self.maintenance_gauges.lock().unwrap().remove(&id);
+        self.packet_loss_gauges.lock().unwrap().remove(&id);
+        self.dns_resolution_histograms.lock().unwrap().remove(&id);
+        self.job_duration_gauges.lock().unwrap().remove(&id);
+
+        // Keyed maps use "{id}:..." keys
+        let prefix = format!("{id}:");
+        self.phase_histograms
+            .lock()
+            .unwrap()
+            .retain(|key, _| !key.starts_with(&prefix));
+        self.request_counters
+            .lock()
+            .unwrap()
+            .retain(|key, _| !key.starts_with(&prefix));
+        self.failure_counters
+            .lock()
+            .unwrap()
+            .retain(|key, _| !key.starts_with(&prefix));
+        self.retried_check_counters
+            .lock()
+            .unwrap()
+            .retain(|key, _| !key.starts_with(&prefix));
+        self.ping_rtt_gauges
+            .lock()
+            .unwrap()
+            .retain(|key, _| !key.starts_with(&prefix));
+    }
+
+    /// Record how late a check started relative to when it was due
+    pub fn record_schedule_lag(&self, monitor_id: Uuid, lag: Duration) {
+        if let Ok(lags) = self.schedule_lag_gauges.lock() {
+            if let Some(gauge) = lags.get(&monitor_id) {
+                gauge.set(lag.as_secs_f64());
+            }
+        }
+    }
+
+    /// Record how long each phase of a check took
+    pub fn record_phase_timings(&self, monitor_id: Uuid, timings: &PhaseTimings) {
+        if let Ok(histograms) = self.phase_histograms.lock() {
+            for phase in Phase::ALL {
+                let Some(duration) = timings.get(phase) else {
+                    continue;
+                };
+
+                if let Some(histogram) = histograms.get(&format!("{monitor_id}:{}", phase.as_str()))
+                {
+                    histogram.record(duration.as_secs_f64());
+                }
+            }
+        }
+    }
+
+    /// Record how long a DNS check took to resolve its query. Only DNS
+    /// monitors have the histogram, so other monitors are ignored.
+    pub fn record_dns_resolution(&self, monitor_id: Uuid, resolution: Duration) {
+        if let Ok(histograms) = self.dns_resolution_histograms.lock() {
+            if let Some(histogram) = histograms.get(&monitor_id) {
+                histogram.record(resolution.as_secs_f64());
+            }
+        }
+    }
+
+    /// Record how long a heartbeat monitor's job ran, from its start ping to
+    /// its success or fail ping. Other monitors are ignored.
+    pub fn record_job_duration(&self, monitor_id: Uuid, duration: Duration) {
+        if let Ok(gauges) = self.job_duration_gauges.lock() {
+            if let Some(gauge) = gauges.get(&monitor_id) {
+                gauge.set(duration.as_secs_f64());
+            }
+        }
+    }
+
+    /// Record the round-trip times and packet loss of a ping check. Round
+    /// trips keep their values when no request was answered.
+    pub fn record_ping(&self, monitor_id: Uuid, stats: &PingStats) {
+        if let Ok(gauges) = self.ping_rtt_gauges.lock() {
+            for (stat, round_trip) in [("min", stats.min), ("avg", stats.avg), ("max", stats.max)] {
+                let Some(round_trip) = round_trip else {
+                    continue;
+                };
+
+                if let Some(gauge) = gauges.get(&format!("{monitor_id}:{stat}")) {
+                    gauge.set(round_trip.as_secs_f64());
+                }
+            }
+        }
+
+        if let Ok(gauges) = self.packet_loss_gauges.lock() {
+            if let Some(gauge) = gauges.get(&monitor_id) {
+                gauge.set(stats.packet_loss());
+            }
+        }
+    }
+
+    /// Record whether a monitor is degraded, e.g. by an expiring certificate
+    pub fn record_degraded(&self, monitor_id: Uuid, degraded: bool) {
+        if let Ok(gauges) = self.degraded_gauges.lock() {
+            if let Some(gauge) = gauges.get(&monitor_id) {
+                gauge.set(if degraded { 1.0 } else { 0.0 });
+            }
+        }
+    }
+
+    /// Count a check that was retried after failing, by whether it passed
+    /// in the end
+    pub fn record_retried_check(&self, monitor_id: Uuid, success: bool) {
+        if let Ok(counters) = self.retried_check_counters.lock() {
+            let outcome = if success { "success" } else { "failure" };
+            if let Some(counter) = counters.get(&format!("{monitor_id}:{outcome}")) {
+                counter.increment(1);
+            }
+        }
+    }
+
+    /// Set whether a monitor is reported up, e.g. once its thresholds are
+    /// met or from stored history after a restart
+    pub fn record_status(&self, monitor_id: Uuid, up: bool) {
+        if let Ok(gauges) = self.monitor_status_gauges.lock() {
+            if let Some(gauge) = gauges.get(&monitor_id) {
+                gauge.set(if up { 1.0 } else { 0.0 });
+            }
+        }
+    }
+
+    /// Record the expiry of each certificate in a monitor's TLS chain.
+    ///
+    /// When the chain changes, e.g. after a renewal, the series of the
+    /// previous chain are dropped rather than left at their last expiry.
+    pub fn record_tls_certificates(&self, monitor_id: Uuid, chain: &[CertificateInfo]) {
+        if let Ok(metadata) = self.monitor_metadata.lock() {
+            if let Some(meta) = metadata.get(&monitor_id) {
+                let mut expiry_gauges = self.tls_cert_expiry_gauges.lock().unwrap();
+                let gauges = expiry_gauges.entry(monitor_id).or_default();
+
+                let unchanged = gauges.len() == chain.len()
+                    && chain.iter().all(|cert| {
+                        gauges.contains_key(&(cert.subject.clone(), cert.issuer.clone()))
+                    });
+                if !unchanged {
+                    gauges.clear();
+                }
+
+                for cert in chain {
+                    let cert_key = (cert.subject.clone(), cert.issuer.clone());
+
+                    let gauge = gauges.entry(cert_key).or_insert_with(|| {
+                        metrics::gauge!(
+                            "http_monitor_tls_cert_expiry_timestamp",
+                            meta.labels_with(
+                                monitor_id,
+                                &[
+                                    ("subject", cert.subject.clone()),
+                                    ("issuer", cert.issuer.clone())
+                                ]
+                            )
+                        )
+                    });
+                    gauge.set(cert.not_after as f64);
+                }
+            }
+        }
     }
 
-    /// Record a successful HTTP check
+    /// Record whether a monitor is flapping
+    pub fn record_flapping(&self, monitor_id: Uuid, flapping: bool) {
+        if let Ok(gauges) = self.flapping_gauges.lock() {
+            if let Some(gauge) = gauges.get(&monitor_id) {
+                gauge.set(if flapping { 1.0 } else { 0.0 });
+            }
+        }
+    }
+
+    /// Record whether a monitor is in a maintenance window
+    pub fn record_in_maintenance(&self, monitor_id: Uuid, in_maintenance: bool) {
+        if let Ok(gauges) = self.maintenance_gauges.lock() {
+            if let Some(gauge) = gauges.get(&monitor_id) {
+                gauge.set(if in_maintenance { 1.0 } else { 0.0 });
+            }
+        }
+    }
+
+    /// Record a successful HTTP check and report the monitor up
     pub fn record_success(&self, monitor_id: Uuid, response_time_ms: u64) {
+        self.count_success(monitor_id, response_time_ms);
+        self.record_status(monitor_id, true);
+    }
+
+    /// Record a successful HTTP check, leaving the monitor's status alone
+    pub fn count_success(&self, monitor_id: Uuid, response_time_ms: u64) {
         // Record response time in histogram (convert ms to seconds)
         if let Ok(histograms) = self.response_time_histograms.lock() {
             if let Some(histogram) = histograms.get(&monitor_id) {
@@ -148,13 +523,6 @@ impl MetricsRegistry {
             }
         }
 
-        // Update status to up (1.0)
-        if let Ok(gauges) = self.monitor_status_gauges.lock() {
-            if let Some(gauge) = gauges.get(&monitor_id) {
-                gauge.set(1.0);
-            }
-        }
-
         // Update last success timestamp
         if let Ok(timestamps) = self.last_success_timestamps.lock() {
             if let Some(timestamp) = timestamps.get(&monitor_id) {
@@ -167,13 +535,25 @@ impl MetricsRegistry {
         }
     }
 
-    /// Record a failed HTTP check
+    /// Record a failed HTTP check and report the monitor down
     pub fn record_failure(
         &self,
         monitor_id: Uuid,
         response_time_ms: u64,
         error_type: &str,
         status_code: Option<u16>,
+    ) {
+        self.count_failure(monitor_id, response_time_ms, error_type, status_code);
+        self.record_status(monitor_id, false);
+    }
+
+    /// Record a failed HTTP check, leaving the monitor's status alone
+    pub fn count_failure(
+        &self,
+        monitor_id: Uuid,
+        response_time_ms: u64,
+        error_type: &str,
+        status_code: Option<u16>,
     ) {
         // Still record response time for failed requests (important for timeout analysis)
         if let Ok(histograms) = self.response_time_histograms.lock() {
@@ -200,24 +580,23 @@ impl MetricsRegistry {
                 let counter = failure_counters.entry(failure_key).or_insert_with(|| {
                     metrics::counter!(
                         "http_monitor_failures_total",
-                        "monitor_id" => monitor_id.to_string(),
-                        "monitor_name" => meta.name.clone(),
-                        "monitor_url" => meta.url.clone(),
-                        "interval_minutes" => meta.interval.to_string(),
-                        "error_type" => error_type.to_string(),
-                        "status_code" => status_code.map(|c| c.to_string()).unwrap_or_else(|| "none".to_string())
+                        meta.labels_with(
+                            monitor_id,
+                            &[
+                                ("error_type", error_type.to_string()),
+                                (
+                                    "status_code",
+                                    status_code
+                                        .map(|c| c.to_string())
+                                        .unwrap_or_else(|| "none".to_string())
+                                )
+                            ]
+                        )
                     )
                 });
                 counter.increment(1);
             }
         }
-
-        // Update status to down (0.0)
-        if let Ok(gauges) = self.monitor_status_gauges.lock() {
-            if let Some(gauge) = gauges.get(&monitor_id) {
-                gauge.set(0.0);
-            }
-        }
     }
 }
 
@@ -229,6 +608,12 @@ pub fn init_metrics() {
         "HTTP response time in seconds"
     );
 
+    metrics::describe_histogram!(
+        "http_monitor_phase_duration_seconds",
+        Unit::Seconds,
+        "Time spent in each phase of a check: dns, connect, ttfb and download"
+    );
+
     metrics::describe_counter!(
         "http_monitor_requests_total",
         Unit::Count,
@@ -241,6 +626,12 @@ pub fn init_metrics() {
         "Total HTTP failures by monitor, error type, and status code"
     );
 
+    metrics::describe_counter!(
+        "http_monitor_retried_checks_total",
+        Unit::Count,
+        "Checks that failed at first and were retried, by whether they passed in the end"
+    );
+
     metrics::describe_gauge!(
         "http_monitor_up",
         Unit::Count,
@@ -252,11 +643,66 @@ pub fn init_metrics() {
         Unit::Seconds,
         "Unix timestamp of last successful check"
     );
+
+    metrics::describe_gauge!(
+        "http_monitor_schedule_lag_seconds",
+        Unit::Seconds,
+        "How late the most recent check started relative to its scheduled time"
+    );
+
+    metrics::describe_gauge!(
+        "http_monitor_degraded",
+        Unit::Count,
+        "Whether the monitor is up but degraded (1), e.g. by a certificate close to expiry"
+    );
+
+    metrics::describe_gauge!(
+        "http_monitor_flapping",
+        Unit::Count,
+        "Whether the monitor is flapping (1), going up and down too often for its status to be reliable"
+    );
+
+    metrics::describe_gauge!(
+        "http_monitor_in_maintenance",
+        Unit::Count,
+        "Whether the monitor is in a maintenance window (1), during which failed checks are not counted"
+    );
+
+    metrics::describe_histogram!(
+        "http_monitor_dns_resolution_seconds",
+        Unit::Seconds,
+        "Time DNS monitors took to resolve their query"
+    );
+
+    metrics::describe_gauge!(
+        "http_monitor_heartbeat_job_duration_seconds",
+        Unit::Seconds,
+        "How long the latest run of a heartbeat monitor's job took, from its start ping to its finish"
+    );
+
+    metrics::describe_gauge!(
+        "http_monitor_ping_rtt_seconds",
+        Unit::Seconds,
+        "Minimum, average and maximum round-trip time of the latest answered ping check"
+    );
+
+    metrics::describe_gauge!(
+        "http_monitor_ping_packet_loss_percent",
+        Unit::Percent,
+        "Percentage of echo requests left unanswered in the latest ping check"
+    );
+
+    metrics::describe_gauge!(
+        "http_monitor_tls_cert_expiry_timestamp",
+        Unit::Seconds,
+        "Unix timestamp at which each certificate presented by the monitored host expires"
+    );
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::recorder::MetricsRecorder;
 
     #[test]
     fn test_metrics_registry_creation() {
@@ -265,8 +711,9 @@ mod tests {
 
         let metadata = MonitorMetadata {
             name: "Test Monitor".to_string(),
-            url: "https://example.com".to_string(),
-            interval: 60,
+            kind: MonitorType::Http,
+            target: "https://example.com".to_string(),
+            interval: Duration::from_secs(60),
         };
 
         registry.register_monitor(monitor_id, metadata);
@@ -281,8 +728,9 @@ mod tests {
 
         let metadata = MonitorMetadata {
             name: "Success Test".to_string(),
-            url: "https://success.com".to_string(),
-            interval: 30,
+            kind: MonitorType::Http,
+            target: "https://success.com".to_string(),
+            interval: Duration::from_secs(30),
         };
 
         registry.register_monitor(monitor_id, metadata);
@@ -298,8 +746,9 @@ mod tests {
 
         let metadata = MonitorMetadata {
             name: "Failure Test".to_string(),
-            url: "https://failure.com".to_string(),
-            interval: 60,
+            kind: MonitorType::Http,
+            target: "https://failure.com".to_string(),
+            interval: Duration::from_secs(60),
         };
 
         registry.register_monitor(monitor_id, metadata);
@@ -308,4 +757,314 @@ mod tests {
 
         // Test passes if no panics occur
     }
+
+    #[test]
+    fn test_schedule_lag_recording() {
+        let registry = MetricsRegistry::new();
+        let monitor_id = Uuid::new_v4();
+
+        let metadata = MonitorMetadata {
+            name: "Lag Test".to_string(),
+            kind: MonitorType::Http,
+            target: "https://lag.com".to_string(),
+            interval: Duration::from_secs(1),
+        };
+
+        registry.register_monitor(monitor_id, metadata);
+        registry.record_schedule_lag(monitor_id, Duration::from_millis(250));
+
+        // Unknown monitors are ignored
+        registry.record_schedule_lag(Uuid::new_v4(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_tls_certificate_recording() {
+        let registry = MetricsRegistry::new();
+        let monitor_id = Uuid::new_v4();
+
+        let metadata = MonitorMetadata {
+            name: "TLS Test".to_string(),
+            kind: MonitorType::Http,
+            target: "https://tls.com".to_string(),
+            interval: Duration::from_secs(60),
+        };
+
+        registry.register_monitor(monitor_id, metadata);
+
+        let chain = vec![
+            CertificateInfo {
+                subject: "CN=tls.com".to_string(),
+                issuer: "CN=Example CA".to_string(),
+                not_after: 1_900_000_000,
+            },
+            CertificateInfo {
+                subject: "CN=Example CA".to_string(),
+                issuer: "CN=Example Root".to_string(),
+                not_after: 2_000_000_000,
+            },
+        ];
+        let recorder = MetricsRecorder::default();
+        let handle = recorder.handle();
+        metrics::with_local_recorder(&recorder, || {
+            registry.record_tls_certificates(monitor_id, &chain);
+            registry.record_tls_certificates(monitor_id, &chain);
+        });
+        registry.record_degraded(monitor_id, true);
+
+        let gauge_count = || registry.tls_cert_expiry_gauges.lock().unwrap()[&monitor_id].len();
+        assert_eq!(gauge_count(), 2);
+        assert!(handle.render().contains("subject=\"CN=tls.com\""));
+
+        // A renewed leaf from another issuer replaces the previous chain
+        let renewed = vec![CertificateInfo {
+            subject: "CN=tls.com".to_string(),
+            issuer: "CN=Other CA".to_string(),
+            not_after: 2_100_000_000,
+        }];
+        metrics::with_local_recorder(&recorder, || {
+            registry.record_tls_certificates(monitor_id, &renewed);
+        });
+        assert_eq!(gauge_count(), 1);
+        let output = handle.render();
+        assert!(!output.contains("CN=Example CA"));
+        assert!(output.contains("issuer=\"CN=Other CA\""));
+
+        // Unknown monitors are ignored
+        registry.record_tls_certificates(Uuid::new_v4(), &chain);
+        assert_eq!(registry.tls_cert_expiry_gauges.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_phase_timing_recording() {
+        let registry = MetricsRegistry::new();
+        let monitor_id = Uuid::new_v4();
+
+        let metadata = MonitorMetadata {
+            name: "Phase Test".to_string(),
+            kind: MonitorType::Http,
+            target: "https://phase.com".to_string(),
+            interval: Duration::from_secs(60),
+        };
+
+        registry.register_monitor(monitor_id, metadata.clone());
+        {
+            // HTTP checks don't time connecting, so there's no connect series
+            let histograms = registry.phase_histograms.lock().unwrap();
+            assert_eq!(histograms.len(), 3);
+            assert!(!histograms.contains_key(&format!("{monitor_id}:connect")));
+        }
+
+        let tcp_id = Uuid::new_v4();
+        registry.register_monitor(
+            tcp_id,
+            MonitorMetadata {
+                kind: MonitorType::Tcp,
+                target: "phase.com:443".to_string(),
+                ..metadata
+            },
+        );
+        assert!(registry
+            .phase_histograms
+            .lock()
+            .unwrap()
+            .contains_key(&format!("{tcp_id}:connect")));
+
+        let timings = PhaseTimings {
+            dns: Some(Duration::from_millis(3)),
+            connect: Some(Duration::from_millis(12)),
+            ttfb: Some(Duration::from_millis(80)),
+            download: Some(Duration::from_millis(4)),
+        };
+        registry.record_phase_timings(monitor_id, &timings);
+
+        // Unknown monitors are ignored
+        registry.record_phase_timings(Uuid::new_v4(), &timings);
+    }
+
+    #[test]
+    fn test_ping_recording() {
+        let registry = MetricsRegistry::new();
+        let monitor_id = Uuid::new_v4();
+        let http_id = Uuid::new_v4();
+
+        let metadata = MonitorMetadata {
+            name: "Ping Test".to_string(),
+            kind: MonitorType::Ping,
+            target: "ping.com".to_string(),
+            interval: Duration::from_secs(60),
+        };
+
+        registry.register_monitor(monitor_id, metadata.clone());
+        registry.register_monitor(
+            http_id,
+            MonitorMetadata {
+                kind: MonitorType::Http,
+                target: "https://ping.com".to_string(),
+                ..metadata
+            },
+        );
+
+        // Only ping monitors get the ping series
+        assert_eq!(registry.ping_rtt_gauges.lock().unwrap().len(), 3);
+        assert_eq!(registry.packet_loss_gauges.lock().unwrap().len(), 1);
+
+        let stats = PingStats {
+            sent: 4,
+            received: 3,
+            min: Some(Duration::from_millis(10)),
+            avg: Some(Duration::from_millis(20)),
+            max: Some(Duration::from_millis(30)),
+        };
+        registry.record_ping(monitor_id, &stats);
+
+        // Unknown monitors are ignored
+        registry.record_ping(Uuid::new_v4(), &stats);
+
+        registry.unregister_monitor(monitor_id);
+        assert!(registry.ping_rtt_gauges.lock().unwrap().is_empty());
+        assert!(registry.packet_loss_gauges.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_dns_resolution_recording() {
+        let registry = MetricsRegistry::new();
+        let monitor_id = Uuid::new_v4();
+
+        let metadata = MonitorMetadata {
+            name: "DNS Test".to_string(),
+            kind: MonitorType::Dns,
+            target: "dns.com".to_string(),
+            interval: Duration::from_secs(60),
+        };
+
+        registry.register_monitor(monitor_id, metadata);
+        assert_eq!(registry.dns_resolution_histograms.lock().unwrap().len(), 1);
+        registry.record_dns_resolution(monitor_id, Duration::from_millis(12));
+
+        // Unknown monitors are ignored
+        registry.record_dns_resolution(Uuid::new_v4(), Duration::from_millis(12));
+
+        registry.unregister_monitor(monitor_id);
+        assert!(registry
+            .dns_resolution_histograms
+            .lock()
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_job_duration_recording() {
+        let registry = MetricsRegistry::new();
+        let monitor_id = Uuid::new_v4();
+
+        let metadata = MonitorMetadata {
+            name: "Nightly Backup".to_string(),
+            kind: MonitorType::Heartbeat,
+            target: format!("/heartbeat/{monitor_id}"),
+            interval: Duration::from_secs(86_400),
+        };
+
+        registry.register_monitor(monitor_id, metadata);
+        assert_eq!(registry.job_duration_gauges.lock().unwrap().len(), 1);
+        registry.record_job_duration(monitor_id, Duration::from_secs(90));
+
+        // Unknown monitors are ignored
+        registry.record_job_duration(Uuid::new_v4(), Duration::from_secs(90));
+
+        registry.unregister_monitor(monitor_id);
+        assert!(registry.job_duration_gauges.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_unregister_monitor() {
+        let recorder = MetricsRecorder::default();
+        let handle = recorder.handle();
+        let registry = MetricsRegistry::new();
+        let monitor_id = Uuid::new_v4();
+        let other_id = Uuid::new_v4();
+
+        let metadata = MonitorMetadata {
+            name: "Removed \"Test\"".to_string(),
+            kind: MonitorType::Http,
+            target: "https://removed.com".to_string(),
+            interval: Duration::from_secs(60),
+        };
+
+        metrics::with_local_recorder(&recorder, || {
+            registry.register_monitor(monitor_id, metadata.clone());
+            registry.register_monitor(
+                other_id,
+                MonitorMetadata {
+                    name: "Kept Test".to_string(),
+                    ..metadata.clone()
+                },
+            );
+            registry.record_failure(monitor_id, 200, "http_error", Some(500));
+        });
+        assert!(handle.render().contains(&monitor_id.to_string()));
+
+        registry.unregister_monitor(monitor_id);
+
+        assert!(!registry
+            .monitor_metadata
+            .lock()
+            .unwrap()
+            .contains_key(&monitor_id));
+        assert!(registry.failure_counters.lock().unwrap().is_empty());
+        assert_eq!(registry.phase_histograms.lock().unwrap().len(), 3);
+        assert_eq!(registry.request_counters.lock().unwrap().len(), 2);
+
+        let output = handle.render();
+        assert!(!output.contains(&monitor_id.to_string()));
+        assert!(output.contains(&other_id.to_string()));
+
+        // Registering it again exports it again
+        metrics::with_local_recorder(&recorder, || {
+            registry.register_monitor(monitor_id, metadata);
+        });
+        assert!(handle.render().contains(&monitor_id.to_string()));
+
+        // Unknown monitors are ignored
+        registry.unregister_monitor(Uuid::new_v4());
+    }
+
+    #[test]
+    fn test_update_monitor() {
+        let registry = MetricsRegistry::new();
+        let monitor_id = Uuid::new_v4();
+
+        let metadata = MonitorMetadata {
+            name: "Old Name".to_string(),
+            kind: MonitorType::Http,
+            target: "https://update.com".to_string(),
+            interval: Duration::from_secs(60),
+        };
+        let renamed = MonitorMetadata {
+            name: "New Name".to_string(),
+            ..metadata.clone()
+        };
+
+        registry.register_monitor(monitor_id, metadata.clone());
+        registry.record_failure(monitor_id, 200, "http_error", Some(500));
+
+        // Unchanged labels keep the existing series
+        registry.update_monitor(monitor_id, metadata.clone());
+        assert_eq!(registry.failure_counters.lock().unwrap().len(), 1);
+
+        registry.update_monitor(monitor_id, renamed.clone());
+        assert_eq!(
+            registry.monitor_metadata.lock().unwrap().get(&monitor_id),
+            Some(&renamed)
+        );
+        assert!(registry.failure_counters.lock().unwrap().is_empty());
+
+        // Unknown monitors are ignored
+        let unknown = Uuid::new_v4();
+        registry.update_monitor(unknown, renamed);
+        assert!(!registry
+            .monitor_metadata
+            .lock()
+            .unwrap()
+            .contains_key(&unknown));
+    }
 }
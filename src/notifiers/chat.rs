@@ -0,0 +1,303 @@
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::{json, Value};
+
+use super::{check_status, request_failed, truncate, Notifier, NotifyError};
+use crate::alerting::{Alert, AlertEvent};
+use crate::secret::Secret;
+use crate::settings::{ChatConfig, DEFAULT_TIMEOUT};
+
+/// Chat services with incoming webhooks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChatService {
+    Slack,
+    Teams,
+    Discord,
+}
+
+impl ChatService {
+    fn name(self) -> &'static str {
+        match self {
+            ChatService::Slack => "slack",
+            ChatService::Teams => "teams",
+            ChatService::Discord => "discord",
+        }
+    }
+}
+
+/// Posts each alert to a chat channel through its incoming webhook, laid
+/// out the way the service expects
+pub struct ChatNotifier {
+    service: ChatService,
+    client: Client,
+    url: Secret,
+}
+
+/// Facts shown on their own line rather than side by side
+const LONG_FACTS: [&str; 3] = ["URL", "Error", "Last error"];
+
+/// Longest title and field value Discord accepts
+const DISCORD_TITLE_LIMIT: usize = 256;
+const DISCORD_FIELD_LIMIT: usize = 1024;
+
+impl ChatNotifier {
+    pub fn new(service: ChatService, config: &ChatConfig) -> Result<Self, String> {
+        let client = Client::builder()
+            .timeout(config.timeout.unwrap_or(DEFAULT_TIMEOUT))
+            .build()
+            .map_err(|e| format!("failed to create {} client: {e}", service.name()))?;
+
+        Ok(Self {
+            service,
+            client,
+            url: config.url.clone(),
+        })
+    }
+
+    fn payload(&self, alert: &Alert) -> Value {
+        let down = alert.event == AlertEvent::Down;
+        let facts = alert.facts();
+
+        match self.service {
+            ChatService::Slack => {
+                let fields: Vec<Value> = facts
+                    .iter()
+                    .map(|(label, value)| {
+                        json!({
+                            "title": label,
+                            "value": escape_slack(value),
+                            "short": !LONG_FACTS.contains(label),
+                        })
+                    })
+                    .collect();
+                json!({
+                    "text": escape_slack(&alert.summary()),
+                    "attachments": [{
+                        "color": if down { "#cf222e" } else { "#2da44e" },
+                        "fields": fields,
+                        "ts": alert.timestamp.timestamp(),
+                    }],
+                })
+            }
+            ChatService::Teams => {
+                let facts: Vec<Value> = facts
+                    .iter()
+                    .map(|(label, value)| json!({ "title": label, "value": value }))
+                    .collect();
+                json!({
+                    "type": "message",
+                    "attachments": [{
+                        "contentType": "application/vnd.microsoft.card.adaptive",
+                        "content": {
+                            "$schema": "http://adaptivecards.io/schemas/adaptive-card.json",
+                            "type": "AdaptiveCard",
+                            "version": "1.4",
+                            "body": [
+                                {
+                                    "type": "TextBlock",
+                                    "text": alert.summary(),
+                                    "weight": "Bolder",
+                                    "size": "Medium",
+                                    "color": if down { "Attention" } else { "Good" },
+                                    "wrap": true,
+                                },
+                                { "type": "FactSet", "facts": facts },
+                            ],
+                        },
+                    }],
+                })
+            }
+            ChatService::Discord => {
+                let fields: Vec<Value> = facts
+                    .iter()
+                    .map(|(label, value)| {
+                        json!({
+                            "name": label,
+                            "value": truncate(value, DISCORD_FIELD_LIMIT),
+                            "inline": !LONG_FACTS.contains(label),
+                        })
+                    })
+                    .collect();
+                json!({
+                    "embeds": [{
+                        "title": truncate(&alert.summary(), DISCORD_TITLE_LIMIT),
+                        "color": if down { 0xcf222e } else { 0x2da44e },
+                        "fields": fields,
+                        "timestamp": alert.timestamp.to_rfc3339(),
+                    }],
+                })
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for ChatNotifier {
+    fn name(&self) -> String {
+        self.service.name().to_string()
+    }
+
+    async fn notify(&self, alert: &Alert) -> Result<(), NotifyError> {
+        let response = self
+            .client
+            .post(self.url.resolve()?)
+            .json(&self.payload(alert))
+            .send()
+            .await
+            .map_err(request_failed)?;
+
+        check_status(&format!("{} webhook", self.service.name()), &response)
+    }
+}
+
+/// Escape the characters Slack treats as markup
+fn escape_slack(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Utc};
+    use mockito::Matcher;
+    use uuid::Uuid;
+
+    fn alert(event: AlertEvent) -> Alert {
+        let now = Utc::now();
+        Alert {
+            event,
+            monitor_id: Uuid::new_v4(),
+            monitor_name: "Chat <Test>".to_string(),
+            url: "https://chat.example.com".to_string(),
+            group: Some("Public".to_string()),
+            down_since: now - Duration::minutes(5),
+            timestamp: now,
+            failures: 3,
+            status_code: Some(502),
+            response_time_ms: 840,
+            error_type: Some("http_error".to_string()),
+            error: Some("HTTP 502".to_string()),
+        }
+    }
+
+    async fn notify(service: ChatService, alert: &Alert, body: Value) {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/hook")
+            .match_header("content-type", "application/json")
+            .match_body(Matcher::PartialJson(body))
+            .with_status(200)
+            .create_async()
+            .await;
+
+        let notifier = ChatNotifier::new(
+            service,
+            &ChatConfig {
+                url: Secret::Plain(format!("{}/hook", server.url())),
+                timeout: None,
+            },
+        )
+        .unwrap();
+        notifier.notify(alert).await.unwrap();
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_slack_payload() {
+        notify(
+            ChatService::Slack,
+            &alert(AlertEvent::Down),
+            json!({
+                "text": "[DOWN] Chat &lt;Test&gt; (https://chat.example.com)",
+                "attachments": [{ "color": "#cf222e" }],
+            }),
+        )
+        .await;
+
+        let payload = ChatNotifier::new(
+            ChatService::Slack,
+            &ChatConfig {
+                url: Secret::Plain("https://hooks.slack.com/services/x".to_string()),
+                timeout: None,
+            },
+        )
+        .unwrap()
+        .payload(&alert(AlertEvent::Down));
+        let fields = payload["attachments"][0]["fields"].as_array().unwrap();
+        let value = |title: &str| {
+            fields
+                .iter()
+                .find(|field| field["title"] == title)
+                .map(|field| field["value"].clone())
+        };
+        assert_eq!(value("Error type"), Some(json!("http_error")));
+        assert_eq!(value("Status code"), Some(json!("502")));
+        assert_eq!(value("Response time"), Some(json!("840ms")));
+        assert_eq!(value("Down for"), Some(json!("5m")));
+    }
+
+    #[tokio::test]
+    async fn test_teams_payload() {
+        notify(
+            ChatService::Teams,
+            &alert(AlertEvent::Recovered),
+            json!({
+                "type": "message",
+                "attachments": [{
+                    "contentType": "application/vnd.microsoft.card.adaptive",
+                    "content": { "type": "AdaptiveCard" },
+                }],
+            }),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_discord_payload() {
+        notify(
+            ChatService::Discord,
+            &alert(AlertEvent::Down),
+            json!({
+                "embeds": [{
+                    "title": "[DOWN] Chat <Test> (https://chat.example.com)",
+                    "color": 0xcf222e,
+                }],
+            }),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_error_status_hides_url() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("POST", "/secret-token")
+            .with_status(404)
+            .create_async()
+            .await;
+
+        let notifier = ChatNotifier::new(
+            ChatService::Discord,
+            &ChatConfig {
+                url: Secret::Plain(format!("{}/secret-token", server.url())),
+                timeout: None,
+            },
+        )
+        .unwrap();
+        let error = notifier
+            .notify(&alert(AlertEvent::Down))
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("404"));
+        assert!(!error.contains("secret-token"));
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("a longer text", 5), "a lo…");
+    }
+}
@@ -0,0 +1,132 @@
+use async_trait::async_trait;
+use reqwest::{Response, StatusCode};
+use serde_json::Value;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::alerting::Alert;
+use crate::settings::NotifierConfig;
+
+mod chat;
+mod opsgenie;
+mod pagerduty;
+mod smtp;
+mod webhook;
+
+pub use chat::{ChatNotifier, ChatService};
+pub use opsgenie::OpsgenieNotifier;
+pub use pagerduty::PagerDutyNotifier;
+pub use smtp::SmtpNotifier;
+pub use webhook::WebhookNotifier;
+
+/// Somewhere alerts can be sent
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// Short description for logs, e.g. "webhook (hooks.example.com)"
+    fn name(&self) -> String;
+
+    /// Deliver an alert
+    async fn notify(&self, alert: &Alert) -> Result<(), NotifyError>;
+}
+
+/// Why an alert wasn't delivered
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotifyError {
+    message: String,
+    retryable: bool,
+}
+
+impl NotifyError {
+    /// A failure that may pass, e.g. a timeout or an overloaded service
+    pub fn transient(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            retryable: true,
+        }
+    }
+
+    /// A failure that would repeat on every attempt, e.g. a rejected key
+    pub fn permanent(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            retryable: false,
+        }
+    }
+
+    /// Whether sending the alert again might work
+    pub fn is_retryable(&self) -> bool {
+        self.retryable
+    }
+}
+
+impl fmt::Display for NotifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Settings problems, like an unreadable secret, won't fix themselves
+impl From<String> for NotifyError {
+    fn from(message: String) -> Self {
+        Self::permanent(message)
+    }
+}
+
+/// A request that got no response. The URL is left out as it may carry a
+/// token.
+fn request_failed(error: reqwest::Error) -> NotifyError {
+    NotifyError::transient(error.without_url().to_string())
+}
+
+/// Check the status of a response from `service`; rate limits and server
+/// errors are worth retrying, other errors are not
+fn check_status(service: &str, response: &Response) -> Result<(), NotifyError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(());
+    }
+
+    let message = format!("{service} returned HTTP {status}");
+    if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+        Err(NotifyError::transient(message))
+    } else {
+        Err(NotifyError::permanent(message))
+    }
+}
+
+/// Shorten `text` to at most `limit` characters for services that cap
+/// field lengths
+fn truncate(text: &str, limit: usize) -> String {
+    if text.chars().count() <= limit {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(limit - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// The alert's facts as a JSON object
+fn facts_object(alert: &Alert) -> Value {
+    Value::Object(
+        alert
+            .facts()
+            .into_iter()
+            .map(|(label, value)| (label.to_string(), Value::String(value)))
+            .collect(),
+    )
+}
+
+/// Create the notifier described by `config`
+pub fn build(config: &NotifierConfig) -> Result<Arc<dyn Notifier>, String> {
+    Ok(match config {
+        NotifierConfig::Webhook(config) => Arc::new(WebhookNotifier::new(config)?),
+        NotifierConfig::Smtp(config) => Arc::new(SmtpNotifier::new(config)),
+        NotifierConfig::Slack(config) => Arc::new(ChatNotifier::new(ChatService::Slack, config)?),
+        NotifierConfig::Teams(config) => Arc::new(ChatNotifier::new(ChatService::Teams, config)?),
+        NotifierConfig::Discord(config) => {
+            Arc::new(ChatNotifier::new(ChatService::Discord, config)?)
+        }
+        NotifierConfig::PagerDuty(config) => Arc::new(PagerDutyNotifier::new(config)?),
+        NotifierConfig::Opsgenie(config) => Arc::new(OpsgenieNotifier::new(config)?),
+    })
+}
@@ -0,0 +1,177 @@
+use async_trait::async_trait;
+use reqwest::{Client, Url};
+use serde_json::{json, Value};
+
+use super::{check_status, facts_object, request_failed, truncate, Notifier, NotifyError};
+use crate::alerting::{Alert, AlertEvent};
+use crate::secret::Secret;
+use crate::settings::{OpsgenieConfig, DEFAULT_TIMEOUT};
+
+const DEFAULT_BASE_URL: &str = "https://api.opsgenie.com";
+const DEFAULT_PRIORITY: &str = "P1";
+
+/// Longest message and description Opsgenie accepts
+const MESSAGE_LIMIT: usize = 130;
+const DESCRIPTION_LIMIT: usize = 15_000;
+
+/// Opens an Opsgenie alert when a monitor goes down and closes it when the
+/// monitor recovers. The monitor's id is the alert's alias, which Opsgenie
+/// deduplicates on.
+pub struct OpsgenieNotifier {
+    client: Client,
+    base_url: Url,
+    api_key: Secret,
+    priority: String,
+}
+
+impl OpsgenieNotifier {
+    pub fn new(config: &OpsgenieConfig) -> Result<Self, String> {
+        let base_url = config.base_url.as_deref().unwrap_or(DEFAULT_BASE_URL);
+        let base_url = Url::parse(base_url.trim_end_matches('/'))
+            .map_err(|e| format!("invalid opsgenie base_url: {e}"))?;
+        let client = Client::builder()
+            .timeout(config.timeout.unwrap_or(DEFAULT_TIMEOUT))
+            .build()
+            .map_err(|e| format!("failed to create opsgenie client: {e}"))?;
+
+        Ok(Self {
+            client,
+            base_url,
+            api_key: config.api_key.clone(),
+            priority: config
+                .priority
+                .clone()
+                .unwrap_or_else(|| DEFAULT_PRIORITY.to_string()),
+        })
+    }
+
+    /// Where the request for `alert` goes and what it carries
+    fn request(&self, alert: &Alert) -> Result<(Url, Value), NotifyError> {
+        let alias = alert.monitor_id.to_string();
+        let base = self.base_url.as_str().trim_end_matches('/');
+
+        let (url, body) = match alert.event {
+            AlertEvent::Down => (
+                format!("{base}/v2/alerts"),
+                json!({
+                    "message": truncate(&alert.summary(), MESSAGE_LIMIT),
+                    "alias": alias,
+                    "description": truncate(&alert.details(), DESCRIPTION_LIMIT),
+                    "entity": alert.monitor_name,
+                    "source": "sammy_monitor",
+                    "priority": self.priority,
+                    "tags": alert.group.iter().collect::<Vec<_>>(),
+                    "details": facts_object(alert),
+                }),
+            ),
+            AlertEvent::Recovered => (
+                format!("{base}/v2/alerts/{alias}/close?identifierType=alias"),
+                json!({
+                    "source": "sammy_monitor",
+                    "note": truncate(&alert.summary(), MESSAGE_LIMIT),
+                }),
+            ),
+        };
+
+        let url = Url::parse(&url).map_err(|e| format!("invalid opsgenie url: {e}"))?;
+        Ok((url, body))
+    }
+}
+
+#[async_trait]
+impl Notifier for OpsgenieNotifier {
+    fn name(&self) -> String {
+        format!(
+            "opsgenie ({})",
+            self.base_url.host_str().unwrap_or_default()
+        )
+    }
+
+    async fn notify(&self, alert: &Alert) -> Result<(), NotifyError> {
+        let (url, body) = self.request(alert)?;
+        let response = self
+            .client
+            .post(url)
+            .header(
+                "Authorization",
+                format!("GenieKey {}", self.api_key.resolve()?),
+            )
+            .json(&body)
+            .send()
+            .await
+            .map_err(request_failed)?;
+
+        check_status("opsgenie", &response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use mockito::Matcher;
+    use uuid::Uuid;
+
+    fn alert(event: AlertEvent) -> Alert {
+        Alert {
+            event,
+            monitor_id: Uuid::parse_str("550e8400-e29b-41d4-a716-446655440000").unwrap(),
+            monitor_name: "Opsgenie Test".to_string(),
+            url: "https://opsgenie.example.com".to_string(),
+            group: None,
+            down_since: Utc::now(),
+            timestamp: Utc::now(),
+            failures: 1,
+            status_code: None,
+            response_time_ms: 30_000,
+            error_type: Some("connect_timeout".to_string()),
+            error: Some("Timed out connecting".to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_open_and_close() {
+        let mut server = mockito::Server::new_async().await;
+        let open = server
+            .mock("POST", "/v2/alerts")
+            .match_header("authorization", "GenieKey api-key")
+            .match_body(Matcher::PartialJson(json!({
+                "message": "[DOWN] Opsgenie Test (https://opsgenie.example.com)",
+                "alias": "550e8400-e29b-41d4-a716-446655440000",
+                "priority": "P2",
+                "details": { "Error type": "connect_timeout" },
+            })))
+            .with_status(202)
+            .create_async()
+            .await;
+        let close = server
+            .mock(
+                "POST",
+                "/v2/alerts/550e8400-e29b-41d4-a716-446655440000/close",
+            )
+            .match_query(Matcher::UrlEncoded(
+                "identifierType".to_string(),
+                "alias".to_string(),
+            ))
+            .match_header("authorization", "GenieKey api-key")
+            .with_status(202)
+            .create_async()
+            .await;
+
+        let notifier = OpsgenieNotifier::new(&OpsgenieConfig {
+            api_key: Secret::Plain("api-key".to_string()),
+            base_url: Some(format!("{}/", server.url())),
+            priority: Some("P2".to_string()),
+            timeout: None,
+        })
+        .unwrap();
+        notifier.notify(&alert(AlertEvent::Down)).await.unwrap();
+        notifier
+            .notify(&alert(AlertEvent::Recovered))
+            .await
+            .unwrap();
+        open.assert_async().await;
+        close.assert_async().await;
+        assert_eq!(notifier.name(), "opsgenie (127.0.0.1)");
+    }
+}
@@ -0,0 +1,195 @@
+use async_trait::async_trait;
+use reqwest::{Client, Url};
+use serde_json::{json, Value};
+
+use super::{check_status, facts_object, request_failed, truncate, Notifier, NotifyError};
+use crate::alerting::{Alert, AlertEvent};
+use crate::secret::Secret;
+use crate::settings::{PagerDutyConfig, DEFAULT_TIMEOUT};
+
+const DEFAULT_BASE_URL: &str = "https://events.pagerduty.com";
+const DEFAULT_SEVERITY: &str = "critical";
+
+/// Longest summary PagerDuty accepts
+const SUMMARY_LIMIT: usize = 1024;
+
+/// Triggers a PagerDuty incident when a monitor goes down and resolves it
+/// when the monitor recovers. The monitor's id is the dedup key, so each
+/// outage is one incident however often it's reported.
+pub struct PagerDutyNotifier {
+    client: Client,
+    url: Url,
+    routing_key: Secret,
+    severity: String,
+}
+
+impl PagerDutyNotifier {
+    pub fn new(config: &PagerDutyConfig) -> Result<Self, String> {
+        let base_url = config.base_url.as_deref().unwrap_or(DEFAULT_BASE_URL);
+        let url = Url::parse(&format!("{}/v2/enqueue", base_url.trim_end_matches('/')))
+            .map_err(|e| format!("invalid pagerduty base_url: {e}"))?;
+        let client = Client::builder()
+            .timeout(config.timeout.unwrap_or(DEFAULT_TIMEOUT))
+            .build()
+            .map_err(|e| format!("failed to create pagerduty client: {e}"))?;
+
+        Ok(Self {
+            client,
+            url,
+            routing_key: config.routing_key.clone(),
+            severity: config
+                .severity
+                .clone()
+                .unwrap_or_else(|| DEFAULT_SEVERITY.to_string()),
+        })
+    }
+
+    fn event(&self, routing_key: String, alert: &Alert) -> Value {
+        match alert.event {
+            AlertEvent::Down => json!({
+                "routing_key": routing_key,
+                "event_action": "trigger",
+                "dedup_key": alert.monitor_id.to_string(),
+                "client": "sammy_monitor",
+                "payload": {
+                    "summary": truncate(&alert.summary(), SUMMARY_LIMIT),
+                    "source": alert.url,
+                    "severity": self.severity,
+                    "timestamp": alert.down_since.to_rfc3339(),
+                    "component": alert.monitor_name,
+                    "group": alert.group,
+                    "class": alert.error_type,
+                    "custom_details": facts_object(alert),
+                },
+            }),
+            AlertEvent::Recovered => json!({
+                "routing_key": routing_key,
+                "event_action": "resolve",
+                "dedup_key": alert.monitor_id.to_string(),
+            }),
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for PagerDutyNotifier {
+    fn name(&self) -> String {
+        format!("pagerduty ({})", self.url.host_str().unwrap_or_default())
+    }
+
+    async fn notify(&self, alert: &Alert) -> Result<(), NotifyError> {
+        let event = self.event(self.routing_key.resolve()?, alert);
+        let response = self
+            .client
+            .post(self.url.clone())
+            .json(&event)
+            .send()
+            .await
+            .map_err(request_failed)?;
+
+        check_status("pagerduty", &response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use mockito::Matcher;
+    use uuid::Uuid;
+
+    fn alert(event: AlertEvent) -> Alert {
+        Alert {
+            event,
+            monitor_id: Uuid::parse_str("550e8400-e29b-41d4-a716-446655440000").unwrap(),
+            monitor_name: "PagerDuty Test".to_string(),
+            url: "https://pagerduty.example.com".to_string(),
+            group: Some("Public".to_string()),
+            down_since: Utc::now(),
+            timestamp: Utc::now(),
+            failures: 2,
+            status_code: Some(503),
+            response_time_ms: 450,
+            error_type: Some("http_error".to_string()),
+            error: Some("HTTP 503".to_string()),
+        }
+    }
+
+    fn notifier(base_url: String) -> PagerDutyNotifier {
+        PagerDutyNotifier::new(&PagerDutyConfig {
+            routing_key: Secret::Plain("routing-key".to_string()),
+            base_url: Some(base_url),
+            severity: None,
+            timeout: None,
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_trigger_and_resolve() {
+        let mut server = mockito::Server::new_async().await;
+        let trigger = server
+            .mock("POST", "/v2/enqueue")
+            .match_body(Matcher::PartialJson(json!({
+                "routing_key": "routing-key",
+                "event_action": "trigger",
+                "dedup_key": "550e8400-e29b-41d4-a716-446655440000",
+                "payload": {
+                    "summary": "[DOWN] PagerDuty Test (https://pagerduty.example.com)",
+                    "severity": "critical",
+                    "component": "PagerDuty Test",
+                    "group": "Public",
+                    "class": "http_error",
+                },
+            })))
+            .with_status(202)
+            .create_async()
+            .await;
+        let resolve = server
+            .mock("POST", "/v2/enqueue")
+            .match_body(Matcher::PartialJson(json!({
+                "event_action": "resolve",
+                "dedup_key": "550e8400-e29b-41d4-a716-446655440000",
+            })))
+            .with_status(202)
+            .create_async()
+            .await;
+
+        let notifier = notifier(server.url());
+        notifier.notify(&alert(AlertEvent::Down)).await.unwrap();
+        notifier
+            .notify(&alert(AlertEvent::Recovered))
+            .await
+            .unwrap();
+        trigger.assert_async().await;
+        resolve.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_error_statuses() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("POST", "/v2/enqueue")
+            .with_status(429)
+            .create_async()
+            .await;
+        let error = notifier(server.url())
+            .notify(&alert(AlertEvent::Down))
+            .await
+            .unwrap_err();
+        assert!(error.is_retryable());
+
+        server.reset();
+        let _mock = server
+            .mock("POST", "/v2/enqueue")
+            .with_status(400)
+            .create_async()
+            .await;
+        let error = notifier(server.url())
+            .notify(&alert(AlertEvent::Down))
+            .await
+            .unwrap_err();
+        assert!(!error.is_retryable());
+        assert!(error.to_string().contains("400"));
+    }
+}
@@ -0,0 +1,236 @@
+use async_trait::async_trait;
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+use super::{Notifier, NotifyError};
+use crate::alerting::Alert;
+use crate::settings::{SmtpConfig, SmtpTls, DEFAULT_TIMEOUT};
+
+/// Emails each alert through an SMTP server
+pub struct SmtpNotifier {
+    config: SmtpConfig,
+}
+
+impl SmtpNotifier {
+    pub fn new(config: &SmtpConfig) -> Self {
+        Self {
+            config: config.clone(),
+        }
+    }
+
+    /// The alert as an email to every recipient
+    fn message(&self, alert: &Alert) -> Result<Message, NotifyError> {
+        let mut builder = Message::builder()
+            .from(mailbox(&self.config.from)?)
+            .subject(alert.summary())
+            .header(ContentType::TEXT_PLAIN);
+        for recipient in &self.config.to {
+            builder = builder.to(mailbox(recipient)?);
+        }
+
+        builder
+            .body(alert.details())
+            .map_err(|e| NotifyError::permanent(format!("invalid email: {e}")))
+    }
+
+    /// A connection to the server, secured as configured
+    fn transport(&self) -> Result<AsyncSmtpTransport<Tokio1Executor>, NotifyError> {
+        let host = self.config.host.as_str();
+        let builder = match self.config.tls {
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host),
+            SmtpTls::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host),
+            SmtpTls::None => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(
+                host,
+            )),
+        }
+        .map_err(|e| NotifyError::permanent(format!("TLS setup failed: {e}")))?
+        .port(self.config.port())
+        .timeout(Some(self.config.timeout.unwrap_or(DEFAULT_TIMEOUT)));
+
+        let builder = match &self.config.username {
+            Some(username) => {
+                let password = match &self.config.password {
+                    Some(password) => password.resolve()?,
+                    None => String::new(),
+                };
+                builder.credentials(Credentials::new(username.clone(), password))
+            }
+            None => builder,
+        };
+
+        Ok(builder.build())
+    }
+}
+
+#[async_trait]
+impl Notifier for SmtpNotifier {
+    fn name(&self) -> String {
+        format!("smtp ({})", self.config.host)
+    }
+
+    async fn notify(&self, alert: &Alert) -> Result<(), NotifyError> {
+        let message = self.message(alert)?;
+        let transport = self.transport()?;
+
+        let sending = transport.send(message);
+        let result = tokio::time::timeout(self.config.timeout.unwrap_or(DEFAULT_TIMEOUT), sending)
+            .await
+            .map_err(|_| {
+                NotifyError::transient(format!("timed out sending email via {}", self.config.host))
+            })?;
+
+        result.map(|_| ()).map_err(|e| {
+            let message = format!("failed to send email via {}: {e}", self.config.host);
+            // 5xx replies and local errors would repeat; 4xx replies are
+            // temporary, e.g. a full mailbox or greylisting
+            if e.is_permanent() || e.is_client() {
+                NotifyError::permanent(message)
+            } else {
+                NotifyError::transient(message)
+            }
+        })
+    }
+}
+
+/// Parse an address from the settings, e.g. `Alerts <alerts@example.com>`
+fn mailbox(address: &str) -> Result<Mailbox, NotifyError> {
+    address
+        .parse()
+        .map_err(|e| NotifyError::permanent(format!("invalid email address {address}: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alerting::AlertEvent;
+    use crate::secret::Secret;
+    use base64::engine::general_purpose::STANDARD as BASE64;
+    use base64::Engine;
+    use chrono::Utc;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+    use tokio::task::JoinHandle;
+    use uuid::Uuid;
+
+    fn alert() -> Alert {
+        Alert {
+            event: AlertEvent::Down,
+            monitor_id: Uuid::new_v4(),
+            monitor_name: "Smtp Test".to_string(),
+            url: "https://smtp.example.com".to_string(),
+            group: None,
+            down_since: Utc::now(),
+            timestamp: Utc::now(),
+            failures: 1,
+            status_code: Some(503),
+            response_time_ms: 1200,
+            error_type: Some("http_error".to_string()),
+            error: Some("HTTP 503".to_string()),
+        }
+    }
+
+    fn config(port: u16) -> SmtpConfig {
+        SmtpConfig {
+            host: "127.0.0.1".to_string(),
+            port: Some(port),
+            tls: SmtpTls::None,
+            username: Some("alerts".to_string()),
+            password: Some(Secret::Plain("hunter2".to_string())),
+            from: "monitor@example.com".to_string(),
+            to: vec!["ops@example.com".to_string(), "dev@example.com".to_string()],
+            timeout: None,
+        }
+    }
+
+    /// A scripted SMTP server that accepts one message, rejecting
+    /// recipients in `reject`, and returns what the client sent
+    async fn serve(reject: &'static str) -> (u16, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = BufReader::new(stream);
+            let mut received = Vec::new();
+            stream.write_all(b"220 test ESMTP\r\n").await.unwrap();
+
+            let mut in_data = false;
+            loop {
+                let mut line = String::new();
+                if stream.read_line(&mut line).await.unwrap() == 0 {
+                    break;
+                }
+                let line = line.trim_end_matches("\r\n").to_string();
+                received.push(line.clone());
+
+                let reply: &[u8] = if in_data {
+                    if line != "." {
+                        continue;
+                    }
+                    in_data = false;
+                    b"250 queued\r\n"
+                } else if line.starts_with("EHLO") {
+                    b"250-test\r\n250 AUTH PLAIN\r\n"
+                } else if line.starts_with("AUTH") {
+                    b"235 ok\r\n"
+                } else if line.starts_with("RCPT") && line.contains(reject) {
+                    b"550 no such user\r\n"
+                } else if line == "DATA" {
+                    in_data = true;
+                    b"354 go ahead\r\n"
+                } else if line == "QUIT" {
+                    stream.write_all(b"221 bye\r\n").await.unwrap();
+                    break;
+                } else {
+                    b"250 ok\r\n"
+                };
+                stream.write_all(reply).await.unwrap();
+            }
+            received
+        });
+
+        (port, server)
+    }
+
+    #[tokio::test]
+    async fn test_smtp_sends_alert() {
+        let (port, server) = serve("nobody").await;
+
+        let mut alert = alert();
+        alert.error = Some("Bad gateway\n.hidden line".to_string());
+        SmtpNotifier::new(&config(port))
+            .notify(&alert)
+            .await
+            .unwrap();
+
+        let received = server.await.unwrap();
+        assert!(received[0].starts_with("EHLO "));
+        let credentials = received[1].strip_prefix("AUTH PLAIN ").unwrap();
+        assert_eq!(BASE64.decode(credentials).unwrap(), b"\0alerts\0hunter2");
+        assert_eq!(received[2], "MAIL FROM:<monitor@example.com>");
+        assert_eq!(received[3], "RCPT TO:<ops@example.com>");
+        assert_eq!(received[4], "RCPT TO:<dev@example.com>");
+        assert_eq!(received[5], "DATA");
+        assert!(
+            received.contains(&"Subject: [DOWN] Smtp Test (https://smtp.example.com)".to_string())
+        );
+        assert!(received.contains(&"To: ops@example.com, dev@example.com".to_string()));
+        assert!(received.contains(&"Error: Bad gateway".to_string()));
+        assert!(received.contains(&"..hidden line".to_string()));
+        assert_eq!(received.last().unwrap(), "QUIT");
+    }
+
+    #[tokio::test]
+    async fn test_smtp_rejected_recipient() {
+        let (port, _server) = serve("dev@").await;
+
+        let error = SmtpNotifier::new(&config(port))
+            .notify(&alert())
+            .await
+            .unwrap_err();
+        assert!(!error.is_retryable());
+        assert!(error.to_string().contains("550"), "{error}");
+    }
+}
@@ -0,0 +1,127 @@
+use async_trait::async_trait;
+use reqwest::{Client, Url};
+use std::collections::BTreeMap;
+
+use super::{check_status, request_failed, Notifier, NotifyError};
+use crate::alerting::Alert;
+use crate::secret::Secret;
+use crate::settings::{WebhookConfig, DEFAULT_TIMEOUT};
+
+/// POSTs each alert as JSON to a URL
+pub struct WebhookNotifier {
+    client: Client,
+    url: Url,
+    headers: BTreeMap<String, Secret>,
+}
+
+impl WebhookNotifier {
+    pub fn new(config: &WebhookConfig) -> Result<Self, String> {
+        let url = Url::parse(&config.url).map_err(|e| format!("invalid webhook url: {e}"))?;
+        let client = Client::builder()
+            .timeout(config.timeout.unwrap_or(DEFAULT_TIMEOUT))
+            .build()
+            .map_err(|e| format!("failed to create webhook client: {e}"))?;
+
+        Ok(Self {
+            client,
+            url,
+            headers: config.headers.clone(),
+        })
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    fn name(&self) -> String {
+        // The full URL often carries a token
+        format!("webhook ({})", self.url.host_str().unwrap_or_default())
+    }
+
+    async fn notify(&self, alert: &Alert) -> Result<(), NotifyError> {
+        let mut request = self.client.post(self.url.clone()).json(alert);
+        for (name, value) in &self.headers {
+            request = request.header(name, value.resolve()?);
+        }
+
+        let response = request.send().await.map_err(request_failed)?;
+        check_status("webhook", &response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alerting::AlertEvent;
+    use chrono::Utc;
+    use mockito::Matcher;
+    use serde_json::json;
+    use uuid::Uuid;
+
+    fn alert() -> Alert {
+        Alert {
+            event: AlertEvent::Down,
+            monitor_id: Uuid::new_v4(),
+            monitor_name: "Webhook Test".to_string(),
+            url: "https://webhook.example.com".to_string(),
+            group: None,
+            down_since: Utc::now(),
+            timestamp: Utc::now(),
+            failures: 3,
+            status_code: Some(503),
+            response_time_ms: 1200,
+            error_type: Some("read_timeout".to_string()),
+            error: Some("Timed out waiting for the response".to_string()),
+        }
+    }
+
+    fn notifier(url: String) -> WebhookNotifier {
+        WebhookNotifier::new(&WebhookConfig {
+            url,
+            headers: BTreeMap::from([(
+                "Authorization".to_string(),
+                Secret::Plain("Bearer hook-token".to_string()),
+            )]),
+            timeout: None,
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_webhook_posts_alert() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/hook")
+            .match_header("authorization", "Bearer hook-token")
+            .match_body(Matcher::PartialJson(json!({
+                "event": "down",
+                "monitor_name": "Webhook Test",
+                "failures": 3,
+                "error_type": "read_timeout",
+            })))
+            .with_status(204)
+            .create_async()
+            .await;
+
+        let notifier = notifier(format!("{}/hook", server.url()));
+        notifier.notify(&alert()).await.unwrap();
+        mock.assert_async().await;
+        assert_eq!(notifier.name(), "webhook (127.0.0.1)");
+    }
+
+    #[tokio::test]
+    async fn test_webhook_error_status() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("POST", "/hook")
+            .with_status(500)
+            .create_async()
+            .await;
+
+        let error = notifier(format!("{}/hook", server.url()))
+            .notify(&alert())
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("500"));
+        assert!(error.is_retryable());
+    }
+}
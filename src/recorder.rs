@@ -0,0 +1,367 @@
+use metrics::{
+    Counter, Gauge, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder, SetRecorderError,
+    SharedString, Unit,
+};
+use metrics_exporter_prometheus::formatting::{
+    sanitize_label_key, sanitize_label_value, sanitize_metric_name, write_help_line,
+    write_metric_line, write_type_line,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+/// Buckets of histograms without their own, Prometheus' defaults
+const DEFAULT_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Records metrics for the Prometheus exposition format.
+///
+/// The exporter's own recorder keeps every series it has seen. This one only
+/// exports a series while a handle to it is held, so dropping a monitor's
+/// handles removes its series.
+pub struct MetricsRecorder {
+    inner: Arc<Inner>,
+}
+
+/// Renders what a [`MetricsRecorder`] recorded
+#[derive(Clone)]
+pub struct MetricsHandle {
+    inner: Arc<Inner>,
+}
+
+/// Configures a [`MetricsRecorder`]
+#[derive(Default)]
+pub struct MetricsRecorderBuilder {
+    global_labels: Vec<(String, String)>,
+    buckets: HashMap<String, Vec<f64>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    /// Labels added to every series, before the series' own
+    global_labels: Vec<(String, String)>,
+    /// Histogram buckets by metric name
+    buckets: HashMap<String, Vec<f64>>,
+    descriptions: Mutex<HashMap<String, SharedString>>,
+    counters: Mutex<HashMap<Key, Weak<AtomicU64>>>,
+    /// Gauge values, stored as `f64` bits
+    gauges: Mutex<HashMap<Key, Weak<AtomicU64>>>,
+    histograms: Mutex<HashMap<Key, Weak<Buckets>>>,
+}
+
+/// Samples recorded in a histogram series
+struct Buckets(Mutex<metrics_util::Histogram>);
+
+impl HistogramFn for Buckets {
+    fn record(&self, value: f64) {
+        self.0.lock().unwrap().record(value);
+    }
+}
+
+impl MetricsRecorderBuilder {
+    /// Add a label to every series
+    pub fn add_global_label(mut self, key: &str, value: &str) -> Self {
+        self.global_labels
+            .push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Set the buckets of the histogram named `name`
+    pub fn set_buckets(mut self, name: &str, buckets: &[f64]) -> Self {
+        self.buckets.insert(name.to_string(), buckets.to_vec());
+        self
+    }
+
+    pub fn build(self) -> MetricsRecorder {
+        MetricsRecorder {
+            inner: Arc::new(Inner {
+                global_labels: self.global_labels,
+                buckets: self.buckets,
+                ..Inner::default()
+            }),
+        }
+    }
+
+    /// Build the recorder and install it as the global recorder
+    pub fn install(self) -> Result<MetricsHandle, SetRecorderError<MetricsRecorder>> {
+        let recorder = self.build();
+        let handle = recorder.handle();
+        metrics::set_global_recorder(recorder)?;
+        Ok(handle)
+    }
+}
+
+impl Default for MetricsRecorder {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+impl MetricsRecorder {
+    pub fn builder() -> MetricsRecorderBuilder {
+        MetricsRecorderBuilder::default()
+    }
+
+    pub fn handle(&self) -> MetricsHandle {
+        MetricsHandle {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl Recorder for MetricsRecorder {
+    fn describe_counter(&self, key: KeyName, _unit: Option<Unit>, description: SharedString) {
+        self.inner.describe(key, description);
+    }
+
+    fn describe_gauge(&self, key: KeyName, _unit: Option<Unit>, description: SharedString) {
+        self.inner.describe(key, description);
+    }
+
+    fn describe_histogram(&self, key: KeyName, _unit: Option<Unit>, description: SharedString) {
+        self.inner.describe(key, description);
+    }
+
+    fn register_counter(&self, key: &Key, _metadata: &Metadata<'_>) -> Counter {
+        Counter::from_arc(live(&self.inner.counters, key, || AtomicU64::new(0)))
+    }
+
+    fn register_gauge(&self, key: &Key, _metadata: &Metadata<'_>) -> Gauge {
+        Gauge::from_arc(live(&self.inner.gauges, key, || AtomicU64::new(0)))
+    }
+
+    fn register_histogram(&self, key: &Key, _metadata: &Metadata<'_>) -> Histogram {
+        let name = sanitize_metric_name(key.name());
+        let bounds = self
+            .inner
+            .buckets
+            .get(&name)
+            .map_or(DEFAULT_BUCKETS, Vec::as_slice);
+
+        Histogram::from_arc(live(&self.inner.histograms, key, || {
+            Buckets(Mutex::new(
+                metrics_util::Histogram::new(bounds).expect("buckets are never empty"),
+            ))
+        }))
+    }
+}
+
+/// The series under `key` while a handle to it is held, or a new one
+fn live<T>(series: &Mutex<HashMap<Key, Weak<T>>>, key: &Key, new: impl FnOnce() -> T) -> Arc<T> {
+    let mut series = series.lock().unwrap();
+    if let Some(existing) = series.get(key).and_then(Weak::upgrade) {
+        return existing;
+    }
+
+    let created = Arc::new(new());
+    series.insert(key.clone(), Arc::downgrade(&created));
+    created
+}
+
+impl MetricsHandle {
+    /// Render every series a handle is held to in the Prometheus
+    /// exposition format
+    pub fn render(&self) -> String {
+        let inner = &self.inner;
+        let mut output = String::new();
+
+        for (name, series) in inner.live(&inner.counters) {
+            inner.write_header(&mut output, &name, "counter");
+            for (labels, counter) in series {
+                let value = counter.load(Ordering::Acquire);
+                write_metric_line::<&str, u64>(&mut output, &name, None, &labels, None, value);
+            }
+            output.push('\n');
+        }
+
+        for (name, series) in inner.live(&inner.gauges) {
+            inner.write_header(&mut output, &name, "gauge");
+            for (labels, gauge) in series {
+                let value = f64::from_bits(gauge.load(Ordering::Acquire));
+                write_metric_line::<&str, f64>(&mut output, &name, None, &labels, None, value);
+            }
+            output.push('\n');
+        }
+
+        for (name, series) in inner.live(&inner.histograms) {
+            inner.write_header(&mut output, &name, "histogram");
+            for (labels, histogram) in series {
+                let histogram = histogram.0.lock().unwrap();
+                for (le, count) in histogram.buckets() {
+                    write_metric_line(
+                        &mut output,
+                        &name,
+                        Some("bucket"),
+                        &labels,
+                        Some(("le", le)),
+                        count,
+                    );
+                }
+                write_metric_line(
+                    &mut output,
+                    &name,
+                    Some("bucket"),
+                    &labels,
+                    Some(("le", "+Inf")),
+                    histogram.count(),
+                );
+                write_metric_line::<&str, f64>(
+                    &mut output,
+                    &name,
+                    Some("sum"),
+                    &labels,
+                    None,
+                    histogram.sum(),
+                );
+                write_metric_line::<&str, u64>(
+                    &mut output,
+                    &name,
+                    Some("count"),
+                    &labels,
+                    None,
+                    histogram.count(),
+                );
+            }
+            output.push('\n');
+        }
+
+        output
+    }
+}
+
+impl Inner {
+    /// Keep the first description given for a metric
+    fn describe(&self, key: KeyName, description: SharedString) {
+        self.descriptions
+            .lock()
+            .unwrap()
+            .entry(sanitize_metric_name(key.as_str()))
+            .or_insert(description);
+    }
+
+    /// Series of one kind that a handle is still held to, by name and then
+    /// labels, forgetting the rest
+    #[allow(clippy::type_complexity)]
+    fn live<T>(
+        &self,
+        series: &Mutex<HashMap<Key, Weak<T>>>,
+    ) -> BTreeMap<String, BTreeMap<Vec<String>, Arc<T>>> {
+        let mut series = series.lock().unwrap();
+        series.retain(|_, handle| handle.strong_count() > 0);
+
+        let mut by_name: BTreeMap<String, BTreeMap<Vec<String>, Arc<T>>> = BTreeMap::new();
+        for (key, handle) in series.iter() {
+            if let Some(handle) = handle.upgrade() {
+                by_name
+                    .entry(sanitize_metric_name(key.name()))
+                    .or_default()
+                    .insert(self.labels(key), handle);
+            }
+        }
+        by_name
+    }
+
+    /// The global labels followed by the key's own, which win over global
+    /// ones of the same name
+    fn labels(&self, key: &Key) -> Vec<String> {
+        let mut labels = self.global_labels.clone();
+        for label in key.labels() {
+            match labels.iter_mut().find(|(name, _)| name == label.key()) {
+                Some(existing) => existing.1 = label.value().to_string(),
+                None => labels.push((label.key().to_string(), label.value().to_string())),
+            }
+        }
+
+        labels
+            .iter()
+            .map(|(name, value)| {
+                format!(
+                    "{}=\"{}\"",
+                    sanitize_label_key(name),
+                    sanitize_label_value(value)
+                )
+            })
+            .collect()
+    }
+
+    fn write_header(&self, output: &mut String, name: &str, metric_type: &str) {
+        if let Some(description) = self.descriptions.lock().unwrap().get(name) {
+            write_help_line(output, name, description);
+        }
+        write_type_line(output, name, metric_type);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let recorder = MetricsRecorder::builder()
+            .add_global_label("app", "test")
+            .set_buckets("latency_seconds", &[0.1, 1.0])
+            .build();
+        let handle = recorder.handle();
+
+        let (counter, gauge, histogram) = metrics::with_local_recorder(&recorder, || {
+            metrics::describe_counter!("requests_total", "Requests served");
+            (
+                metrics::counter!("requests_total", "status" => "ok"),
+                metrics::gauge!("up", "app" => "override"),
+                metrics::histogram!("latency_seconds"),
+            )
+        });
+        counter.increment(3);
+        gauge.set(1.5);
+        histogram.record(0.05);
+        histogram.record(0.5);
+
+        let output = handle.render();
+        assert!(output.contains("# HELP requests_total Requests served\n"));
+        assert!(output.contains("# TYPE requests_total counter\n"));
+        assert!(output.contains("requests_total{app=\"test\",status=\"ok\"} 3\n"));
+        assert!(output.contains("up{app=\"override\"} 1.5\n"));
+        assert!(output.contains("# TYPE latency_seconds histogram\n"));
+        assert!(output.contains("latency_seconds_bucket{app=\"test\",le=\"0.1\"} 1\n"));
+        assert!(output.contains("latency_seconds_bucket{app=\"test\",le=\"1\"} 2\n"));
+        assert!(output.contains("latency_seconds_bucket{app=\"test\",le=\"+Inf\"} 2\n"));
+        assert!(output.contains("latency_seconds_sum{app=\"test\"} 0.55\n"));
+        assert!(output.contains("latency_seconds_count{app=\"test\"} 2\n"));
+    }
+
+    #[test]
+    fn test_dropped_handles_remove_series() {
+        let recorder = MetricsRecorder::default();
+        let handle = recorder.handle();
+
+        let (kept, dropped) = metrics::with_local_recorder(&recorder, || {
+            (
+                metrics::counter!("checks_total", "monitor" => "kept"),
+                metrics::counter!("checks_total", "monitor" => "dropped"),
+            )
+        });
+        kept.increment(1);
+        dropped.increment(1);
+        assert!(handle.render().contains("monitor=\"dropped\""));
+
+        drop(dropped);
+        let output = handle.render();
+        assert!(!output.contains("monitor=\"dropped\""));
+        assert!(output.contains("checks_total{monitor=\"kept\"} 1\n"));
+
+        // Registering the same series again while held shares its value
+        let again = metrics::with_local_recorder(
+            &recorder,
+            || metrics::counter!("checks_total", "monitor" => "kept"),
+        );
+        again.increment(1);
+        assert!(handle
+            .render()
+            .contains("checks_total{monitor=\"kept\"} 2\n"));
+
+        drop((kept, again));
+        assert_eq!(handle.render(), "");
+    }
+}
@@ -0,0 +1,327 @@
+use log::{error, info, warn};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::{mpsc, watch};
+use tokio::time::{Interval, MissedTickBehavior};
+
+use crate::settings::Settings;
+
+/// How often the settings file is checked for changes when it can't be
+/// watched
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long to wait for a write to settle before reloading, as editors and
+/// `cp` change a file in several steps
+const SETTLE_DELAY: Duration = Duration::from_millis(100);
+
+/// Reload the settings file whenever it changes or the process receives
+/// SIGHUP, publishing each version that loads cleanly on `updates`.
+///
+/// Changes are picked up from file system events on the file's directory,
+/// so files replaced by a rename are seen too. Where those aren't available
+/// the file is polled every `poll_interval` instead.
+///
+/// Settings that fail to load are logged and skipped, so a bad edit leaves
+/// the running settings in place. Returns once every receiver of `updates`
+/// is gone.
+pub async fn watch_settings(
+    path: PathBuf,
+    poll_interval: Duration,
+    updates: watch::Sender<Settings>,
+) -> io::Result<()> {
+    let changes = match Changes::watch(&path) {
+        Ok(changes) => changes,
+        Err(e) => {
+            warn!(
+                "Can't watch {} for changes, polling it every {poll_interval:?} instead: {e}",
+                path.display()
+            );
+            Changes::poll(poll_interval)
+        }
+    };
+
+    reload_on_changes(path, changes, updates).await
+}
+
+/// Where to learn that the settings file may have changed
+enum Changes {
+    /// File system events for the file, sent while the watcher is alive
+    Watch {
+        _watcher: RecommendedWatcher,
+        events: mpsc::UnboundedReceiver<()>,
+    },
+    Poll(Interval),
+}
+
+impl Changes {
+    fn watch(path: &Path) -> notify::Result<Self> {
+        let name = path.file_name().map(|name| name.to_os_string());
+        let (sender, receiver) = mpsc::unbounded_channel();
+
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            let relevant = match event {
+                Ok(event) => {
+                    !event.kind.is_access()
+                        && event
+                            .paths
+                            .iter()
+                            .any(|changed| changed.file_name() == name.as_deref())
+                }
+                // Rather reload once too often than miss a change
+                Err(_) => true,
+            };
+            if relevant {
+                let _ = sender.send(());
+            }
+        })?;
+
+        // Editors often replace the file, which a watch on it would lose
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        watcher.watch(directory, RecursiveMode::NonRecursive)?;
+
+        Ok(Changes::Watch {
+            _watcher: watcher,
+            events: receiver,
+        })
+    }
+
+    fn poll(interval: Duration) -> Self {
+        let mut poll = tokio::time::interval(interval);
+        poll.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        Changes::Poll(poll)
+    }
+
+    /// Wait until the file may have changed
+    async fn next(&mut self) {
+        match self {
+            Changes::Watch { events, .. } => {
+                if events.recv().await.is_none() {
+                    // The watcher stopped, so changes can only come by signal
+                    std::future::pending::<()>().await;
+                }
+                tokio::time::sleep(SETTLE_DELAY).await;
+                while events.try_recv().is_ok() {}
+            }
+            Changes::Poll(poll) => {
+                poll.tick().await;
+            }
+        }
+    }
+}
+
+async fn reload_on_changes(
+    path: PathBuf,
+    mut changes: Changes,
+    updates: watch::Sender<Settings>,
+) -> io::Result<()> {
+    let mut hangup = signal(SignalKind::hangup())?;
+    let mut version = file_version(&path);
+
+    loop {
+        tokio::select! {
+            Some(()) = hangup.recv() => {
+                info!("Received SIGHUP, reloading settings from {}", path.display());
+            }
+            () = changes.next() => {
+                let current = file_version(&path);
+                if current == version {
+                    continue;
+                }
+                version = current;
+                info!("Settings file {} changed, reloading", path.display());
+            }
+            _ = updates.closed() => return Ok(()),
+        }
+
+        match Settings::load(&path) {
+            Ok(settings) => {
+                updates.send_replace(settings);
+            }
+            Err(e) => error!(
+                "Failed to reload settings from {}, keeping the current settings: {e}",
+                path.display()
+            ),
+        }
+    }
+}
+
+/// Modification time and size of `path`, to notice when it is rewritten
+fn file_version(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use tempfile::NamedTempFile;
+
+    const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+    fn monitor(id: &str) -> String {
+        format!(
+            r#"
+[[monitors]]
+id = "{id}"
+name = "Monitor {id}"
+url = "https://example.com"
+interval = "1m"
+enabled = true
+"#
+        )
+    }
+
+    fn write_settings(file: &NamedTempFile, contents: &str) {
+        fs::write(file.path(), contents).expect("Failed to write settings");
+    }
+
+    fn initial_settings() -> Settings {
+        Settings::from_str("monitors = []").unwrap()
+    }
+
+    /// Wait for an update with `count` monitors, skipping any others
+    async fn expect_monitors(updates: &mut watch::Receiver<Settings>, count: usize) {
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            updates.wait_for(|settings| settings.monitors.len() == count),
+        )
+        .await
+        .expect("settings should be reloaded")
+        .expect("watcher stopped");
+    }
+
+    #[tokio::test]
+    async fn test_reload_on_change() {
+        let file = NamedTempFile::new().expect("Failed to create temp file");
+        write_settings(&file, &monitor("550e8400-e29b-41d4-a716-446655440000"));
+
+        let (sender, mut updates) = watch::channel(initial_settings());
+        tokio::spawn(watch_settings(
+            file.path().to_path_buf(),
+            POLL_INTERVAL,
+            sender,
+        ));
+        tokio::time::sleep(POLL_INTERVAL * 3).await;
+
+        write_settings(
+            &file,
+            &format!(
+                "{}{}",
+                monitor("550e8400-e29b-41d4-a716-446655440000"),
+                monitor("550e8400-e29b-41d4-a716-446655440001")
+            ),
+        );
+
+        expect_monitors(&mut updates, 2).await;
+    }
+
+    #[tokio::test]
+    async fn test_reload_on_replace() {
+        let directory = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = directory.path().join("settings.toml");
+        fs::write(&path, monitor("550e8400-e29b-41d4-a716-446655440000")).unwrap();
+
+        let (sender, mut updates) = watch::channel(initial_settings());
+        tokio::spawn(watch_settings(
+            path.clone(),
+            Duration::from_secs(60),
+            sender,
+        ));
+        tokio::time::sleep(POLL_INTERVAL * 3).await;
+
+        // Written elsewhere and renamed over the file, as editors do
+        let replacement = directory.path().join("settings.toml.new");
+        fs::write(
+            &replacement,
+            format!(
+                "{}{}",
+                monitor("550e8400-e29b-41d4-a716-446655440000"),
+                monitor("550e8400-e29b-41d4-a716-446655440001")
+            ),
+        )
+        .unwrap();
+        fs::rename(&replacement, &path).unwrap();
+
+        expect_monitors(&mut updates, 2).await;
+    }
+
+    #[tokio::test]
+    async fn test_reload_when_polling() {
+        let file = NamedTempFile::new().expect("Failed to create temp file");
+        write_settings(&file, &monitor("550e8400-e29b-41d4-a716-446655440000"));
+
+        let (sender, mut updates) = watch::channel(initial_settings());
+        tokio::spawn(reload_on_changes(
+            file.path().to_path_buf(),
+            Changes::poll(POLL_INTERVAL),
+            sender,
+        ));
+        tokio::time::sleep(POLL_INTERVAL * 3).await;
+
+        write_settings(
+            &file,
+            &format!(
+                "{}{}",
+                monitor("550e8400-e29b-41d4-a716-446655440000"),
+                monitor("550e8400-e29b-41d4-a716-446655440001")
+            ),
+        );
+
+        expect_monitors(&mut updates, 2).await;
+    }
+
+    #[tokio::test]
+    async fn test_invalid_settings_are_skipped() {
+        let file = NamedTempFile::new().expect("Failed to create temp file");
+        write_settings(&file, &monitor("550e8400-e29b-41d4-a716-446655440000"));
+
+        let (sender, mut updates) = watch::channel(initial_settings());
+        tokio::spawn(watch_settings(
+            file.path().to_path_buf(),
+            POLL_INTERVAL,
+            sender,
+        ));
+        tokio::time::sleep(POLL_INTERVAL * 3).await;
+
+        write_settings(&file, "[[monitors]]\nid = \"not a uuid\"\n");
+        tokio::time::sleep(POLL_INTERVAL * 5).await;
+        assert!(!updates.has_changed().unwrap());
+
+        write_settings(
+            &file,
+            &format!(
+                "{}{}",
+                monitor("550e8400-e29b-41d4-a716-446655440000"),
+                monitor("550e8400-e29b-41d4-a716-446655440001")
+            ),
+        );
+        expect_monitors(&mut updates, 2).await;
+    }
+
+    #[tokio::test]
+    async fn test_watcher_stops_when_updates_close() {
+        let file = NamedTempFile::new().expect("Failed to create temp file");
+
+        let (sender, updates) = watch::channel(initial_settings());
+        let watcher = tokio::spawn(watch_settings(
+            file.path().to_path_buf(),
+            POLL_INTERVAL,
+            sender,
+        ));
+        drop(updates);
+
+        tokio::time::timeout(Duration::from_secs(5), watcher)
+            .await
+            .expect("watcher should stop")
+            .unwrap()
+            .unwrap();
+    }
+}
@@ -0,0 +1,214 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// Upper bound on how far a monitor's first check is pushed back when
+/// spreading start times, so long intervals still get checked soon after
+/// startup.
+const MAX_INITIAL_SPREAD: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct ScheduledCheck {
+    due: Instant,
+    monitor_id: Uuid,
+}
+
+/// Priority queue of monitor checks keyed on their next due time
+#[derive(Debug, Default)]
+pub struct Scheduler {
+    queue: BinaryHeap<Reverse<ScheduledCheck>>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self {
+            queue: BinaryHeap::new(),
+        }
+    }
+
+    /// Schedule a check for `monitor_id` at `due`
+    pub fn schedule(&mut self, monitor_id: Uuid, due: Instant) {
+        self.queue.push(Reverse(ScheduledCheck { due, monitor_id }));
+    }
+
+    /// Drop any scheduled checks for `monitor_id`
+    pub fn remove(&mut self, monitor_id: Uuid) {
+        self.queue
+            .retain(|Reverse(check)| check.monitor_id != monitor_id);
+    }
+
+    /// When the earliest scheduled check is due, if any
+    pub fn next_due(&self) -> Option<Instant> {
+        self.queue.peek().map(|Reverse(check)| check.due)
+    }
+
+    /// Remove and return every check due at or before `now`, earliest first,
+    /// as `(monitor_id, due)` pairs.
+    pub fn pop_due(&mut self, now: Instant) -> Vec<(Uuid, Instant)> {
+        let mut due = Vec::new();
+
+        while let Some(Reverse(check)) = self.queue.peek() {
+            if check.due > now {
+                break;
+            }
+            due.push((check.monitor_id, check.due));
+            self.queue.pop();
+        }
+
+        due
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+/// Delay before a monitor's first check.
+///
+/// Derived from the monitor id so monitors sharing an interval are spread out
+/// instead of all firing together, while each monitor keeps the same offset
+/// across restarts.
+pub fn initial_offset(monitor_id: Uuid, interval: Duration) -> Duration {
+    let spread = interval.min(MAX_INITIAL_SPREAD).as_millis() as u64;
+    if spread == 0 {
+        return Duration::ZERO;
+    }
+
+    let (high, low) = monitor_id.as_u64_pair();
+    Duration::from_millis((high ^ low) % spread)
+}
+
+/// Next due time after a check that was due at `due`.
+///
+/// Runs stay on a fixed cadence from the previous due time rather than from
+/// when the check happened to start, so they don't drift. Runs missed
+/// entirely (for example after the host was suspended) are skipped.
+pub fn next_due_after(due: Instant, interval: Duration, now: Instant) -> Instant {
+    let next = due + interval;
+    if next > now || interval.is_zero() {
+        return next;
+    }
+
+    let behind = now.duration_since(due).as_nanos();
+    let missed = behind / interval.as_nanos();
+    due + interval * (missed as u32 + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pop_due_returns_earliest_first() {
+        let mut scheduler = Scheduler::new();
+        let now = Instant::now();
+        let first = Uuid::new_v4();
+        let second = Uuid::new_v4();
+        let later = Uuid::new_v4();
+
+        scheduler.schedule(second, now + Duration::from_secs(2));
+        scheduler.schedule(later, now + Duration::from_secs(30));
+        scheduler.schedule(first, now + Duration::from_secs(1));
+
+        assert_eq!(scheduler.len(), 3);
+        assert_eq!(scheduler.next_due(), Some(now + Duration::from_secs(1)));
+
+        let due = scheduler.pop_due(now + Duration::from_secs(5));
+        assert_eq!(
+            due,
+            vec![
+                (first, now + Duration::from_secs(1)),
+                (second, now + Duration::from_secs(2)),
+            ]
+        );
+
+        assert_eq!(scheduler.len(), 1);
+        assert_eq!(scheduler.next_due(), Some(now + Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn test_pop_due_nothing_due() {
+        let mut scheduler = Scheduler::new();
+        let now = Instant::now();
+
+        assert!(scheduler.pop_due(now).is_empty());
+
+        scheduler.schedule(Uuid::new_v4(), now + Duration::from_secs(10));
+        assert!(scheduler.pop_due(now).is_empty());
+        assert!(!scheduler.is_empty());
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut scheduler = Scheduler::new();
+        let now = Instant::now();
+        let removed = Uuid::new_v4();
+        let kept = Uuid::new_v4();
+
+        scheduler.schedule(removed, now);
+        scheduler.schedule(kept, now + Duration::from_secs(1));
+        scheduler.schedule(removed, now + Duration::from_secs(2));
+
+        scheduler.remove(removed);
+
+        assert_eq!(scheduler.len(), 1);
+        assert_eq!(scheduler.next_due(), Some(now + Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn test_initial_offset_within_interval() {
+        let interval = Duration::from_secs(10);
+
+        for _ in 0..100 {
+            let offset = initial_offset(Uuid::new_v4(), interval);
+            assert!(offset < interval);
+        }
+    }
+
+    #[test]
+    fn test_initial_offset_is_stable_and_capped() {
+        let id = Uuid::new_v4();
+        let interval = Duration::from_secs(3600);
+
+        let offset = initial_offset(id, interval);
+        assert_eq!(offset, initial_offset(id, interval));
+        assert!(offset < MAX_INITIAL_SPREAD);
+    }
+
+    #[test]
+    fn test_initial_offset_spreads_monitors() {
+        let interval = Duration::from_secs(30);
+        let offsets: std::collections::HashSet<Duration> = (0..20)
+            .map(|_| initial_offset(Uuid::new_v4(), interval))
+            .collect();
+
+        assert!(offsets.len() > 1, "offsets should not all be identical");
+    }
+
+    #[test]
+    fn test_next_due_after_keeps_cadence() {
+        let start = Instant::now();
+        let interval = Duration::from_secs(10);
+
+        // Check started late but still before the next run
+        let next = next_due_after(start, interval, start + Duration::from_secs(3));
+        assert_eq!(next, start + interval);
+    }
+
+    #[test]
+    fn test_next_due_after_skips_missed_runs() {
+        let start = Instant::now();
+        let interval = Duration::from_secs(10);
+
+        let next = next_due_after(start, interval, start + Duration::from_secs(35));
+        assert_eq!(next, start + Duration::from_secs(40));
+
+        let next = next_due_after(start, interval, start + Duration::from_secs(10));
+        assert_eq!(next, start + Duration::from_secs(20));
+    }
+}
@@ -0,0 +1,132 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+/// What inline secret values are shown as
+pub const REDACTED: &str = "***";
+
+/// A configuration value that may be kept out of the settings file.
+///
+/// Written in TOML as a plain string, `{ env = "VAR" }` to read an
+/// environment variable, or `{ file = "/path" }` to read a file. Values are
+/// resolved each time they are used, so rotated secrets are picked up
+/// without a restart.
+#[derive(Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum Secret {
+    Plain(String),
+    Env { env: String },
+    File { file: PathBuf },
+}
+
+impl Secret {
+    /// Return the secret's current value.
+    ///
+    /// Trailing newlines are trimmed from file contents, since most secret
+    /// files are written with one.
+    pub fn resolve(&self) -> Result<String, String> {
+        match self {
+            Secret::Plain(value) => Ok(value.clone()),
+            Secret::Env { env } => std::env::var(env)
+                .map_err(|e| format!("failed to read environment variable {env}: {e}")),
+            Secret::File { file } => fs::read_to_string(file)
+                .map(|contents| contents.trim_end_matches(['\r', '\n']).to_string())
+                .map_err(|e| format!("failed to read secret file {}: {e}", file.display())),
+        }
+    }
+
+    /// The secret with any value written inline masked, for display.
+    /// References to environment variables and files are kept.
+    pub fn redacted(&self) -> Secret {
+        match self {
+            Secret::Plain(_) => Secret::Plain(REDACTED.to_string()),
+            other => other.clone(),
+        }
+    }
+
+    /// Whether this is the masked value [`Secret::redacted`] shows
+    pub fn is_redacted(&self) -> bool {
+        matches!(self, Secret::Plain(value) if value == REDACTED)
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Secret::Plain(_) => f.write_str("Secret(***)"),
+            Secret::Env { env } => write!(f, "Secret(env: {env})"),
+            Secret::File { file } => write!(f, "Secret(file: {})", file.display()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[derive(Deserialize)]
+    struct Wrapper {
+        secret: Secret,
+    }
+
+    fn parse(toml_content: &str) -> Secret {
+        toml::from_str::<Wrapper>(toml_content)
+            .expect("Failed to parse secret")
+            .secret
+    }
+
+    #[test]
+    fn test_plain_secret() {
+        let secret = parse(r#"secret = "hunter2""#);
+
+        assert_eq!(secret, Secret::Plain("hunter2".to_string()));
+        assert_eq!(secret.resolve(), Ok("hunter2".to_string()));
+        assert_eq!(format!("{secret:?}"), "Secret(***)");
+        assert!(!secret.is_redacted());
+        assert!(secret.redacted().is_redacted());
+    }
+
+    #[test]
+    fn test_env_secret() {
+        std::env::set_var("SAMMY_MONITOR_TEST_SECRET", "from-env");
+        let secret = parse(r#"secret = { env = "SAMMY_MONITOR_TEST_SECRET" }"#);
+
+        assert_eq!(secret.resolve(), Ok("from-env".to_string()));
+        assert_eq!(
+            format!("{secret:?}"),
+            "Secret(env: SAMMY_MONITOR_TEST_SECRET)"
+        );
+    }
+
+    #[test]
+    fn test_env_secret_missing() {
+        let secret = parse(r#"secret = { env = "SAMMY_MONITOR_TEST_SECRET_UNSET" }"#);
+
+        let error = secret.resolve().unwrap_err();
+        assert!(error.contains("SAMMY_MONITOR_TEST_SECRET_UNSET"));
+    }
+
+    #[test]
+    fn test_file_secret() {
+        let mut temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        temp_file
+            .write_all(b"from-file\n")
+            .expect("Failed to write to temp file");
+
+        let secret = Secret::File {
+            file: temp_file.path().to_path_buf(),
+        };
+
+        assert_eq!(secret.resolve(), Ok("from-file".to_string()));
+    }
+
+    #[test]
+    fn test_file_secret_missing() {
+        let secret = parse(r#"secret = { file = "/path/that/does/not/exist" }"#);
+
+        assert!(secret.resolve().is_err());
+    }
+}
@@ -0,0 +1,325 @@
+use axum::Router;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto::Builder;
+use hyper_util::service::TowerToHyperService;
+use log::{error, info, warn};
+use openssl::ssl::{Ssl, SslAcceptor, SslFiletype, SslMethod};
+use std::fs;
+use std::io;
+use std::net::SocketAddr;
+use std::os::unix::fs::FileTypeExt;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, UnixListener};
+use tokio_openssl::SslStream;
+
+use crate::settings::{ServerSettings, ServerTlsConfig};
+
+/// Wait after a failed accept, doubled for each failure in a row
+const ACCEPT_BACKOFF_MIN: Duration = Duration::from_millis(5);
+/// Longest wait between accepts that keep failing, e.g. when out of file
+/// descriptors
+const ACCEPT_BACKOFF_MAX: Duration = Duration::from_secs(1);
+
+enum Listener {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
+
+/// HTTP server for the metrics endpoint, bound but not yet serving
+pub struct Server {
+    listener: Listener,
+    acceptor: Option<Arc<SslAcceptor>>,
+}
+
+impl Server {
+    /// Bind the TCP address or Unix socket in `settings`
+    pub async fn bind(settings: &ServerSettings) -> io::Result<Self> {
+        let acceptor = settings
+            .tls
+            .as_ref()
+            .map(tls_acceptor)
+            .transpose()?
+            .map(Arc::new);
+
+        let listener = match &settings.unix_socket {
+            Some(path) => {
+                // A socket left behind by an earlier run would make bind fail
+                if fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_socket()) {
+                    fs::remove_file(path)?;
+                }
+                Listener::Unix(UnixListener::bind(path)?)
+            }
+            None => Listener::Tcp(TcpListener::bind(settings.socket_addr()).await?),
+        };
+
+        Ok(Self { listener, acceptor })
+    }
+
+    /// The bound TCP address, or `None` when listening on a Unix socket
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        match &self.listener {
+            Listener::Tcp(listener) => listener.local_addr().ok(),
+            Listener::Unix(_) => None,
+        }
+    }
+
+    /// Where the server can be reached, for logging
+    pub fn address(&self) -> String {
+        let scheme = if self.acceptor.is_some() {
+            "https"
+        } else {
+            "http"
+        };
+
+        match &self.listener {
+            Listener::Tcp(listener) => match listener.local_addr() {
+                Ok(address) => format!("{scheme}://{address}"),
+                Err(_) => format!("{scheme}://(unknown)"),
+            },
+            Listener::Unix(listener) => match listener.local_addr() {
+                Ok(address) => match address.as_pathname() {
+                    Some(path) => format!("{scheme}+unix://{}", path.display()),
+                    None => format!("{scheme}+unix://(unnamed)"),
+                },
+                Err(_) => format!("{scheme}+unix://(unknown)"),
+            },
+        }
+    }
+
+    /// Serve `app` until the listener fails
+    pub async fn serve(self, app: Router) -> io::Result<()> {
+        info!("Metrics server listening on {}", self.address());
+
+        match (self.listener, self.acceptor) {
+            (Listener::Tcp(listener), None) => axum::serve(listener, app).await,
+            (Listener::Tcp(listener), Some(acceptor)) => {
+                let mut backoff = ACCEPT_BACKOFF_MIN;
+                loop {
+                    match listener.accept().await {
+                        Ok((stream, _)) => {
+                            backoff = ACCEPT_BACKOFF_MIN;
+                            spawn_connection(stream, app.clone(), Some(acceptor.clone()))
+                        }
+                        Err(e) => backoff = back_off(e, backoff).await,
+                    }
+                }
+            }
+            (Listener::Unix(listener), acceptor) => {
+                let mut backoff = ACCEPT_BACKOFF_MIN;
+                loop {
+                    match listener.accept().await {
+                        Ok((stream, _)) => {
+                            backoff = ACCEPT_BACKOFF_MIN;
+                            spawn_connection(stream, app.clone(), acceptor.clone())
+                        }
+                        Err(e) => backoff = back_off(e, backoff).await,
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Wait `backoff` after a failed accept rather than retrying straight away,
+/// returning the wait for the next failure in a row
+async fn back_off(e: io::Error, backoff: Duration) -> Duration {
+    warn!("Failed to accept connection, retrying in {backoff:?}: {e}");
+    tokio::time::sleep(backoff).await;
+    next_backoff(backoff)
+}
+
+fn next_backoff(backoff: Duration) -> Duration {
+    (backoff * 2).min(ACCEPT_BACKOFF_MAX)
+}
+
+fn tls_acceptor(config: &ServerTlsConfig) -> io::Result<SslAcceptor> {
+    let tls_error = |e| io::Error::new(io::ErrorKind::InvalidData, format!("server TLS: {e}"));
+
+    let mut builder = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).map_err(tls_error)?;
+    builder
+        .set_certificate_chain_file(&config.cert)
+        .map_err(tls_error)?;
+    builder
+        .set_private_key_file(&config.key, SslFiletype::PEM)
+        .map_err(tls_error)?;
+    builder.check_private_key().map_err(tls_error)?;
+
+    Ok(builder.build())
+}
+
+/// Serve HTTP on an accepted connection, after a TLS handshake if configured
+fn spawn_connection<S>(stream: S, app: Router, acceptor: Option<Arc<SslAcceptor>>)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        let Some(acceptor) = acceptor else {
+            serve_connection(stream, app).await;
+            return;
+        };
+
+        let stream = Ssl::new(acceptor.context()).and_then(|ssl| SslStream::new(ssl, stream));
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                error!("Failed to set up TLS: {e}");
+                return;
+            }
+        };
+
+        match Pin::new(&mut stream).accept().await {
+            Ok(()) => serve_connection(stream, app).await,
+            Err(e) => warn!("TLS handshake failed: {e}"),
+        }
+    });
+}
+
+async fn serve_connection<S>(stream: S, app: Router)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let service = TowerToHyperService::new(app);
+
+    if let Err(e) = Builder::new(TokioExecutor::new())
+        .serve_connection(TokioIo::new(stream), service)
+        .await
+    {
+        warn!("Error serving connection: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tls::test_support::self_signed;
+    use axum::routing::get;
+    use std::io::Write;
+    use std::net::{IpAddr, Ipv4Addr};
+    use tempfile::{NamedTempFile, TempDir};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::UnixStream;
+
+    fn app() -> Router {
+        Router::new().route("/metrics", get(|| async { "metrics" }))
+    }
+
+    fn localhost() -> ServerSettings {
+        ServerSettings {
+            bind_address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_accept_backoff() {
+        let mut backoff = ACCEPT_BACKOFF_MIN;
+        for _ in 0..20 {
+            let next = next_backoff(backoff);
+            assert!(next >= backoff);
+            backoff = next;
+        }
+        assert_eq!(backoff, ACCEPT_BACKOFF_MAX);
+        assert_eq!(next_backoff(ACCEPT_BACKOFF_MIN), Duration::from_millis(10));
+    }
+
+    #[tokio::test]
+    async fn test_serve_tcp() {
+        let server = Server::bind(&localhost()).await.expect("bind");
+        let address = server.local_addr().expect("TCP address");
+        assert!(server.address().starts_with("http://127.0.0.1:"));
+        tokio::spawn(server.serve(app()));
+
+        let body = reqwest::get(format!("http://{address}/metrics"))
+            .await
+            .expect("request")
+            .text()
+            .await
+            .expect("body");
+
+        assert_eq!(body, "metrics");
+    }
+
+    #[tokio::test]
+    async fn test_serve_tls() {
+        let now = chrono::Utc::now().timestamp();
+        let (cert, key) = self_signed("localhost", now - 86_400, now + 86_400);
+
+        let mut cert_file = NamedTempFile::new().expect("Failed to create temp file");
+        cert_file.write_all(&cert.to_pem().unwrap()).unwrap();
+        let mut key_file = NamedTempFile::new().expect("Failed to create temp file");
+        key_file
+            .write_all(&key.private_key_to_pem_pkcs8().unwrap())
+            .unwrap();
+
+        let settings = ServerSettings {
+            tls: Some(ServerTlsConfig {
+                cert: cert_file.path().to_path_buf(),
+                key: key_file.path().to_path_buf(),
+            }),
+            ..localhost()
+        };
+        let server = Server::bind(&settings).await.expect("bind");
+        let port = server.local_addr().expect("TCP address").port();
+        tokio::spawn(server.serve(app()));
+
+        let client = reqwest::Client::builder()
+            .add_root_certificate(reqwest::Certificate::from_pem(&cert.to_pem().unwrap()).unwrap())
+            .build()
+            .unwrap();
+        let body = client
+            .get(format!("https://localhost:{port}/metrics"))
+            .send()
+            .await
+            .expect("request")
+            .text()
+            .await
+            .expect("body");
+
+        assert_eq!(body, "metrics");
+    }
+
+    #[tokio::test]
+    async fn test_serve_unix_socket() {
+        let dir = TempDir::new().expect("Failed to create temp dir");
+        let path = dir.path().join("sammy_monitor.sock");
+
+        let settings = ServerSettings {
+            unix_socket: Some(path.clone()),
+            ..Default::default()
+        };
+        let server = Server::bind(&settings).await.expect("bind");
+        assert!(server.local_addr().is_none());
+        tokio::spawn(server.serve(app()));
+
+        let mut stream = UnixStream::connect(&path).await.expect("connect");
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+        assert!(response.ends_with("metrics"));
+
+        // A stale socket from an earlier run is replaced
+        assert!(Server::bind(&settings).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_bind_invalid_tls() {
+        let settings = ServerSettings {
+            tls: Some(ServerTlsConfig {
+                cert: "/path/that/does/not/exist.pem".into(),
+                key: "/path/that/does/not/exist.key".into(),
+            }),
+            ..localhost()
+        };
+
+        assert!(Server::bind(&settings).await.is_err());
+    }
+}
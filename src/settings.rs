@@ -1,348 +1,2897 @@
-use serde::Deserialize;
+use crate::assertions::ResponseAssertions;
+use crate::maintenance::Window;
+use crate::secret::Secret;
+use crate::timing::Phase;
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
 use std::io::{Error, ErrorKind};
-use std::path::PathBuf;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::time::Duration;
+use toml_edit::{ArrayOfTables, DocumentMut, Item, Table};
 use uuid::Uuid;
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct MonitorConfig {
     pub id: Uuid,
     pub name: String,
+    /// What the monitor checks, HTTP when omitted
+    #[serde(default, rename = "type", skip_serializing_if = "MonitorType::is_http")]
+    pub kind: MonitorType,
+    /// URL checked by HTTP monitors
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub url: String,
-    pub interval: u64, // in seconds
+    /// Time between checks, written with a unit such as "30s", "5m" or "1h"
+    #[serde(
+        deserialize_with = "crate::duration::deserialize",
+        serialize_with = "crate::duration::serialize"
+    )]
+    pub interval: Duration,
     pub enabled: bool,
+    /// Heading the monitor is listed under on the status page
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// Position on the status page, lower first; unordered monitors come
+    /// last, by name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order: Option<i32>,
+    /// Labels maintenance windows can select the monitor by
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Consecutive failed checks before the monitor is reported down, 1 when
+    /// omitted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure_threshold: Option<u32>,
+    /// Consecutive passing checks before a down monitor is reported up
+    /// again, 1 when omitted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub success_threshold: Option<u32>,
+    /// Times a failed check is tried again before the failure is recorded
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub retries: u32,
+    /// Wait before each retry, 5s when omitted
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "crate::duration::deserialize_option",
+        serialize_with = "crate::duration::serialize_option"
+    )]
+    pub retry_delay: Option<Duration>,
+    /// How the check request is built
+    #[serde(flatten)]
+    pub request: RequestConfig,
+    /// Connection, redirect and TLS options for the HTTP client
+    #[serde(flatten)]
+    pub client: ClientConfig,
+    /// Conditions a response must meet to count as healthy
+    #[serde(
+        default,
+        rename = "assert",
+        skip_serializing_if = "ResponseAssertions::is_empty"
+    )]
+    pub assertions: ResponseAssertions,
+    /// Address and exchange of TCP monitors
+    #[serde(flatten)]
+    pub tcp: TcpConfig,
+    /// Host and echo requests of ping monitors
+    #[serde(flatten)]
+    pub ping: PingConfig,
+    /// Query and expected answer of DNS monitors
+    #[serde(flatten)]
+    pub dns: DnsConfig,
+    /// Leeway given to the pings of heartbeat monitors
+    #[serde(flatten)]
+    pub heartbeat: HeartbeatConfig,
 }
 
-#[derive(Deserialize, Debug, Clone)]
-pub struct Settings {
-    pub monitors: Vec<MonitorConfig>,
-    pub prometheus_url: Option<String>,
+/// Kinds of check a monitor can run
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MonitorType {
+    /// Request a URL and check the response
+    #[default]
+    Http,
+    /// Connect to a host and port, optionally exchanging a banner
+    Tcp,
+    /// Send ICMP echo requests to a host
+    Ping,
+    /// Resolve a DNS record
+    Dns,
+    /// Wait for a job to ping `/heartbeat/{id}`
+    Heartbeat,
 }
 
-impl Settings {
-    pub fn load(path: &PathBuf) -> Result<Settings, Error> {
-        if !path.exists() {
-            return Err(Error::new(
-                ErrorKind::NotFound,
-                format!("Settings file not found: {}", path.display()),
-            ));
+impl MonitorType {
+    fn is_http(&self) -> bool {
+        *self == MonitorType::Http
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            MonitorType::Http => "http",
+            MonitorType::Tcp => "tcp",
+            MonitorType::Ping => "ping",
+            MonitorType::Dns => "dns",
+            MonitorType::Heartbeat => "heartbeat",
         }
+    }
 
-        let config_file_contents = match fs::read_to_string(path) {
-            Ok(content) => content,
-            Err(e) => {
-                return Err(Error::new(
-                    ErrorKind::InvalidData,
-                    format!("Failed to read settings file: {e}"),
-                ));
-            }
+    /// Metrics label holding what monitors of this type check
+    pub fn target_label(self) -> &'static str {
+        match self {
+            MonitorType::Http => "monitor_url",
+            MonitorType::Tcp => "monitor_address",
+            MonitorType::Ping => "monitor_host",
+            MonitorType::Dns => "monitor_query",
+            MonitorType::Heartbeat => "monitor_endpoint",
+        }
+    }
+
+    /// Phases that checks of this type time. The HTTP client doesn't report
+    /// when it has connected, so HTTP checks have no `connect` phase.
+    pub fn phases(self) -> &'static [Phase] {
+        match self {
+            MonitorType::Http => &[Phase::Dns, Phase::Ttfb, Phase::Download],
+            MonitorType::Tcp => &[Phase::Dns, Phase::Connect, Phase::Ttfb],
+            MonitorType::Ping | MonitorType::Dns => &[Phase::Dns],
+            MonitorType::Heartbeat => &[],
+        }
+    }
+}
+
+/// Options of TCP monitors
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct TcpConfig {
+    /// Host and port to connect to, e.g. "db.example.com:5432"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    /// Sent once connected
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub send: Option<String>,
+    /// Text the server must send back for the check to pass
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expect: Option<String>,
+}
+
+/// Options of ping monitors
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct PingConfig {
+    /// Host name or IP address to ping
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    /// Echo requests sent per check, 3 when omitted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<u32>,
+    /// Highest percentage of lost packets a passing check may have; only a
+    /// check with every packet lost fails when omitted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_packet_loss: Option<f64>,
+}
+
+/// Echo requests sent per ping check when a monitor doesn't set `count`
+pub const DEFAULT_PING_COUNT: u32 = 3;
+
+/// Wait for each echo reply when a ping monitor doesn't set `timeout`
+pub const DEFAULT_PING_TIMEOUT: Duration = Duration::from_secs(1);
+
+impl PingConfig {
+    fn is_empty(&self) -> bool {
+        *self == PingConfig::default()
+    }
+
+    /// Echo requests sent per check
+    pub fn count(&self) -> u32 {
+        self.count.unwrap_or(DEFAULT_PING_COUNT)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.host.as_deref().unwrap_or_default().is_empty() {
+            return Err("ping monitors need a host".to_string());
+        }
+
+        if !(1..=100).contains(&self.count()) {
+            return Err("count must be between 1 and 100".to_string());
+        }
+
+        if self
+            .max_packet_loss
+            .is_some_and(|loss| !(0.0..=100.0).contains(&loss))
+        {
+            return Err("max_packet_loss must be a percentage between 0 and 100".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+/// Options of heartbeat monitors
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct HeartbeatConfig {
+    /// How late a ping may be on top of the interval, 1 minute when omitted
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "crate::duration::deserialize_option",
+        serialize_with = "crate::duration::serialize_option"
+    )]
+    pub grace: Option<Duration>,
+}
+
+/// Leeway of heartbeat monitors that don't set `grace`
+pub const DEFAULT_HEARTBEAT_GRACE: Duration = Duration::from_secs(60);
+
+impl HeartbeatConfig {
+    fn is_empty(&self) -> bool {
+        *self == HeartbeatConfig::default()
+    }
+
+    /// How late a ping may be on top of the interval
+    pub fn grace(&self) -> Duration {
+        self.grace.unwrap_or(DEFAULT_HEARTBEAT_GRACE)
+    }
+}
+
+/// Options of DNS monitors
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct DnsConfig {
+    /// Name to resolve, e.g. "example.com"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+    /// Type of record to resolve, A when omitted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub record: Option<DnsRecordType>,
+    /// Address of the name server to ask, e.g. "1.1.1.1" or "10.0.0.2:5353";
+    /// the system's resolvers when omitted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolver: Option<String>,
+    /// Values that must all appear in the answer
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub answers: Vec<String>,
+}
+
+/// Record types DNS monitors can resolve
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum DnsRecordType {
+    #[default]
+    A,
+    Aaaa,
+    Cname,
+    Mx,
+    Txt,
+}
+
+impl DnsConfig {
+    fn is_empty(&self) -> bool {
+        *self == DnsConfig::default()
+    }
+
+    /// Type of record to resolve
+    pub fn record(&self) -> DnsRecordType {
+        self.record.unwrap_or_default()
+    }
+
+    /// The name server to ask, port 53 unless the resolver names another
+    pub fn resolver_address(&self) -> Result<Option<SocketAddr>, String> {
+        let Some(resolver) = &self.resolver else {
+            return Ok(None);
         };
 
-        let settings: Settings = match toml::from_str(config_file_contents.as_str()) {
-            Ok(token) => token,
-            Err(e) => {
-                return Err(Error::new(
-                    ErrorKind::InvalidData,
-                    format!("Failed to parse settings file: {e}"),
+        resolver
+            .parse::<SocketAddr>()
+            .or_else(|_| {
+                resolver
+                    .parse::<IpAddr>()
+                    .map(|ip| SocketAddr::new(ip, 53))
+            })
+            .map(Some)
+            .map_err(|_| {
+                format!("invalid resolver \"{resolver}\": expected an IP address, optionally with a port")
+            })
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.query.as_deref().unwrap_or_default().is_empty() {
+            return Err("dns monitors need a query".to_string());
+        }
+
+        if self.answers.iter().any(String::is_empty) {
+            return Err("answers must not be empty".to_string());
+        }
+
+        self.resolver_address().map(|_| ())
+    }
+}
+
+impl TcpConfig {
+    fn is_empty(&self) -> bool {
+        *self == TcpConfig::default()
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let Some(address) = &self.address else {
+            return Err("tcp monitors need an address".to_string());
+        };
+
+        let port = address
+            .rsplit_once(':')
+            .map(|(host, port)| (host, port.parse::<u16>()));
+        match port {
+            Some((host, Ok(port))) if !host.is_empty() && port > 0 => {}
+            _ => {
+                return Err(format!(
+                "invalid address \"{address}\": expected host:port, e.g. \"db.example.com:5432\""
+            ))
+            }
+        }
+
+        if self.expect.as_deref() == Some("") {
+            return Err("expect must not be empty".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+fn is_zero(value: &u32) -> bool {
+    *value == 0
+}
+
+/// Wait before retrying a failed check when a monitor doesn't set
+/// `retry_delay`
+pub const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+impl MonitorConfig {
+    /// An enabled monitor of `kind` with a new id, checked every `interval`
+    /// and with every other option at its default
+    pub fn new(name: impl Into<String>, kind: MonitorType, interval: Duration) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            name: name.into(),
+            kind,
+            url: String::new(),
+            interval,
+            enabled: true,
+            group: None,
+            order: None,
+            tags: Vec::new(),
+            failure_threshold: None,
+            success_threshold: None,
+            retries: 0,
+            retry_delay: None,
+            request: RequestConfig::default(),
+            client: ClientConfig::default(),
+            assertions: ResponseAssertions::default(),
+            tcp: TcpConfig::default(),
+            ping: PingConfig::default(),
+            dns: DnsConfig::default(),
+            heartbeat: HeartbeatConfig::default(),
+        }
+    }
+
+    /// Consecutive failed checks before the monitor is reported down
+    pub fn failure_threshold(&self) -> u32 {
+        self.failure_threshold.unwrap_or(1)
+    }
+
+    /// Consecutive passing checks before the monitor is reported up again
+    pub fn success_threshold(&self) -> u32 {
+        self.success_threshold.unwrap_or(1)
+    }
+
+    /// Wait before retrying a failed check
+    pub fn retry_delay(&self) -> Duration {
+        self.retry_delay.unwrap_or(DEFAULT_RETRY_DELAY)
+    }
+
+    /// What the monitor checks: its URL, the address, host or query of
+    /// other checks, or the path heartbeat monitors are pinged on
+    pub fn target(&self) -> Cow<'_, str> {
+        match self.kind {
+            MonitorType::Http => Cow::Borrowed(&self.url),
+            MonitorType::Tcp => Cow::Borrowed(self.tcp.address.as_deref().unwrap_or_default()),
+            MonitorType::Ping => Cow::Borrowed(self.ping.host.as_deref().unwrap_or_default()),
+            MonitorType::Dns => Cow::Borrowed(self.dns.query.as_deref().unwrap_or_default()),
+            MonitorType::Heartbeat => Cow::Owned(format!("/heartbeat/{}", self.id)),
+        }
+    }
+
+    /// How long a heartbeat monitor waits for a ping before it's overdue
+    pub fn heartbeat_deadline(&self) -> Duration {
+        self.interval + self.heartbeat.grace()
+    }
+
+    /// Check the options set suit the monitor's type
+    fn validate_type(&self) -> Result<(), String> {
+        let kind = self.kind.name();
+
+        if !self.kind.is_http() {
+            if !self.url.is_empty() {
+                let instead = match self.kind {
+                    MonitorType::Tcp => "take an address instead of a url",
+                    MonitorType::Dns => "take a query instead of a url",
+                    MonitorType::Heartbeat => "are pinged by their job instead of checking a url",
+                    _ => "take a host instead of a url",
+                };
+                return Err(format!("{kind} monitors {instead}"));
+            }
+
+            // Only the timeouts apply to other checks
+            let request = RequestConfig {
+                timeout: None,
+                ..self.request.clone()
+            };
+            let client = ClientConfig {
+                connect_timeout: None,
+                ..self.client.clone()
+            };
+            if request != RequestConfig::default()
+                || client != ClientConfig::default()
+                || !self.assertions.is_empty()
+            {
+                return Err(format!(
+                    "HTTP request, client and assert options don't apply to {kind} monitors"
                 ));
             }
-        };
+        }
 
-        Ok(settings)
+        if self.kind != MonitorType::Tcp && !self.tcp.is_empty() {
+            return Err("address, send and expect only apply to tcp monitors".to_string());
+        }
+
+        if self.kind != MonitorType::Ping && !self.ping.is_empty() {
+            return Err("host, count and max_packet_loss only apply to ping monitors".to_string());
+        }
+
+        if self.kind != MonitorType::Dns && !self.dns.is_empty() {
+            return Err(
+                "query, record, resolver and answers only apply to dns monitors".to_string(),
+            );
+        }
+
+        if self.kind != MonitorType::Heartbeat && !self.heartbeat.is_empty() {
+            return Err("grace only applies to heartbeat monitors".to_string());
+        }
+
+        match self.kind {
+            MonitorType::Http if self.url.is_empty() => Err("url is required".to_string()),
+            MonitorType::Http => Ok(()),
+            MonitorType::Tcp => self.tcp.validate(),
+            MonitorType::Ping | MonitorType::Dns if self.client.connect_timeout.is_some() => {
+                Err(format!("connect_timeout doesn't apply to {kind} monitors"))
+            }
+            MonitorType::Ping => self.ping.validate(),
+            MonitorType::Dns => self.dns.validate(),
+            MonitorType::Heartbeat
+                if self.request.timeout.is_some() || self.client.connect_timeout.is_some() =>
+            {
+                Err("timeout and connect_timeout don't apply to heartbeat monitors".to_string())
+            }
+            MonitorType::Heartbeat if self.retries > 0 => {
+                Err("retries don't apply to heartbeat monitors".to_string())
+            }
+            MonitorType::Heartbeat => Ok(()),
+        }
     }
 
-    pub fn get_prometheus_url(&self) -> String {
-        self.prometheus_url
-            .clone()
-            .unwrap_or_else(|| "http://localhost:9090".to_string())
+    fn validate_thresholds(&self) -> Result<(), String> {
+        if self.failure_threshold == Some(0) || self.success_threshold == Some(0) {
+            return Err(
+                "failure_threshold and success_threshold must be greater than 0".to_string(),
+            );
+        }
+
+        Ok(())
+    }
+
+    fn validate_retries(&self) -> Result<(), String> {
+        if self.retry_delay == Some(Duration::ZERO) {
+            return Err("retry_delay must be greater than 0".to_string());
+        }
+
+        if self.retries == 0 && self.retry_delay.is_some() {
+            return Err("retry_delay has no effect without retries".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct RequestConfig {
+    /// HTTP method, GET when omitted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    /// Extra request headers; values may be secrets
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, Secret>,
+    /// Request body sent as-is
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    /// File whose contents are sent as the request body
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body_file: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth: Option<AuthConfig>,
+    /// Limit on the whole request, 30s when omitted
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "crate::duration::deserialize_option",
+        serialize_with = "crate::duration::serialize_option"
+    )]
+    pub timeout: Option<Duration>,
+    /// Largest response body read for assertions, in bytes; 10 MiB when omitted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_body_size: Option<usize>,
+}
+
+/// Options fixed when an HTTP client is built.
+///
+/// Monitors with equal options share a client, so this doubles as the key
+/// of the client cache.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ClientConfig {
+    /// Limit on establishing a connection
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "crate::duration::deserialize_option",
+        serialize_with = "crate::duration::serialize_option"
+    )]
+    pub connect_timeout: Option<Duration>,
+    /// Whether to follow redirects, true when omitted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub follow_redirects: Option<bool>,
+    /// Maximum redirects to follow, 10 when omitted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_redirects: Option<usize>,
+    #[serde(default, skip_serializing_if = "TlsConfig::is_default")]
+    pub tls: TlsConfig,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    /// Skip certificate validation, for internal hosts with self-signed certificates
+    #[serde(default)]
+    pub accept_invalid_certs: bool,
+    /// PEM file of extra CA certificates to trust
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ca_bundle: Option<PathBuf>,
+    /// PEM client certificate for mutual TLS
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_cert: Option<PathBuf>,
+    /// PEM (PKCS#8) private key for `client_cert`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_key: Option<PathBuf>,
+    /// Mark the monitor degraded once a certificate in the chain expires
+    /// within this long, 14d when omitted
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "crate::duration::deserialize_option",
+        serialize_with = "crate::duration::serialize_option"
+    )]
+    pub cert_expiry_threshold: Option<Duration>,
+}
+
+/// Certificate expiry warning used when a monitor doesn't set
+/// `tls.cert_expiry_threshold`
+pub const DEFAULT_CERT_EXPIRY_THRESHOLD: Duration = Duration::from_secs(14 * 24 * 60 * 60);
+
+impl TlsConfig {
+    fn is_default(&self) -> bool {
+        *self == TlsConfig::default()
+    }
+
+    /// How close to expiry a certificate may get before the monitor is
+    /// marked degraded
+    pub fn cert_expiry_threshold(&self) -> Duration {
+        self.cert_expiry_threshold
+            .unwrap_or(DEFAULT_CERT_EXPIRY_THRESHOLD)
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum AuthConfig {
+    Basic {
+        username: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        password: Option<Secret>,
+    },
+    Bearer {
+        token: Secret,
+    },
+}
+
+/// Request timeout used when a monitor doesn't set `timeout`
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Response body limit used when a monitor doesn't set `max_body_size`
+pub const DEFAULT_MAX_BODY_SIZE: usize = 10 * 1024 * 1024;
+
+impl RequestConfig {
+    /// The HTTP method to use, defaulting to GET
+    pub fn method(&self) -> Result<Method, String> {
+        match &self.method {
+            Some(method) => Method::from_bytes(method.to_ascii_uppercase().as_bytes())
+                .map_err(|_| format!("invalid HTTP method \"{method}\"")),
+            None => Ok(Method::GET),
+        }
+    }
+
+    /// Limit on the whole request
+    pub fn timeout(&self) -> Duration {
+        self.timeout.unwrap_or(DEFAULT_TIMEOUT)
+    }
+
+    /// Largest response body to read, in bytes
+    pub fn max_body_size(&self) -> usize {
+        self.max_body_size.unwrap_or(DEFAULT_MAX_BODY_SIZE)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        self.method()?;
+
+        if self.timeout == Some(Duration::ZERO) {
+            return Err("timeout must be greater than 0".to_string());
+        }
+
+        if self.max_body_size == Some(0) {
+            return Err("max_body_size must be greater than 0".to_string());
+        }
+
+        if self.body.is_some() && self.body_file.is_some() {
+            return Err("body and body_file cannot both be set".to_string());
+        }
+
+        if let Some(path) = &self.body_file {
+            if !path.is_file() {
+                return Err(format!("body_file not found: {}", path.display()));
+            }
+        }
+
+        for (name, value) in &self.headers {
+            value.resolve().map_err(|e| format!("header {name}: {e}"))?;
+        }
+
+        match &self.auth {
+            Some(AuthConfig::Basic {
+                password: Some(password),
+                ..
+            }) => {
+                password
+                    .resolve()
+                    .map_err(|e| format!("auth password: {e}"))?;
+            }
+            Some(AuthConfig::Bearer { token }) => {
+                token.resolve().map_err(|e| format!("auth token: {e}"))?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct WorkerSettings {
+    /// Maximum number of checks in flight at once across all monitors
+    #[serde(default = "default_max_concurrent_checks")]
+    pub max_concurrent_checks: usize,
+    /// Optional cap on checks in flight at once against a single host
+    pub max_concurrent_checks_per_host: Option<usize>,
+}
+
+fn default_max_concurrent_checks() -> usize {
+    16
+}
+
+impl Default for WorkerSettings {
+    fn default() -> Self {
+        Self {
+            max_concurrent_checks: default_max_concurrent_checks(),
+            max_concurrent_checks_per_host: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ServerSettings {
+    /// Address the metrics server listens on
+    #[serde(default = "default_bind_address")]
+    pub bind_address: IpAddr,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    /// Listen on this Unix domain socket instead of TCP
+    pub unix_socket: Option<PathBuf>,
+    /// Serve HTTPS with this certificate and key
+    pub tls: Option<ServerTlsConfig>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ServerTlsConfig {
+    /// PEM certificate chain, leaf first
+    pub cert: PathBuf,
+    /// PEM private key for `cert`
+    pub key: PathBuf,
+}
+
+fn default_bind_address() -> IpAddr {
+    IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+}
+
+fn default_port() -> u16 {
+    3000
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        Self {
+            bind_address: default_bind_address(),
+            port: default_port(),
+            unix_socket: None,
+            tls: None,
+        }
+    }
+}
+
+impl ServerSettings {
+    /// TCP address to listen on when no Unix socket is configured
+    pub fn socket_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.bind_address, self.port)
+    }
+
+    /// Check the server settings, e.g. after command line overrides
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(tls) = &self.tls {
+            for path in [&tls.cert, &tls.key] {
+                if !path.is_file() {
+                    return Err(format!("server TLS file not found: {}", path.display()));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ApiSettings {
+    /// Serve the monitor management API under `/api`
+    #[serde(default)]
+    pub enabled: bool,
+    /// Bearer token API requests must present
+    pub token: Option<Secret>,
+    /// Write monitor changes made through the API back to the settings file
+    #[serde(default)]
+    pub persist: bool,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct StatusPageSettings {
+    /// Serve the public status page at `/status`
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_status_page_title")]
+    pub title: String,
+}
+
+fn default_status_page_title() -> String {
+    "Service Status".to_string()
+}
+
+impl Default for StatusPageSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            title: default_status_page_title(),
+        }
+    }
+}
+
+/// Detection of monitors that keep going up and down
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct FlappingSettings {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Most recent checks of a monitor considered
+    #[serde(default = "default_flapping_window")]
+    pub window: usize,
+    /// Fraction of those checks changing outcome at which a monitor starts
+    /// flapping
+    #[serde(default = "default_flapping_high_threshold")]
+    pub high_threshold: f64,
+    /// Fraction at or below which it stops flapping
+    #[serde(default = "default_flapping_low_threshold")]
+    pub low_threshold: f64,
+}
+
+fn default_flapping_window() -> usize {
+    20
+}
+
+fn default_flapping_high_threshold() -> f64 {
+    0.5
+}
+
+fn default_flapping_low_threshold() -> f64 {
+    0.25
+}
+
+impl Default for FlappingSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window: default_flapping_window(),
+            high_threshold: default_flapping_high_threshold(),
+            low_threshold: default_flapping_low_threshold(),
+        }
+    }
+}
+
+impl FlappingSettings {
+    fn validate(&self) -> Result<(), String> {
+        if self.window < 3 {
+            return Err("flapping.window must be at least 3".to_string());
+        }
+
+        if !(0.0 < self.low_threshold
+            && self.low_threshold < self.high_threshold
+            && self.high_threshold <= 1.0)
+        {
+            return Err(
+                "flapping thresholds must satisfy 0 < low_threshold < high_threshold <= 1"
+                    .to_string(),
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// A period during which monitors aren't expected to be healthy, e.g. for
+/// planned deploys.
+///
+/// A window either runs once from `start` to `end`, or opens every time its
+/// cron `schedule` fires and stays open for `duration`. Times are read in
+/// `timezone`. It covers the monitors listed and those with any of the tags
+/// listed, or every monitor when neither is set.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct MaintenanceWindow {
+    /// Shown in logs
+    pub name: String,
+    /// When a one-off window opens, e.g. "2026-11-01 22:00" or an RFC 3339
+    /// timestamp
+    pub start: Option<String>,
+    /// When a one-off window closes
+    pub end: Option<String>,
+    /// When a recurring window opens, as a five-field cron expression, e.g.
+    /// "0 22 * * sun"
+    pub schedule: Option<String>,
+    /// How long a recurring window stays open
+    #[serde(default, deserialize_with = "crate::duration::deserialize_option")]
+    pub duration: Option<Duration>,
+    /// IANA time zone of `start`, `end` and `schedule`, UTC when omitted
+    pub timezone: Option<String>,
+    #[serde(default)]
+    pub monitors: Vec<Uuid>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub mode: MaintenanceMode,
+}
+
+/// What happens to checks during a maintenance window
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MaintenanceMode {
+    /// Don't run them
+    #[default]
+    Skip,
+    /// Run them, but don't count failures against the monitor
+    Mark,
+}
+
+impl MaintenanceWindow {
+    /// Whether the window covers a monitor
+    pub fn applies_to(&self, monitor: &MonitorConfig) -> bool {
+        if self.monitors.is_empty() && self.tags.is_empty() {
+            return true;
+        }
+
+        self.monitors.contains(&monitor.id) || monitor.tags.iter().any(|t| self.tags.contains(t))
     }
 }
 
-impl FromStr for Settings {
-    type Err = Error;
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct HistorySettings {
+    /// File check results are stored in
+    pub path: PathBuf,
+    /// How long results are kept
+    #[serde(
+        default = "default_history_retention",
+        deserialize_with = "crate::duration::deserialize"
+    )]
+    pub retention: Duration,
+    /// Age after which results are merged into one entry per
+    /// `downsample_interval`
+    #[serde(
+        default = "default_downsample_after",
+        deserialize_with = "crate::duration::deserialize"
+    )]
+    pub downsample_after: Duration,
+    #[serde(
+        default = "default_downsample_interval",
+        deserialize_with = "crate::duration::deserialize"
+    )]
+    pub downsample_interval: Duration,
+}
+
+fn default_history_retention() -> Duration {
+    Duration::from_secs(30 * 86_400)
+}
+
+fn default_downsample_after() -> Duration {
+    Duration::from_secs(86_400)
+}
+
+fn default_downsample_interval() -> Duration {
+    Duration::from_secs(3600)
+}
+
+impl HistorySettings {
+    /// History settings for the file at `path`, with default limits
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            retention: default_history_retention(),
+            downsample_after: default_downsample_after(),
+            downsample_interval: default_downsample_interval(),
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.retention.is_zero() || self.downsample_interval.is_zero() {
+            return Err(
+                "history.retention and history.downsample_interval must be greater than 0"
+                    .to_string(),
+            );
+        }
+
+        if self.downsample_after > self.retention {
+            return Err("history.downsample_after must not exceed history.retention".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct AlertingSettings {
+    /// How long a monitor must have been failing before it's reported down
+    #[serde(default, deserialize_with = "crate::duration::deserialize_option")]
+    pub down_for: Option<Duration>,
+    /// Where down and recovery alerts are sent
+    #[serde(default)]
+    pub notifiers: Vec<NotifierSettings>,
+    /// Attempts after a failed delivery, when the failure may be temporary
+    #[serde(default = "default_delivery_retries")]
+    pub delivery_retries: u32,
+    /// Wait before the first retry, doubled before each one after it
+    #[serde(
+        default = "default_delivery_backoff",
+        deserialize_with = "crate::duration::deserialize"
+    )]
+    pub delivery_backoff: Duration,
+}
+
+fn default_delivery_retries() -> u32 {
+    3
+}
+
+fn default_delivery_backoff() -> Duration {
+    Duration::from_secs(1)
+}
+
+impl Default for AlertingSettings {
+    fn default() -> Self {
+        Self {
+            down_for: None,
+            notifiers: Vec::new(),
+            delivery_retries: default_delivery_retries(),
+            delivery_backoff: default_delivery_backoff(),
+        }
+    }
+}
+
+/// A notifier and the monitors it's sent alerts for
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct NotifierSettings {
+    /// Only alert for these monitors
+    #[serde(default)]
+    pub monitors: Vec<Uuid>,
+    /// Only alert for monitors in these groups
+    #[serde(default)]
+    pub groups: Vec<String>,
+    #[serde(flatten)]
+    pub notifier: NotifierConfig,
+}
+
+impl NotifierSettings {
+    /// Whether alerts for a monitor go to this notifier: all of them unless
+    /// restricted to some monitors or groups
+    pub fn applies_to(&self, monitor_id: Uuid, group: Option<&str>) -> bool {
+        if self.monitors.is_empty() && self.groups.is_empty() {
+            return true;
+        }
+
+        self.monitors.contains(&monitor_id)
+            || group.is_some_and(|group| self.groups.iter().any(|g| g == group))
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum NotifierConfig {
+    Webhook(WebhookConfig),
+    Smtp(SmtpConfig),
+    Slack(ChatConfig),
+    Teams(ChatConfig),
+    Discord(ChatConfig),
+    PagerDuty(PagerDutyConfig),
+    Opsgenie(OpsgenieConfig),
+}
+
+/// Incidents opened and resolved through the PagerDuty Events API v2
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct PagerDutyConfig {
+    /// Integration key of the service incidents are opened on
+    pub routing_key: Secret,
+    /// https://events.pagerduty.com when omitted
+    pub base_url: Option<String>,
+    /// critical, error, warning or info; critical when omitted
+    pub severity: Option<String>,
+    /// Limit on each request, 30s when omitted
+    #[serde(default, deserialize_with = "crate::duration::deserialize_option")]
+    pub timeout: Option<Duration>,
+}
+
+/// Alerts opened and closed through the Opsgenie Alert API
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct OpsgenieConfig {
+    /// Key of an API integration
+    pub api_key: Secret,
+    /// https://api.opsgenie.com when omitted; https://api.eu.opsgenie.com
+    /// for the EU instance
+    pub base_url: Option<String>,
+    /// P1 to P5; P1 when omitted
+    pub priority: Option<String>,
+    /// Limit on each request, 30s when omitted
+    #[serde(default, deserialize_with = "crate::duration::deserialize_option")]
+    pub timeout: Option<Duration>,
+}
+
+/// Alerts posted to a chat service's incoming webhook
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ChatConfig {
+    /// Incoming webhook URL; it grants access to the channel, so it may be
+    /// a secret
+    pub url: Secret,
+    /// Limit on each request, 30s when omitted
+    #[serde(default, deserialize_with = "crate::duration::deserialize_option")]
+    pub timeout: Option<Duration>,
+}
+
+/// Alerts POSTed as JSON to a URL
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Extra request headers; values may be secrets
+    #[serde(default)]
+    pub headers: BTreeMap<String, Secret>,
+    /// Limit on each request, 30s when omitted
+    #[serde(default, deserialize_with = "crate::duration::deserialize_option")]
+    pub timeout: Option<Duration>,
+}
+
+/// Alerts sent by email
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct SmtpConfig {
+    pub host: String,
+    /// 587 when omitted, or 465 with `tls = "tls"`
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub tls: SmtpTls,
+    pub username: Option<String>,
+    pub password: Option<Secret>,
+    pub from: String,
+    pub to: Vec<String>,
+    /// Limit on sending each email, 30s when omitted
+    #[serde(default, deserialize_with = "crate::duration::deserialize_option")]
+    pub timeout: Option<Duration>,
+}
+
+/// How the connection to the SMTP server is secured
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    /// Upgrade a plain connection with STARTTLS
+    #[default]
+    Starttls,
+    /// Connect with TLS from the start
+    Tls,
+    /// No encryption, for local relays only
+    None,
+}
+
+impl SmtpConfig {
+    pub fn port(&self) -> u16 {
+        self.port.unwrap_or(match self.tls {
+            SmtpTls::Tls => 465,
+            SmtpTls::Starttls | SmtpTls::None => 587,
+        })
+    }
+}
+
+const PAGERDUTY_SEVERITIES: [&str; 4] = ["critical", "error", "warning", "info"];
+const OPSGENIE_PRIORITIES: [&str; 5] = ["P1", "P2", "P3", "P4", "P5"];
+
+fn validate_base_url(notifier: &str, base_url: &Option<String>) -> Result<(), String> {
+    if let Some(base_url) = base_url {
+        reqwest::Url::parse(base_url)
+            .map_err(|e| format!("{notifier} base_url {base_url}: {e}"))?;
+    }
+    Ok(())
+}
+
+impl AlertingSettings {
+    fn validate(&self) -> Result<(), String> {
+        if self.delivery_backoff.is_zero() {
+            return Err("alerting.delivery_backoff must be greater than 0".to_string());
+        }
+
+        for notifier in &self.notifiers {
+            match &notifier.notifier {
+                NotifierConfig::Webhook(webhook) => {
+                    reqwest::Url::parse(&webhook.url)
+                        .map_err(|e| format!("webhook url {}: {e}", webhook.url))?;
+                }
+                NotifierConfig::Smtp(smtp) => {
+                    if smtp.to.is_empty() {
+                        return Err("smtp notifier needs at least one recipient in to".to_string());
+                    }
+                    if smtp.password.is_some() && smtp.username.is_none() {
+                        return Err("smtp password has no effect without a username".to_string());
+                    }
+                    for address in std::iter::once(&smtp.from).chain(&smtp.to) {
+                        address
+                            .parse::<lettre::message::Mailbox>()
+                            .map_err(|e| format!("smtp address {address}: {e}"))?;
+                    }
+                }
+                NotifierConfig::Slack(chat)
+                | NotifierConfig::Teams(chat)
+                | NotifierConfig::Discord(chat) => {
+                    let url = chat.url.resolve()?;
+                    reqwest::Url::parse(&url).map_err(|e| format!("chat webhook url: {e}"))?;
+                }
+                NotifierConfig::PagerDuty(pagerduty) => {
+                    validate_base_url("pagerduty", &pagerduty.base_url)?;
+                    if let Some(severity) = &pagerduty.severity {
+                        if !PAGERDUTY_SEVERITIES.contains(&severity.as_str()) {
+                            return Err(format!(
+                                "pagerduty severity must be one of {}, got {severity}",
+                                PAGERDUTY_SEVERITIES.join(", ")
+                            ));
+                        }
+                    }
+                }
+                NotifierConfig::Opsgenie(opsgenie) => {
+                    validate_base_url("opsgenie", &opsgenie.base_url)?;
+                    if let Some(priority) = &opsgenie.priority {
+                        if !OPSGENIE_PRIORITIES.contains(&priority.as_str()) {
+                            return Err(format!(
+                                "opsgenie priority must be one of {}, got {priority}",
+                                OPSGENIE_PRIORITIES.join(", ")
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Settings {
+    pub monitors: Vec<MonitorConfig>,
+    pub prometheus_url: Option<String>,
+    #[serde(default)]
+    pub worker: WorkerSettings,
+    #[serde(default)]
+    pub server: ServerSettings,
+    #[serde(default)]
+    pub api: ApiSettings,
+    /// Store check results locally, disabled unless set
+    pub history: Option<HistorySettings>,
+    #[serde(default)]
+    pub status_page: StatusPageSettings,
+    #[serde(default)]
+    pub alerting: AlertingSettings,
+    #[serde(default)]
+    pub flapping: FlappingSettings,
+    #[serde(default)]
+    pub maintenance: Vec<MaintenanceWindow>,
+}
+
+impl ClientConfig {
+    fn validate(&self) -> Result<(), String> {
+        if self.connect_timeout == Some(Duration::ZERO) {
+            return Err("connect_timeout must be greater than 0".to_string());
+        }
+
+        if self.follow_redirects == Some(false) && self.max_redirects.is_some() {
+            return Err("max_redirects has no effect when follow_redirects is false".to_string());
+        }
+
+        match (&self.tls.client_cert, &self.tls.client_key) {
+            (Some(_), None) | (None, Some(_)) => {
+                return Err("tls.client_cert and tls.client_key must be set together".to_string());
+            }
+            _ => {}
+        }
+
+        for path in [
+            &self.tls.ca_bundle,
+            &self.tls.client_cert,
+            &self.tls.client_key,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if !path.is_file() {
+                return Err(format!("TLS file not found: {}", path.display()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Settings {
+    pub fn load(path: &PathBuf) -> Result<Settings, Error> {
+        if !path.exists() {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("Settings file not found: {}", path.display()),
+            ));
+        }
+
+        let config_file_contents = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Failed to read settings file: {e}"),
+                ));
+            }
+        };
+
+        let settings: Settings = match toml::from_str(config_file_contents.as_str()) {
+            Ok(token) => token,
+            Err(e) => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Failed to parse settings file: {e}"),
+                ));
+            }
+        };
+
+        settings.validate()?;
+
+        Ok(settings)
+    }
+
+    /// Check the settings, e.g. after monitors were changed at runtime
+    pub fn validate(&self) -> Result<(), Error> {
+        let mut ids = HashSet::new();
+
+        for monitor in &self.monitors {
+            if !ids.insert(monitor.id) {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Monitor {}: duplicate id {}", monitor.name, monitor.id),
+                ));
+            }
+
+            if monitor.interval.is_zero() {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Monitor {}: interval must be greater than 0", monitor.name),
+                ));
+            }
+
+            if let Err(e) = monitor.validate_type() {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Monitor {}: {e}", monitor.name),
+                ));
+            }
+
+            if let Err(e) = monitor.validate_thresholds() {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Monitor {}: {e}", monitor.name),
+                ));
+            }
+
+            if let Err(e) = monitor.validate_retries() {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Monitor {}: {e}", monitor.name),
+                ));
+            }
+
+            if let Err(e) = monitor.request.validate() {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Monitor {}: {e}", monitor.name),
+                ));
+            }
+
+            if let Err(e) = monitor.client.validate() {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Monitor {}: {e}", monitor.name),
+                ));
+            }
+
+            if let Err(e) = monitor.assertions.validate() {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Monitor {}: {e}", monitor.name),
+                ));
+            }
+        }
+
+        if self.worker.max_concurrent_checks == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "worker.max_concurrent_checks must be greater than 0",
+            ));
+        }
+
+        if self.worker.max_concurrent_checks_per_host == Some(0) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "worker.max_concurrent_checks_per_host must be greater than 0",
+            ));
+        }
+
+        self.server
+            .validate()
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+
+        if let Some(history) = &self.history {
+            history
+                .validate()
+                .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        }
+
+        self.alerting
+            .validate()
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+
+        self.flapping
+            .validate()
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+
+        for window in &self.maintenance {
+            if let Err(e) = Window::new(window) {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Maintenance window {}: {e}", window.name),
+                ));
+            }
+        }
+
+        match &self.api.token {
+            Some(token) => {
+                token
+                    .resolve()
+                    .map_err(|e| Error::new(ErrorKind::InvalidData, format!("api.token: {e}")))?;
+            }
+            None if self.api.enabled => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    "api.token is required when api.enabled is set",
+                ));
+            }
+            None => {}
+        }
+
+        Ok(())
+    }
+
+    /// Write the monitors back to the settings file at `path`.
+    ///
+    /// Monitors are matched to their `[[monitors]]` tables by id and only
+    /// added, changed or removed ones are rewritten, so comments and the
+    /// rest of the file are kept.
+    pub fn save_monitors(&self, path: &Path) -> Result<(), Error> {
+        let invalid = |e: String| Error::new(ErrorKind::InvalidData, e);
+
+        let mut document: DocumentMut = fs::read_to_string(path)?
+            .parse()
+            .map_err(|e| invalid(format!("Failed to parse settings: {e}")))?;
+
+        let mut existing: HashMap<Uuid, Table> = HashMap::new();
+        if let Some(tables) = document.get("monitors").and_then(Item::as_array_of_tables) {
+            for table in tables {
+                if let Ok(monitor) = toml::from_str::<MonitorConfig>(&table_to_string(table)) {
+                    existing.insert(monitor.id, table.clone());
+                }
+            }
+        }
+
+        let mut tables = ArrayOfTables::new();
+        for monitor in &self.monitors {
+            let unchanged = existing.remove(&monitor.id).filter(|table| {
+                toml::from_str::<MonitorConfig>(&table_to_string(table))
+                    .ok()
+                    .as_ref()
+                    == Some(monitor)
+            });
+
+            match unchanged {
+                Some(table) => tables.push(table),
+                None => {
+                    let contents = toml::to_string(monitor)
+                        .map_err(|e| invalid(format!("Failed to write monitor: {e}")))?;
+                    let table: DocumentMut = contents
+                        .parse()
+                        .map_err(|e| invalid(format!("Failed to write monitor: {e}")))?;
+                    tables.push(table.as_table().clone());
+                }
+            }
+        }
+        document.insert("monitors", Item::ArrayOfTables(tables));
+
+        // Replace the file in one step so a reload never sees it half written
+        let temporary = path.with_extension("toml.tmp");
+        fs::write(&temporary, document.to_string())?;
+        fs::rename(&temporary, path)
+    }
+
+    pub fn get_prometheus_url(&self) -> String {
+        self.prometheus_url
+            .clone()
+            .unwrap_or_else(|| "http://localhost:9090".to_string())
+    }
+}
+
+impl FromStr for Settings {
+    type Err = Error;
+
+    fn from_str(content: &str) -> Result<Settings, Error> {
+        let settings: Settings = match toml::from_str(content) {
+            Ok(settings) => settings,
+            Err(e) => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Failed to parse settings: {e}"),
+                ));
+            }
+        };
+
+        settings.validate()?;
+
+        Ok(settings)
+    }
+}
+
+/// Render a table on its own, to parse it back into a config type
+fn table_to_string(table: &Table) -> String {
+    DocumentMut::from(table.clone()).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assertions::BodyRegex;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_settings_from_str_valid() {
+        let toml_content = r#"
+[[monitors]]
+id = "550e8400-e29b-41d4-a716-446655440001"
+name = "Example Site"
+url = "https://example.com"
+interval = "60s"
+enabled = true
+
+[[monitors]]
+id = "550e8400-e29b-41d4-a716-446655440002"
+name = "Google"
+url = "https://google.com"
+interval = "30s"
+enabled = false
+"#;
+
+        let settings: Settings = toml_content.parse().expect("Failed to parse valid TOML");
+
+        assert_eq!(settings.monitors.len(), 2);
+
+        assert_eq!(settings.monitors[0].name, "Example Site");
+        assert_eq!(settings.monitors[0].url, "https://example.com");
+        assert_eq!(settings.monitors[0].interval, Duration::from_secs(60));
+
+        assert_eq!(settings.monitors[1].name, "Google");
+        assert_eq!(settings.monitors[1].url, "https://google.com");
+        assert_eq!(settings.monitors[1].interval, Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_settings_from_str_invalid() {
+        let invalid_toml = r#"
+[[monitors]]
+name = "Missing URL"
+interval = "60s"
+"#;
+
+        let result: Result<Settings, _> = invalid_toml.parse();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_settings_from_str_empty_monitors() {
+        let toml_content = r#"
+monitors = []
+"#;
+
+        let settings: Settings = toml_content
+            .parse()
+            .expect("Failed to parse empty monitors");
+        assert_eq!(settings.monitors.len(), 0);
+    }
+
+    #[test]
+    fn test_settings_load_file_exists() {
+        let mut temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        let toml_content = r#"
+[[monitors]]
+id = "550e8400-e29b-41d4-a716-446655440003"
+name = "Test Site"
+url = "https://test.com"
+interval = "45s"
+enabled = true
+"#;
+
+        temp_file
+            .write_all(toml_content.as_bytes())
+            .expect("Failed to write to temp file");
+        let temp_path = temp_file.path().to_path_buf();
+
+        let settings = Settings::load(&temp_path).expect("Failed to load settings from file");
+
+        assert_eq!(settings.monitors.len(), 1);
+        assert_eq!(settings.monitors[0].name, "Test Site");
+        assert_eq!(settings.monitors[0].url, "https://test.com");
+        assert_eq!(settings.monitors[0].interval, Duration::from_secs(45));
+    }
+
+    #[test]
+    fn test_settings_load_file_not_found() {
+        let non_existent_path = PathBuf::from("/path/that/does/not/exist/settings.toml");
+        let result = Settings::load(&non_existent_path);
+
+        assert!(result.is_err());
+        let error = result.unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_settings_load_invalid_file_content() {
+        let mut temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        let invalid_content = "this is not valid TOML content";
+
+        temp_file
+            .write_all(invalid_content.as_bytes())
+            .expect("Failed to write to temp file");
+        let temp_path = temp_file.path().to_path_buf();
+
+        let result = Settings::load(&temp_path);
+
+        assert!(result.is_err());
+        let error = result.unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_monitor_config_fields() {
+        let monitor = MonitorConfig {
+            id: uuid::Uuid::parse_str("550e8400-e29b-41d4-a716-446655440004").unwrap(),
+            url: "https://example.org".to_string(),
+            ..MonitorConfig::new("Test Monitor", MonitorType::Http, Duration::from_secs(120))
+        };
+
+        assert_eq!(monitor.name, "Test Monitor");
+        assert_eq!(monitor.url, "https://example.org");
+        assert_eq!(monitor.interval, Duration::from_secs(120));
+        assert!(monitor.enabled);
+    }
+
+    #[test]
+    fn test_monitor_config_enabled_true() {
+        let toml_content = r#"
+[[monitors]]
+id = "550e8400-e29b-41d4-a716-446655440005"
+name = "Enabled Monitor"
+url = "https://enabled.com"
+interval = "60s"
+enabled = true
+"#;
+
+        let settings: Settings = toml_content.parse().expect("Failed to parse TOML");
+        assert_eq!(settings.monitors.len(), 1);
+        assert!(settings.monitors[0].enabled);
+        assert_eq!(settings.monitors[0].name, "Enabled Monitor");
+    }
+
+    #[test]
+    fn test_monitor_config_enabled_false() {
+        let toml_content = r#"
+[[monitors]]
+id = "550e8400-e29b-41d4-a716-446655440006"
+name = "Disabled Monitor"
+url = "https://disabled.com"
+interval = "60s"
+enabled = false
+"#;
+
+        let settings: Settings = toml_content.parse().expect("Failed to parse TOML");
+        assert_eq!(settings.monitors.len(), 1);
+        assert!(!settings.monitors[0].enabled);
+        assert_eq!(settings.monitors[0].name, "Disabled Monitor");
+    }
+
+    #[test]
+    fn test_monitor_config_mixed_enabled_states() {
+        let toml_content = r#"
+[[monitors]]
+id = "550e8400-e29b-41d4-a716-446655440007"
+name = "First Monitor"
+url = "https://first.com"
+interval = "60s"
+enabled = true
+
+[[monitors]]
+id = "550e8400-e29b-41d4-a716-446655440008"
+name = "Second Monitor"
+url = "https://second.com"
+interval = "30s"
+enabled = false
+
+[[monitors]]
+id = "550e8400-e29b-41d4-a716-446655440009"
+name = "Third Monitor"
+url = "https://third.com"
+interval = "45s"
+enabled = true
+"#;
+
+        let settings: Settings = toml_content.parse().expect("Failed to parse TOML");
+        assert_eq!(settings.monitors.len(), 3);
+
+        assert!(settings.monitors[0].enabled);
+        assert_eq!(settings.monitors[0].name, "First Monitor");
+
+        assert!(!settings.monitors[1].enabled);
+        assert_eq!(settings.monitors[1].name, "Second Monitor");
+
+        assert!(settings.monitors[2].enabled);
+        assert_eq!(settings.monitors[2].name, "Third Monitor");
+    }
+
+    #[test]
+    fn test_monitor_config_missing_enabled_field() {
+        let toml_content = r#"
+[[monitors]]
+id = "550e8400-e29b-41d4-a716-44665544000a"
+name = "Missing Enabled Field"
+url = "https://missing.com"
+interval = "60s"
+"#;
+
+        let result: Result<Settings, _> = toml_content.parse();
+        assert!(result.is_err(), "Should fail when enabled field is missing");
+    }
+
+    #[test]
+    fn test_settings_get_enabled_monitors() {
+        let toml_content = r#"
+[[monitors]]
+id = "550e8400-e29b-41d4-a716-44665544000b"
+name = "Active Monitor 1"
+url = "https://active1.com"
+interval = "60s"
+enabled = true
+
+[[monitors]]
+id = "550e8400-e29b-41d4-a716-44665544000c"
+name = "Inactive Monitor"
+url = "https://inactive.com"
+interval = "30s"
+enabled = false
+
+[[monitors]]
+id = "550e8400-e29b-41d4-a716-44665544000d"
+name = "Active Monitor 2"
+url = "https://active2.com"
+interval = "45s"
+enabled = true
+"#;
+
+        let settings: Settings = toml_content.parse().expect("Failed to parse TOML");
+        let enabled_monitors: Vec<&MonitorConfig> = settings
+            .monitors
+            .iter()
+            .filter(|monitor| monitor.enabled)
+            .collect();
+
+        assert_eq!(enabled_monitors.len(), 2);
+        assert_eq!(enabled_monitors[0].name, "Active Monitor 1");
+        assert_eq!(enabled_monitors[1].name, "Active Monitor 2");
+    }
+
+    #[test]
+    fn test_monitor_config_enable_disable() {
+        let mut monitor = MonitorConfig {
+            id: uuid::Uuid::parse_str("550e8400-e29b-41d4-a716-44665544000e").unwrap(),
+            url: "https://toggle.com".to_string(),
+            ..MonitorConfig::new("Toggle Monitor", MonitorType::Http, Duration::from_secs(60))
+        };
+
+        assert!(monitor.enabled);
+
+        // Disable the monitor
+        monitor.enabled = false;
+        assert!(!monitor.enabled);
+
+        // Re-enable the monitor
+        monitor.enabled = true;
+        assert!(monitor.enabled);
+    }
+
+    #[test]
+    fn test_worker_settings_defaults() {
+        let toml_content = r#"
+monitors = []
+"#;
+
+        let settings: Settings = toml_content.parse().expect("Failed to parse TOML");
+        assert_eq!(settings.worker.max_concurrent_checks, 16);
+        assert!(settings.worker.max_concurrent_checks_per_host.is_none());
+    }
+
+    #[test]
+    fn test_worker_settings_concurrency_limits() {
+        let toml_content = r#"
+monitors = []
+
+[worker]
+max_concurrent_checks = 64
+max_concurrent_checks_per_host = 4
+"#;
+
+        let settings: Settings = toml_content.parse().expect("Failed to parse TOML");
+        assert_eq!(settings.worker.max_concurrent_checks, 64);
+        assert_eq!(settings.worker.max_concurrent_checks_per_host, Some(4));
+    }
+
+    #[test]
+    fn test_worker_settings_zero_concurrency_rejected() {
+        let toml_content = r#"
+monitors = []
+
+[worker]
+max_concurrent_checks = 0
+"#;
+
+        let result: Result<Settings, _> = toml_content.parse();
+        assert!(result.is_err());
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_server_settings_defaults() {
+        let settings: Settings = "monitors = []".parse().expect("Failed to parse TOML");
+
+        assert_eq!(settings.server, ServerSettings::default());
+        assert_eq!(
+            settings.server.socket_addr(),
+            "0.0.0.0:3000".parse::<SocketAddr>().unwrap()
+        );
+        assert!(settings.server.unix_socket.is_none());
+        assert!(settings.server.tls.is_none());
+    }
+
+    #[test]
+    fn test_server_settings() {
+        let mut cert = NamedTempFile::new().expect("Failed to create temp file");
+        cert.write_all(b"placeholder")
+            .expect("Failed to write to temp file");
+
+        let toml_content = format!(
+            r#"
+monitors = []
+
+[server]
+bind_address = "127.0.0.1"
+port = 9187
+unix_socket = "/run/sammy_monitor.sock"
+
+[server.tls]
+cert = "{0}"
+key = "{0}"
+"#,
+            cert.path().display()
+        );
+
+        let settings: Settings = toml_content.parse().expect("Failed to parse TOML");
+        assert_eq!(
+            settings.server.socket_addr(),
+            "127.0.0.1:9187".parse::<SocketAddr>().unwrap()
+        );
+        assert_eq!(
+            settings.server.unix_socket,
+            Some(PathBuf::from("/run/sammy_monitor.sock"))
+        );
+        assert_eq!(
+            settings.server.tls.map(|tls| tls.cert),
+            Some(cert.path().to_path_buf())
+        );
+    }
+
+    #[test]
+    fn test_server_settings_invalid() {
+        let bad_address = r#"
+monitors = []
+
+[server]
+bind_address = "localhost"
+"#;
+        assert!(bad_address.parse::<Settings>().is_err());
+
+        let missing_cert = r#"
+monitors = []
+
+[server.tls]
+cert = "/path/that/does/not/exist.pem"
+key = "/path/that/does/not/exist.key"
+"#;
+        assert!(missing_cert.parse::<Settings>().is_err());
+    }
+
+    #[test]
+    fn test_monitor_interval_units() {
+        let toml_content = r#"
+[[monitors]]
+id = "550e8400-e29b-41d4-a716-44665544000f"
+name = "Units Monitor"
+url = "https://units.com"
+interval = "5m"
+enabled = true
+
+[[monitors]]
+id = "550e8400-e29b-41d4-a716-446655440010"
+name = "Hourly Monitor"
+url = "https://hourly.com"
+interval = "1h"
+enabled = true
+"#;
+
+        let settings: Settings = toml_content.parse().expect("Failed to parse TOML");
+        assert_eq!(settings.monitors[0].interval, Duration::from_secs(300));
+        assert_eq!(settings.monitors[1].interval, Duration::from_secs(3600));
+    }
+
+    #[test]
+    fn test_monitor_interval_bare_integer_rejected() {
+        let toml_content = r#"
+[[monitors]]
+id = "550e8400-e29b-41d4-a716-446655440011"
+name = "Legacy Monitor"
+url = "https://legacy.com"
+interval = 5
+enabled = true
+"#;
+
+        let error = toml_content
+            .parse::<Settings>()
+            .expect_err("bare integer intervals should be rejected");
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert!(error.to_string().contains("ambiguous"));
+        assert!(error.to_string().contains("\"5m\""));
+    }
+
+    #[test]
+    fn test_monitor_interval_zero_rejected() {
+        let toml_content = r#"
+[[monitors]]
+id = "550e8400-e29b-41d4-a716-446655440012"
+name = "Zero Monitor"
+url = "https://zero.com"
+interval = "0s"
+enabled = true
+"#;
+
+        let result: Result<Settings, _> = toml_content.parse();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_monitor_assertions() {
+        let toml_content = r#"
+[[monitors]]
+id = "550e8400-e29b-41d4-a716-446655440013"
+name = "Asserted Monitor"
+url = "https://asserted.com/health"
+interval = "1m"
+enabled = true
+
+[monitors.assert]
+status = [200, 301]
+body_contains = ["ok"]
+body_regex = "version: \\d+"
+
+[[monitors.assert.json]]
+path = "$.status"
+equals = "ok"
+
+[[monitors.assert.headers]]
+name = "content-type"
+contains = "json"
+"#;
+
+        let settings: Settings = toml_content.parse().expect("Failed to parse TOML");
+        let assertions = &settings.monitors[0].assertions;
+
+        assert_eq!(assertions.status, vec![200, 301]);
+        assert_eq!(assertions.body_contains, vec!["ok".to_string()]);
+        assert_eq!(
+            assertions.body_regex.as_ref().map(BodyRegex::as_str),
+            Some("version: \\d+")
+        );
+        assert_eq!(assertions.json[0].path, "$.status");
+        assert_eq!(assertions.headers[0].contains.as_deref(), Some("json"));
+    }
+
+    #[test]
+    fn test_monitor_assertions_default_to_none() {
+        let toml_content = r#"
+[[monitors]]
+id = "550e8400-e29b-41d4-a716-446655440014"
+name = "Plain Monitor"
+url = "https://plain.com"
+interval = "1m"
+enabled = true
+"#;
+
+        let settings: Settings = toml_content.parse().expect("Failed to parse TOML");
+        assert_eq!(
+            settings.monitors[0].assertions,
+            ResponseAssertions::default()
+        );
+    }
+
+    #[test]
+    fn test_monitor_assertions_invalid_regex_rejected() {
+        let toml_content = r#"
+[[monitors]]
+id = "550e8400-e29b-41d4-a716-446655440015"
+name = "Bad Regex"
+url = "https://bad-regex.com"
+interval = "1m"
+enabled = true
+
+[monitors.assert]
+body_regex = "(unclosed"
+"#;
+
+        let result: Result<Settings, _> = toml_content.parse();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_monitor_request_options() {
+        std::env::set_var("SAMMY_MONITOR_TEST_API_TOKEN", "token-from-env");
+        let toml_content = r#"
+[[monitors]]
+id = "550e8400-e29b-41d4-a716-446655440016"
+name = "API Health"
+url = "https://api.example.com/health"
+interval = "1m"
+enabled = true
+method = "post"
+body = '{"ping": true}'
+
+[monitors.headers]
+Content-Type = "application/json"
+X-Api-Key = { env = "SAMMY_MONITOR_TEST_API_TOKEN" }
+
+[monitors.auth]
+type = "bearer"
+token = { env = "SAMMY_MONITOR_TEST_API_TOKEN" }
+"#;
+
+        let settings: Settings = toml_content.parse().expect("Failed to parse TOML");
+        let request = &settings.monitors[0].request;
+
+        assert_eq!(request.method(), Ok(Method::POST));
+        assert_eq!(request.body.as_deref(), Some(r#"{"ping": true}"#));
+        assert_eq!(
+            request.headers["Content-Type"],
+            Secret::Plain("application/json".to_string())
+        );
+        assert_eq!(
+            request.headers["X-Api-Key"].resolve(),
+            Ok("token-from-env".to_string())
+        );
+        assert_eq!(
+            request.auth,
+            Some(AuthConfig::Bearer {
+                token: Secret::Env {
+                    env: "SAMMY_MONITOR_TEST_API_TOKEN".to_string()
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_monitor_request_defaults_to_get() {
+        let toml_content = r#"
+[[monitors]]
+id = "550e8400-e29b-41d4-a716-446655440017"
+name = "Plain GET"
+url = "https://get.example.com"
+interval = "1m"
+enabled = true
+"#;
+
+        let settings: Settings = toml_content.parse().expect("Failed to parse TOML");
+        assert_eq!(settings.monitors[0].request, RequestConfig::default());
+        assert_eq!(settings.monitors[0].request.method(), Ok(Method::GET));
+    }
+
+    #[test]
+    fn test_monitor_basic_auth() {
+        let toml_content = r#"
+[[monitors]]
+id = "550e8400-e29b-41d4-a716-446655440018"
+name = "Basic Auth"
+url = "https://basic.example.com"
+interval = "1m"
+enabled = true
+
+[monitors.auth]
+type = "basic"
+username = "monitor"
+password = "s3cret"
+"#;
+
+        let settings: Settings = toml_content.parse().expect("Failed to parse TOML");
+        assert_eq!(
+            settings.monitors[0].request.auth,
+            Some(AuthConfig::Basic {
+                username: "monitor".to_string(),
+                password: Some(Secret::Plain("s3cret".to_string())),
+            })
+        );
+    }
+
+    #[test]
+    fn test_monitor_request_invalid_options_rejected() {
+        let body_and_file = r#"
+[[monitors]]
+id = "550e8400-e29b-41d4-a716-446655440019"
+name = "Both Bodies"
+url = "https://both.example.com"
+interval = "1m"
+enabled = true
+body = "inline"
+body_file = "/tmp/body.json"
+"#;
+        assert!(body_and_file.parse::<Settings>().is_err());
+
+        let missing_secret = r#"
+[[monitors]]
+id = "550e8400-e29b-41d4-a716-44665544001a"
+name = "Missing Secret"
+url = "https://missing.example.com"
+interval = "1m"
+enabled = true
+
+[monitors.auth]
+type = "bearer"
+token = { env = "SAMMY_MONITOR_TEST_UNSET_TOKEN" }
+"#;
+        let error = missing_secret.parse::<Settings>().unwrap_err();
+        assert!(error.to_string().contains("SAMMY_MONITOR_TEST_UNSET_TOKEN"));
+
+        let bad_method = r#"
+[[monitors]]
+id = "550e8400-e29b-41d4-a716-44665544001b"
+name = "Bad Method"
+url = "https://method.example.com"
+interval = "1m"
+enabled = true
+method = "NOT A METHOD"
+"#;
+        assert!(bad_method.parse::<Settings>().is_err());
+    }
+
+    #[test]
+    fn test_monitor_client_options() {
+        let mut ca_bundle = NamedTempFile::new().expect("Failed to create temp file");
+        ca_bundle
+            .write_all(b"placeholder")
+            .expect("Failed to write to temp file");
+
+        let toml_content = format!(
+            r#"
+[[monitors]]
+id = "550e8400-e29b-41d4-a716-44665544001c"
+name = "Internal Service"
+url = "https://internal.example.com"
+interval = "1m"
+enabled = true
+timeout = "10s"
+max_body_size = 65536
+connect_timeout = "2s"
+follow_redirects = true
+max_redirects = 3
+
+[monitors.tls]
+accept_invalid_certs = true
+ca_bundle = "{}"
+cert_expiry_threshold = "30d"
+"#,
+            ca_bundle.path().display()
+        );
 
-    fn from_str(content: &str) -> Result<Settings, Error> {
-        match toml::from_str(content) {
-            Ok(settings) => Ok(settings),
-            Err(e) => Err(Error::new(
-                ErrorKind::InvalidData,
-                format!("Failed to parse settings: {e}"),
-            )),
-        }
-    }
-}
+        let settings: Settings = toml_content.parse().expect("Failed to parse TOML");
+        let monitor = &settings.monitors[0];
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::io::Write;
-    use tempfile::NamedTempFile;
+        assert_eq!(monitor.request.timeout(), Duration::from_secs(10));
+        assert_eq!(monitor.request.max_body_size(), 65536);
+        assert_eq!(monitor.client.connect_timeout, Some(Duration::from_secs(2)));
+        assert_eq!(monitor.client.follow_redirects, Some(true));
+        assert_eq!(monitor.client.max_redirects, Some(3));
+        assert!(monitor.client.tls.accept_invalid_certs);
+        assert_eq!(
+            monitor.client.tls.ca_bundle.as_deref(),
+            Some(ca_bundle.path())
+        );
+        assert_eq!(
+            monitor.client.tls.cert_expiry_threshold(),
+            Duration::from_secs(30 * 24 * 60 * 60)
+        );
+    }
 
     #[test]
-    fn test_settings_from_str_valid() {
+    fn test_monitor_client_options_default() {
         let toml_content = r#"
 [[monitors]]
-id = "550e8400-e29b-41d4-a716-446655440001"
-name = "Example Site"
-url = "https://example.com"
-interval = 60
+id = "550e8400-e29b-41d4-a716-44665544001d"
+name = "Defaults"
+url = "https://defaults.example.com"
+interval = "1m"
 enabled = true
-
-[[monitors]]
-id = "550e8400-e29b-41d4-a716-446655440002"
-name = "Google"
-url = "https://google.com"
-interval = 30
-enabled = false
 "#;
 
-        let settings: Settings = toml_content.parse().expect("Failed to parse valid TOML");
-
-        assert_eq!(settings.monitors.len(), 2);
-
-        assert_eq!(settings.monitors[0].name, "Example Site");
-        assert_eq!(settings.monitors[0].url, "https://example.com");
-        assert_eq!(settings.monitors[0].interval, 60);
+        let settings: Settings = toml_content.parse().expect("Failed to parse TOML");
+        let monitor = &settings.monitors[0];
 
-        assert_eq!(settings.monitors[1].name, "Google");
-        assert_eq!(settings.monitors[1].url, "https://google.com");
-        assert_eq!(settings.monitors[1].interval, 30);
+        assert_eq!(monitor.request.timeout(), DEFAULT_TIMEOUT);
+        assert_eq!(monitor.request.max_body_size(), DEFAULT_MAX_BODY_SIZE);
+        assert_eq!(monitor.client, ClientConfig::default());
+        assert_eq!(
+            monitor.client.tls.cert_expiry_threshold(),
+            DEFAULT_CERT_EXPIRY_THRESHOLD
+        );
     }
 
     #[test]
-    fn test_settings_from_str_invalid() {
-        let invalid_toml = r#"
+    fn test_monitor_client_options_invalid() {
+        let cert_without_key = r#"
 [[monitors]]
-name = "Missing URL"
-interval = 60
+id = "550e8400-e29b-41d4-a716-44665544001e"
+name = "Half mTLS"
+url = "https://mtls.example.com"
+interval = "1m"
+enabled = true
+
+[monitors.tls]
+client_cert = "/etc/ssl/client.pem"
 "#;
+        assert!(cert_without_key.parse::<Settings>().is_err());
 
-        let result: Result<Settings, _> = invalid_toml.parse();
-        assert!(result.is_err());
+        let missing_bundle = r#"
+[[monitors]]
+id = "550e8400-e29b-41d4-a716-44665544001f"
+name = "Missing Bundle"
+url = "https://bundle.example.com"
+interval = "1m"
+enabled = true
+
+[monitors.tls]
+ca_bundle = "/path/that/does/not/exist.pem"
+"#;
+        assert!(missing_bundle.parse::<Settings>().is_err());
+
+        let redirect_conflict = r#"
+[[monitors]]
+id = "550e8400-e29b-41d4-a716-446655440020"
+name = "Redirect Conflict"
+url = "https://redirect.example.com"
+interval = "1m"
+enabled = true
+follow_redirects = false
+max_redirects = 3
+"#;
+        assert!(redirect_conflict.parse::<Settings>().is_err());
     }
 
     #[test]
-    fn test_settings_from_str_empty_monitors() {
+    fn test_duplicate_monitor_ids_rejected() {
         let toml_content = r#"
-monitors = []
+[[monitors]]
+id = "550e8400-e29b-41d4-a716-446655440000"
+name = "First"
+url = "https://example.com"
+interval = "1m"
+enabled = true
+
+[[monitors]]
+id = "550e8400-e29b-41d4-a716-446655440000"
+name = "Second"
+url = "https://example.org"
+interval = "1m"
+enabled = true
 "#;
 
-        let settings: Settings = toml_content
-            .parse()
-            .expect("Failed to parse empty monitors");
-        assert_eq!(settings.monitors.len(), 0);
+        let error = Settings::from_str(toml_content).unwrap_err();
+        assert!(error.to_string().contains("duplicate id"));
     }
 
     #[test]
-    fn test_settings_load_file_exists() {
-        let mut temp_file = NamedTempFile::new().expect("Failed to create temp file");
+    fn test_api_settings() {
         let toml_content = r#"
-[[monitors]]
-id = "550e8400-e29b-41d4-a716-446655440003"
-name = "Test Site"
-url = "https://test.com"
-interval = 45
+monitors = []
+
+[api]
 enabled = true
+token = { env = "SAMMY_MONITOR_TEST_UNSET_API_TOKEN" }
+persist = true
 "#;
 
-        temp_file
-            .write_all(toml_content.as_bytes())
-            .expect("Failed to write to temp file");
-        let temp_path = temp_file.path().to_path_buf();
+        let error = Settings::from_str(toml_content).unwrap_err();
+        assert!(error.to_string().contains("api.token"));
 
-        let settings = Settings::load(&temp_path).expect("Failed to load settings from file");
+        let error = Settings::from_str("monitors = []\n[api]\nenabled = true\n").unwrap_err();
+        assert!(error.to_string().contains("api.token is required"));
 
-        assert_eq!(settings.monitors.len(), 1);
-        assert_eq!(settings.monitors[0].name, "Test Site");
-        assert_eq!(settings.monitors[0].url, "https://test.com");
-        assert_eq!(settings.monitors[0].interval, 45);
+        let settings =
+            Settings::from_str("monitors = []\n[api]\nenabled = true\ntoken = \"s3cret\"\n")
+                .unwrap();
+        assert!(settings.api.enabled);
+        assert!(!settings.api.persist);
+        assert!(settings.api.token.is_some());
     }
 
     #[test]
-    fn test_settings_load_file_not_found() {
-        let non_existent_path = PathBuf::from("/path/that/does/not/exist/settings.toml");
-        let result = Settings::load(&non_existent_path);
+    fn test_history_settings() {
+        let settings = Settings::from_str(
+            "monitors = []\n[history]\npath = \"history.db\"\nretention = \"7d\"\n",
+        )
+        .unwrap();
+        let history = settings.history.unwrap();
+        assert_eq!(history.path, PathBuf::from("history.db"));
+        assert_eq!(history.retention, Duration::from_secs(7 * 86_400));
+        assert_eq!(history.downsample_after, Duration::from_secs(86_400));
+        assert_eq!(history.downsample_interval, Duration::from_secs(3600));
 
-        assert!(result.is_err());
-        let error = result.unwrap_err();
-        assert_eq!(error.kind(), ErrorKind::NotFound);
+        let error = Settings::from_str(
+            "monitors = []\n[history]\npath = \"history.db\"\nretention = \"1h\"\n",
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("downsample_after"));
+
+        assert!(Settings::from_str("monitors = []")
+            .unwrap()
+            .history
+            .is_none());
     }
 
     #[test]
-    fn test_settings_load_invalid_file_content() {
-        let mut temp_file = NamedTempFile::new().expect("Failed to create temp file");
-        let invalid_content = "this is not valid TOML content";
+    fn test_alerting_settings() {
+        let settings = Settings::from_str(
+            r#"
+monitors = []
 
-        temp_file
-            .write_all(invalid_content.as_bytes())
-            .expect("Failed to write to temp file");
-        let temp_path = temp_file.path().to_path_buf();
+[alerting]
+down_for = "5m"
 
-        let result = Settings::load(&temp_path);
+[[alerting.notifiers]]
+type = "webhook"
+url = "https://hooks.example.com/alerts"
+headers = { Authorization = { env = "HOOK_TOKEN" } }
 
-        assert!(result.is_err());
-        let error = result.unwrap_err();
-        assert_eq!(error.kind(), ErrorKind::InvalidData);
+[[alerting.notifiers]]
+type = "smtp"
+host = "smtp.example.com"
+tls = "tls"
+username = "alerts"
+password = { file = "/run/secrets/smtp" }
+from = "monitor@example.com"
+to = ["ops@example.com"]
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(settings.alerting.down_for, Some(Duration::from_secs(300)));
+        assert_eq!(settings.alerting.notifiers.len(), 2);
+        match &settings.alerting.notifiers[1].notifier {
+            NotifierConfig::Smtp(smtp) => {
+                assert_eq!(smtp.tls, SmtpTls::Tls);
+                assert_eq!(smtp.port(), 465);
+            }
+            other => panic!("expected smtp notifier, got {other:?}"),
+        }
+
+        let defaults = Settings::from_str("monitors = []").unwrap().alerting;
+        assert_eq!(defaults.down_for, None);
+        assert!(defaults.notifiers.is_empty());
+
+        let error = Settings::from_str(
+            "monitors = []\n[[alerting.notifiers]]\ntype = \"webhook\"\nurl = \"not a url\"\n",
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("webhook url"));
+
+        let error = Settings::from_str(
+            "monitors = []\n[[alerting.notifiers]]\ntype = \"smtp\"\nhost = \"smtp\"\nfrom = \"a@b\"\nto = []\n",
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("recipient"));
+
+        let error = Settings::from_str(
+            "monitors = []\n[[alerting.notifiers]]\ntype = \"smtp\"\nhost = \"smtp\"\nfrom = \"monitor\"\nto = [\"a@b\"]\n",
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("smtp address monitor"));
     }
 
     #[test]
-    fn test_monitor_config_fields() {
-        let monitor = MonitorConfig {
-            id: uuid::Uuid::parse_str("550e8400-e29b-41d4-a716-446655440004").unwrap(),
-            name: "Test Monitor".to_string(),
-            url: "https://example.org".to_string(),
-            interval: 120,
-            enabled: true,
+    fn test_notifier_routing() {
+        let settings = Settings::from_str(
+            r#"
+monitors = []
+
+[[alerting.notifiers]]
+type = "slack"
+url = "https://hooks.slack.com/services/T000/B000/XXXX"
+
+[[alerting.notifiers]]
+type = "discord"
+url = "https://discord.com/api/webhooks/1/abc"
+groups = ["Public"]
+monitors = ["550e8400-e29b-41d4-a716-446655440000"]
+"#,
+        )
+        .unwrap();
+
+        let monitor_id = Uuid::parse_str("550e8400-e29b-41d4-a716-446655440000").unwrap();
+        let other_id = Uuid::new_v4();
+        let [everything, scoped] = &settings.alerting.notifiers[..] else {
+            panic!("expected two notifiers");
         };
+        assert!(matches!(everything.notifier, NotifierConfig::Slack(_)));
+        assert!(everything.applies_to(other_id, None));
+        assert!(scoped.applies_to(monitor_id, None));
+        assert!(scoped.applies_to(other_id, Some("Public")));
+        assert!(!scoped.applies_to(other_id, Some("Internal")));
+        assert!(!scoped.applies_to(other_id, None));
 
-        assert_eq!(monitor.name, "Test Monitor");
-        assert_eq!(monitor.url, "https://example.org");
-        assert_eq!(monitor.interval, 120);
-        assert!(monitor.enabled);
+        let error = Settings::from_str(
+            "monitors = []\n[[alerting.notifiers]]\ntype = \"teams\"\nurl = \"https://example.com\"\nchannel = \"x\"\n",
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("channel"));
     }
 
     #[test]
-    fn test_monitor_config_enabled_true() {
-        let toml_content = r#"
+    fn test_oncall_notifiers() {
+        let settings = Settings::from_str(
+            r#"
+monitors = []
+
+[alerting]
+delivery_retries = 5
+delivery_backoff = "2s"
+
+[[alerting.notifiers]]
+type = "pagerduty"
+routing_key = { env = "PAGERDUTY_ROUTING_KEY" }
+severity = "error"
+
+[[alerting.notifiers]]
+type = "opsgenie"
+api_key = "opsgenie-key"
+base_url = "http://localhost:8081"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(settings.alerting.delivery_retries, 5);
+        assert_eq!(settings.alerting.delivery_backoff, Duration::from_secs(2));
+        match &settings.alerting.notifiers[0].notifier {
+            NotifierConfig::PagerDuty(pagerduty) => {
+                assert_eq!(pagerduty.severity.as_deref(), Some("error"));
+                assert!(pagerduty.base_url.is_none());
+            }
+            other => panic!("expected pagerduty notifier, got {other:?}"),
+        }
+        assert!(matches!(
+            settings.alerting.notifiers[1].notifier,
+            NotifierConfig::Opsgenie(_)
+        ));
+
+        let defaults = AlertingSettings::default();
+        assert_eq!(defaults.delivery_retries, 3);
+        assert_eq!(defaults.delivery_backoff, Duration::from_secs(1));
+
+        let error = Settings::from_str(
+            "monitors = []\n[[alerting.notifiers]]\ntype = \"pagerduty\"\nrouting_key = \"k\"\nseverity = \"high\"\n",
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("severity"));
+
+        let error = Settings::from_str(
+            "monitors = []\n[[alerting.notifiers]]\ntype = \"opsgenie\"\napi_key = \"k\"\npriority = \"P9\"\n",
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("priority"));
+    }
+
+    #[test]
+    fn test_monitor_retries() {
+        let settings = Settings::from_str(
+            r#"
 [[monitors]]
-id = "550e8400-e29b-41d4-a716-446655440005"
-name = "Enabled Monitor"
-url = "https://enabled.com"
-interval = 60
+id = "550e8400-e29b-41d4-a716-446655440000"
+name = "Retried"
+url = "https://retried.example.com"
+interval = "1m"
 enabled = true
-"#;
+retries = 2
+retry_delay = "10s"
 
-        let settings: Settings = toml_content.parse().expect("Failed to parse TOML");
-        assert_eq!(settings.monitors.len(), 1);
-        assert!(settings.monitors[0].enabled);
-        assert_eq!(settings.monitors[0].name, "Enabled Monitor");
+[[monitors]]
+id = "550e8400-e29b-41d4-a716-446655440001"
+name = "Default"
+url = "https://default.example.com"
+interval = "1m"
+enabled = true
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(settings.monitors[0].retries, 2);
+        assert_eq!(settings.monitors[0].retry_delay(), Duration::from_secs(10));
+        assert_eq!(settings.monitors[1].retries, 0);
+        assert_eq!(settings.monitors[1].retry_delay(), DEFAULT_RETRY_DELAY);
+
+        let monitor = |extra: &str| {
+            format!(
+                "[[monitors]]\nid = \"550e8400-e29b-41d4-a716-446655440000\"\nname = \"M\"\nurl = \"https://m.example.com\"\ninterval = \"1m\"\nenabled = true\n{extra}\n"
+            )
+        };
+        let error = Settings::from_str(&monitor("retry_delay = \"5s\"")).unwrap_err();
+        assert!(error.to_string().contains("no effect without retries"));
+        let error = Settings::from_str(&monitor("retries = 1\nretry_delay = \"0s\"")).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("retry_delay must be greater than 0"));
     }
 
     #[test]
-    fn test_monitor_config_enabled_false() {
-        let toml_content = r#"
+    fn test_status_thresholds_and_flapping() {
+        let settings = Settings::from_str(
+            r#"
+[flapping]
+enabled = true
+window = 10
+
 [[monitors]]
-id = "550e8400-e29b-41d4-a716-446655440006"
-name = "Disabled Monitor"
-url = "https://disabled.com"
-interval = 60
-enabled = false
-"#;
+id = "550e8400-e29b-41d4-a716-446655440000"
+name = "Bouncy"
+url = "https://bouncy.example.com"
+interval = "1m"
+enabled = true
+failure_threshold = 3
+success_threshold = 2
+"#,
+        )
+        .unwrap();
 
-        let settings: Settings = toml_content.parse().expect("Failed to parse TOML");
-        assert_eq!(settings.monitors.len(), 1);
-        assert!(!settings.monitors[0].enabled);
-        assert_eq!(settings.monitors[0].name, "Disabled Monitor");
+        let monitor = &settings.monitors[0];
+        assert_eq!(monitor.failure_threshold(), 3);
+        assert_eq!(monitor.success_threshold(), 2);
+        assert!(settings.flapping.enabled);
+        assert_eq!(settings.flapping.window, 10);
+        assert_eq!(settings.flapping.high_threshold, 0.5);
+
+        let defaults = Settings::from_str("monitors = []").unwrap();
+        assert!(!defaults.flapping.enabled);
+
+        let error =
+            Settings::from_str("monitors = []\n[flapping]\nhigh_threshold = 0.2\n").unwrap_err();
+        assert!(error.to_string().contains("low_threshold < high_threshold"));
+        let error = Settings::from_str("monitors = []\n[flapping]\nwindow = 1\n").unwrap_err();
+        assert!(error.to_string().contains("window"));
     }
 
     #[test]
-    fn test_monitor_config_mixed_enabled_states() {
-        let toml_content = r#"
+    fn test_tcp_monitor() {
+        let settings = Settings::from_str(
+            r#"
 [[monitors]]
-id = "550e8400-e29b-41d4-a716-446655440007"
-name = "First Monitor"
-url = "https://first.com"
-interval = 60
+id = "550e8400-e29b-41d4-a716-446655440000"
+name = "SMTP"
+type = "tcp"
+address = "mail.example.com:25"
+expect = "220 "
+interval = "1m"
 enabled = true
+timeout = "5s"
+"#,
+        )
+        .unwrap();
+
+        let monitor = &settings.monitors[0];
+        assert_eq!(monitor.kind, MonitorType::Tcp);
+        assert_eq!(monitor.target(), "mail.example.com:25");
+        assert_eq!(monitor.tcp.expect.as_deref(), Some("220 "));
+        assert_eq!(monitor.request.timeout(), Duration::from_secs(5));
+
+        let tcp = |options: &str| {
+            Settings::from_str(&format!(
+                "[[monitors]]\nid = \"550e8400-e29b-41d4-a716-446655440000\"\nname = \"TCP\"\ninterval = \"1m\"\nenabled = true\n{options}"
+            ))
+        };
+        let error = |options: &str| tcp(options).unwrap_err().to_string();
+
+        assert!(tcp("type = \"tcp\"\naddress = \"[::1]:22\"").is_ok());
+        assert!(error("type = \"tcp\"").contains("need an address"));
+        assert!(error("type = \"tcp\"\naddress = \"db.example.com\"").contains("host:port"));
+        assert!(
+            error("type = \"tcp\"\naddress = \"db:5432\"\nmethod = \"POST\"")
+                .contains("don't apply to tcp monitors")
+        );
+        assert!(
+            error("type = \"tcp\"\nurl = \"https://db.example.com\"\naddress = \"db:5432\"")
+                .contains("instead of a url")
+        );
+        assert!(
+            error("url = \"https://db.example.com\"\naddress = \"db:5432\"")
+                .contains("only apply to tcp monitors")
+        );
+        assert!(error("").contains("url is required"));
+        assert!(error("type = \"udp\"").contains("unknown variant"));
+    }
 
+    #[test]
+    fn test_ping_monitor() {
+        let settings = Settings::from_str(
+            r#"
 [[monitors]]
-id = "550e8400-e29b-41d4-a716-446655440008"
-name = "Second Monitor"
-url = "https://second.com"
-interval = 30
-enabled = false
+id = "550e8400-e29b-41d4-a716-446655440000"
+name = "Gateway"
+type = "ping"
+host = "192.0.2.1"
+count = 5
+max_packet_loss = 20
+interval = "1m"
+enabled = true
+timeout = "2s"
+"#,
+        )
+        .unwrap();
+
+        let monitor = &settings.monitors[0];
+        assert_eq!(monitor.kind, MonitorType::Ping);
+        assert_eq!(monitor.target(), "192.0.2.1");
+        assert_eq!(monitor.ping.count(), 5);
+        assert_eq!(monitor.ping.max_packet_loss, Some(20.0));
+        assert_eq!(monitor.request.timeout, Some(Duration::from_secs(2)));
 
+        let ping = |options: &str| {
+            Settings::from_str(&format!(
+                "[[monitors]]\nid = \"550e8400-e29b-41d4-a716-446655440000\"\nname = \"Ping\"\ninterval = \"1m\"\nenabled = true\n{options}"
+            ))
+        };
+        let error = |options: &str| ping(options).unwrap_err().to_string();
+
+        let minimal = ping("type = \"ping\"\nhost = \"localhost\"").unwrap();
+        assert_eq!(minimal.monitors[0].ping.count(), DEFAULT_PING_COUNT);
+        assert!(error("type = \"ping\"").contains("need a host"));
+        assert!(error("type = \"ping\"\nhost = \"localhost\"\ncount = 0").contains("count"));
+        assert!(
+            error("type = \"ping\"\nhost = \"localhost\"\nmax_packet_loss = 150")
+                .contains("max_packet_loss")
+        );
+        assert!(
+            error("type = \"ping\"\nhost = \"localhost\"\nconnect_timeout = \"1s\"")
+                .contains("connect_timeout doesn't apply")
+        );
+        assert!(
+            error("type = \"ping\"\nurl = \"https://localhost\"\nhost = \"localhost\"")
+                .contains("take a host instead of a url")
+        );
+        assert!(error("type = \"tcp\"\naddress = \"db:5432\"\ncount = 3")
+            .contains("only apply to ping monitors"));
+    }
+
+    #[test]
+    fn test_dns_monitor() {
+        let settings = Settings::from_str(
+            r#"
 [[monitors]]
-id = "550e8400-e29b-41d4-a716-446655440009"
-name = "Third Monitor"
-url = "https://third.com"
-interval = 45
+id = "550e8400-e29b-41d4-a716-446655440000"
+name = "Mail Exchanger"
+type = "dns"
+query = "example.com"
+record = "MX"
+resolver = "1.1.1.1"
+answers = ["mail.example.com"]
+interval = "5m"
 enabled = true
-"#;
+"#,
+        )
+        .unwrap();
 
-        let settings: Settings = toml_content.parse().expect("Failed to parse TOML");
-        assert_eq!(settings.monitors.len(), 3);
+        let monitor = &settings.monitors[0];
+        assert_eq!(monitor.kind, MonitorType::Dns);
+        assert_eq!(monitor.target(), "example.com");
+        assert_eq!(monitor.dns.record(), DnsRecordType::Mx);
+        assert_eq!(
+            monitor.dns.resolver_address(),
+            Ok(Some("1.1.1.1:53".parse().unwrap()))
+        );
+        assert_eq!(monitor.dns.answers, vec!["mail.example.com"]);
 
-        assert!(settings.monitors[0].enabled);
-        assert_eq!(settings.monitors[0].name, "First Monitor");
+        let dns = |options: &str| {
+            Settings::from_str(&format!(
+                "[[monitors]]\nid = \"550e8400-e29b-41d4-a716-446655440000\"\nname = \"DNS\"\ninterval = \"1m\"\nenabled = true\n{options}"
+            ))
+        };
+        let error = |options: &str| dns(options).unwrap_err().to_string();
 
-        assert!(!settings.monitors[1].enabled);
-        assert_eq!(settings.monitors[1].name, "Second Monitor");
+        let minimal = dns("type = \"dns\"\nquery = \"example.com\"").unwrap();
+        assert_eq!(minimal.monitors[0].dns.record(), DnsRecordType::A);
+        assert_eq!(minimal.monitors[0].dns.resolver_address(), Ok(None));
+        assert_eq!(
+            dns("type = \"dns\"\nquery = \"example.com\"\nresolver = \"[::1]:5353\"")
+                .unwrap()
+                .monitors[0]
+                .dns
+                .resolver_address(),
+            Ok(Some("[::1]:5353".parse().unwrap()))
+        );
+        assert!(error("type = \"dns\"").contains("need a query"));
+        assert!(
+            error("type = \"dns\"\nquery = \"example.com\"\nresolver = \"dns.google\"")
+                .contains("invalid resolver")
+        );
+        assert!(
+            error("type = \"dns\"\nquery = \"example.com\"\nrecord = \"SRV\"")
+                .contains("unknown variant")
+        );
+        assert!(
+            error("type = \"dns\"\nquery = \"example.com\"\nanswers = [\"\"]")
+                .contains("answers must not be empty")
+        );
+        assert!(
+            error("type = \"dns\"\nurl = \"https://example.com\"\nquery = \"example.com\"")
+                .contains("take a query instead of a url")
+        );
+        assert!(
+            error("url = \"https://example.com\"\nquery = \"example.com\"")
+                .contains("only apply to dns monitors")
+        );
+    }
 
-        assert!(settings.monitors[2].enabled);
-        assert_eq!(settings.monitors[2].name, "Third Monitor");
+    #[test]
+    fn test_heartbeat_monitor() {
+        let settings = Settings::from_str(
+            r#"
+[[monitors]]
+id = "550e8400-e29b-41d4-a716-446655440000"
+name = "Nightly Backup"
+type = "heartbeat"
+interval = "1d"
+grace = "30m"
+enabled = true
+"#,
+        )
+        .unwrap();
+
+        let monitor = &settings.monitors[0];
+        assert_eq!(monitor.kind, MonitorType::Heartbeat);
+        assert_eq!(
+            monitor.target(),
+            "/heartbeat/550e8400-e29b-41d4-a716-446655440000"
+        );
+        assert_eq!(monitor.heartbeat.grace(), Duration::from_secs(1800));
+        assert_eq!(monitor.heartbeat_deadline(), Duration::from_secs(88_200));
+
+        let heartbeat = |options: &str| {
+            Settings::from_str(&format!(
+                "[[monitors]]\nid = \"550e8400-e29b-41d4-a716-446655440000\"\nname = \"Job\"\ninterval = \"1h\"\nenabled = true\n{options}"
+            ))
+        };
+        let error = |options: &str| heartbeat(options).unwrap_err().to_string();
+
+        let minimal = heartbeat("type = \"heartbeat\"").unwrap();
+        assert_eq!(
+            minimal.monitors[0].heartbeat.grace(),
+            DEFAULT_HEARTBEAT_GRACE
+        );
+        assert!(error("type = \"heartbeat\"\nurl = \"https://example.com\"")
+            .contains("are pinged by their job instead of checking a url"));
+        assert!(error("type = \"heartbeat\"\ntimeout = \"5s\"")
+            .contains("don't apply to heartbeat monitors"));
+        assert!(error("type = \"heartbeat\"\nretries = 2")
+            .contains("retries don't apply to heartbeat monitors"));
+        assert!(error("url = \"https://example.com\"\ngrace = \"5m\"")
+            .contains("grace only applies to heartbeat monitors"));
     }
 
     #[test]
-    fn test_monitor_config_missing_enabled_field() {
-        let toml_content = r#"
+    fn test_maintenance_windows() {
+        let settings = Settings::from_str(
+            r#"
 [[monitors]]
-id = "550e8400-e29b-41d4-a716-44665544000a"
-name = "Missing Enabled Field"
-url = "https://missing.com"
-interval = 60
-"#;
+id = "550e8400-e29b-41d4-a716-446655440000"
+name = "Database"
+url = "https://db.example.com"
+interval = "1m"
+enabled = true
+tags = ["database"]
 
-        let result: Result<Settings, _> = toml_content.parse();
-        assert!(result.is_err(), "Should fail when enabled field is missing");
+[[maintenance]]
+name = "Weekly patching"
+schedule = "0 3 * * sun"
+duration = "2h"
+timezone = "Europe/London"
+tags = ["database"]
+mode = "mark"
+
+[[maintenance]]
+name = "Migration"
+start = "2026-11-01 22:00"
+end = "2026-11-01 23:30"
+monitors = ["550e8400-e29b-41d4-a716-446655440000"]
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(settings.monitors[0].tags, vec!["database"]);
+        let [patching, migration] = &settings.maintenance[..] else {
+            panic!("expected two windows");
+        };
+        assert_eq!(patching.mode, MaintenanceMode::Mark);
+        assert_eq!(patching.duration, Some(Duration::from_secs(7200)));
+        assert_eq!(migration.mode, MaintenanceMode::Skip);
+        assert!(patching.applies_to(&settings.monitors[0]));
+        assert!(migration.applies_to(&settings.monitors[0]));
+
+        let error = Settings::from_str(
+            "monitors = []\n[[maintenance]]\nname = \"Nightly\"\nschedule = \"0 3 * *\"\nduration = \"1h\"\n",
+        )
+        .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("Maintenance window Nightly: invalid schedule"));
     }
 
     #[test]
-    fn test_settings_get_enabled_monitors() {
-        let toml_content = r#"
+    fn test_save_monitors() {
+        let toml_content = r#"# Top comment
+prometheus_url = "http://prometheus:9090"
+
+# Kept as written
 [[monitors]]
-id = "550e8400-e29b-41d4-a716-44665544000b"
-name = "Active Monitor 1"
-url = "https://active1.com"
-interval = 60
+id = "550e8400-e29b-41d4-a716-446655440000"
+name = "Kept"
+url = "https://kept.example.com"
+interval = "5m" # every five minutes
 enabled = true
 
 [[monitors]]
-id = "550e8400-e29b-41d4-a716-44665544000c"
-name = "Inactive Monitor"
-url = "https://inactive.com"
-interval = 30
-enabled = false
+id = "550e8400-e29b-41d4-a716-446655440001"
+name = "Changed"
+url = "https://changed.example.com"
+interval = "1m"
+enabled = true
 
 [[monitors]]
-id = "550e8400-e29b-41d4-a716-44665544000d"
-name = "Active Monitor 2"
-url = "https://active2.com"
-interval = 45
+id = "550e8400-e29b-41d4-a716-446655440002"
+name = "Removed"
+url = "https://removed.example.com"
+interval = "1m"
 enabled = true
+
+[worker]
+max_concurrent_checks = 4
 "#;
 
-        let settings: Settings = toml_content.parse().expect("Failed to parse TOML");
-        let enabled_monitors: Vec<&MonitorConfig> = settings
-            .monitors
-            .iter()
-            .filter(|monitor| monitor.enabled)
-            .collect();
+        let mut file = NamedTempFile::new().expect("Failed to create temp file");
+        write!(file, "{toml_content}").expect("Failed to write to temp file");
 
-        assert_eq!(enabled_monitors.len(), 2);
-        assert_eq!(enabled_monitors[0].name, "Active Monitor 1");
-        assert_eq!(enabled_monitors[1].name, "Active Monitor 2");
-    }
+        let mut settings = Settings::from_str(toml_content).unwrap();
+        settings.monitors[1].enabled = false;
+        settings.monitors.remove(2);
 
-    #[test]
-    fn test_monitor_config_enable_disable() {
-        let mut monitor = MonitorConfig {
-            id: uuid::Uuid::parse_str("550e8400-e29b-41d4-a716-44665544000e").unwrap(),
-            name: "Toggle Monitor".to_string(),
-            url: "https://toggle.com".to_string(),
-            interval: 60,
-            enabled: true,
-        };
+        let mut added = settings.monitors[0].clone();
+        added.id = Uuid::new_v4();
+        added.name = "Added".to_string();
+        added.request.timeout = Some(Duration::from_secs(10));
+        added.request.headers.insert(
+            "X-Token".to_string(),
+            Secret::Env {
+                env: "PATH".to_string(),
+            },
+        );
+        added.assertions.status = vec![200, 204];
+        settings.monitors.push(added);
 
-        assert!(monitor.enabled);
+        settings.save_monitors(file.path()).unwrap();
 
-        // Disable the monitor
-        monitor.enabled = false;
-        assert!(!monitor.enabled);
+        let contents = fs::read_to_string(file.path()).unwrap();
+        assert!(contents.starts_with("# Top comment\n"));
+        assert!(contents.contains("interval = \"5m\" # every five minutes"));
+        assert!(!contents.contains("Removed"));
+        assert!(contents.contains("max_concurrent_checks = 4"));
 
-        // Re-enable the monitor
-        monitor.enabled = true;
-        assert!(monitor.enabled);
+        let saved = Settings::load(&file.path().to_path_buf()).unwrap();
+        assert_eq!(saved.monitors, settings.monitors);
+        assert_eq!(saved.worker.max_concurrent_checks, 4);
     }
 }
@@ -0,0 +1,150 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use uuid::Uuid;
+
+use crate::history::HistoryEntry;
+use crate::transitions::Status;
+use crate::worker::MonitorResult;
+
+/// Outcome of a monitor's most recent check
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MonitorState {
+    /// Reported status, which may lag the latest check when the monitor
+    /// has failure or success thresholds
+    pub up: bool,
+    /// Going up and down too often for `up` to mean much
+    pub flapping: bool,
+    /// Up, but close to failing, e.g. a certificate about to expire
+    pub degraded: bool,
+    /// The latest check ran during a maintenance window
+    pub in_maintenance: bool,
+    pub last_check: DateTime<Utc>,
+    pub response_time_ms: u64,
+    pub status_code: Option<u16>,
+    /// Why the check failed, as the `error_type` metrics label
+    pub error_type: Option<String>,
+    pub error: Option<String>,
+}
+
+impl MonitorState {
+    /// State after `result`, with the monitor's status following it
+    pub fn new(result: &MonitorResult, status: Status) -> Self {
+        Self {
+            up: status.up,
+            flapping: status.flapping,
+            degraded: result.degraded,
+            in_maintenance: result.in_maintenance,
+            last_check: result.timestamp,
+            response_time_ms: result.response_time_ms,
+            status_code: result.status_code,
+            error_type: result.error.as_ref().map(|e| e.error_type().to_string()),
+            error: result.error.as_ref().map(|e| e.to_string()),
+        }
+    }
+}
+
+impl From<&HistoryEntry> for MonitorState {
+    fn from(entry: &HistoryEntry) -> Self {
+        Self {
+            up: entry.up,
+            flapping: false,
+            degraded: entry.degraded,
+            in_maintenance: false,
+            last_check: entry.timestamp,
+            response_time_ms: entry.response_time_ms,
+            status_code: entry.status_code,
+            error_type: entry.error_type.clone(),
+            error: entry.error.clone(),
+        }
+    }
+}
+
+/// Latest check outcome of every monitor, shared between the worker and
+/// anything reporting on it
+#[derive(Debug, Default)]
+pub struct MonitorStates {
+    states: RwLock<HashMap<Uuid, MonitorState>>,
+}
+
+impl MonitorStates {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the outcome of a finished check and the status it left the
+    /// monitor in
+    pub fn record(&self, result: &MonitorResult, status: Status) {
+        self.states
+            .write()
+            .unwrap()
+            .insert(result.monitor_id, MonitorState::new(result, status));
+    }
+
+    /// Set a monitor's state from before a restart, unless it was checked
+    /// since
+    pub fn restore(&self, monitor_id: Uuid, state: MonitorState) {
+        self.states
+            .write()
+            .unwrap()
+            .entry(monitor_id)
+            .or_insert(state);
+    }
+
+    /// The latest state of a monitor, `None` until its first check finishes
+    pub fn get(&self, monitor_id: Uuid) -> Option<MonitorState> {
+        self.states.read().unwrap().get(&monitor_id).cloned()
+    }
+
+    /// Forget a removed monitor
+    pub fn remove(&self, monitor_id: Uuid) {
+        self.states.write().unwrap().remove(&monitor_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::CheckError;
+
+    fn result(monitor_id: Uuid, error: Option<CheckError>) -> MonitorResult {
+        MonitorResult {
+            response_time_ms: 120,
+            status_code: Some(503),
+            ..MonitorResult::new(monitor_id, error)
+        }
+    }
+
+    #[test]
+    fn test_record_and_remove() {
+        let states = MonitorStates::new();
+        let monitor_id = Uuid::new_v4();
+        assert!(states.get(monitor_id).is_none());
+
+        let passed = result(monitor_id, None);
+        states.record(&passed, Status::of(&passed));
+        assert!(states.get(monitor_id).unwrap().up);
+
+        let failed = result(monitor_id, Some(CheckError::HttpStatus(503)));
+        states.record(&failed, Status::of(&failed));
+        let state = states.get(monitor_id).unwrap();
+        assert!(!state.up);
+        assert_eq!(state.error_type.as_deref(), Some("http_error"));
+        assert_eq!(state.error.as_deref(), Some("HTTP 503"));
+
+        // The status can lag the check
+        let status = Status {
+            up: true,
+            flapping: true,
+        };
+        states.record(&failed, status);
+        let state = states.get(monitor_id).unwrap();
+        assert!(state.up);
+        assert!(state.flapping);
+        assert_eq!(state.error_type.as_deref(), Some("http_error"));
+
+        states.remove(monitor_id);
+        assert!(states.get(monitor_id).is_none());
+    }
+}
@@ -0,0 +1,500 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::Html;
+use axum::routing::get;
+use axum::Router;
+use chrono::{DateTime, Duration, Utc};
+use std::fmt::Write;
+use std::sync::Arc;
+use tokio::sync::watch;
+
+use crate::history::{HistoryEntry, HistoryStore};
+use crate::settings::{MonitorConfig, Settings};
+use crate::state::MonitorStates;
+
+/// Days of history shown as bars under each monitor
+const BAR_DAYS: i64 = 30;
+
+/// Number of incidents listed on the page
+const RECENT_INCIDENTS: usize = 10;
+
+/// Public HTML page with the state, uptime and recent incidents of every
+/// monitor.
+///
+/// Rendered on the server from the current settings, the latest check
+/// outcomes and, when enabled, the check history. Monitor URLs are left out
+/// since the page is meant to be shared.
+pub struct StatusPage {
+    settings: watch::Receiver<Settings>,
+    states: Arc<MonitorStates>,
+    history: Option<Arc<HistoryStore>>,
+}
+
+/// A run of failed checks of one monitor
+#[derive(Debug, PartialEq)]
+struct Incident {
+    monitor: String,
+    start: DateTime<Utc>,
+    /// When checks succeeded again, `None` while ongoing
+    end: Option<DateTime<Utc>>,
+    error: Option<String>,
+}
+
+impl StatusPage {
+    pub fn new(settings: watch::Receiver<Settings>, states: Arc<MonitorStates>) -> Self {
+        Self {
+            settings,
+            states,
+            history: None,
+        }
+    }
+
+    /// Show uptime and incidents from `history`
+    pub fn with_history(mut self, history: Arc<HistoryStore>) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    /// Routes of the status page, to be merged into the server's router
+    pub fn router(self) -> Router {
+        Router::new()
+            .route("/status", get(status_page))
+            .with_state(Arc::new(self))
+    }
+
+    fn render(&self, now: DateTime<Utc>) -> String {
+        let settings = self.settings.borrow().clone();
+        let title = escape(&settings.status_page.title);
+
+        let mut html = String::new();
+        let _ = write!(
+            html,
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+             <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+             <meta http-equiv=\"refresh\" content=\"60\">\n<title>{title}</title>\n\
+             <style>{STYLE}</style>\n</head>\n<body>\n<main>\n<h1>{title}</h1>\n"
+        );
+
+        let monitors = ordered(&settings.monitors);
+        let down = monitors
+            .iter()
+            .filter(|monitor| monitor.enabled)
+            .filter(|monitor| self.states.get(monitor.id).is_some_and(|state| !state.up))
+            .count();
+        let _ = match down {
+            0 => writeln!(html, "<p class=\"summary up\">All systems operational</p>"),
+            1 => writeln!(html, "<p class=\"summary down\">1 service is down</p>"),
+            n => writeln!(html, "<p class=\"summary down\">{n} services are down</p>"),
+        };
+
+        let mut incidents = Vec::new();
+        let mut current_group = None;
+        for monitor in &monitors {
+            if current_group != Some(&monitor.group) {
+                if current_group.is_some() {
+                    html.push_str("</section>\n");
+                }
+                html.push_str("<section>\n");
+                if let Some(group) = &monitor.group {
+                    let _ = writeln!(html, "<h2>{}</h2>", escape(group));
+                }
+                current_group = Some(&monitor.group);
+            }
+
+            let entries = self
+                .history
+                .as_ref()
+                .map(|history| history.between(monitor.id, now - Duration::days(BAR_DAYS), now))
+                .unwrap_or_default();
+            self.render_monitor(&mut html, monitor, &entries, now);
+            incidents.extend(find_incidents(&monitor.name, &entries));
+        }
+        if current_group.is_some() {
+            html.push_str("</section>\n");
+        }
+
+        if self.history.is_some() {
+            render_incidents(&mut html, incidents);
+        }
+
+        let _ = write!(
+            html,
+            "<footer>Updated {}</footer>\n</main>\n</body>\n</html>\n",
+            now.format("%Y-%m-%d %H:%M UTC")
+        );
+        html
+    }
+
+    fn render_monitor(
+        &self,
+        html: &mut String,
+        monitor: &MonitorConfig,
+        entries: &[HistoryEntry],
+        now: DateTime<Utc>,
+    ) {
+        let (class, label) = match self.states.get(monitor.id) {
+            _ if !monitor.enabled => ("paused", "Paused"),
+            None => ("unknown", "Pending"),
+            Some(state) if !state.up => ("down", "Down"),
+            Some(state) if state.degraded => ("degraded", "Degraded"),
+            Some(_) => ("up", "Up"),
+        };
+
+        let _ = write!(
+            html,
+            "<div class=\"monitor\">\n<div class=\"row\"><span class=\"name\">{}</span>\
+             <span class=\"state {class}\">{label}</span></div>\n",
+            escape(&monitor.name)
+        );
+
+        if self.history.is_some() {
+            html.push_str("<div class=\"uptime\">");
+            for (window, days) in [("24h", 1), ("7d", 7), ("30d", 30)] {
+                let uptime = uptime(entries, now - Duration::days(days), now);
+                let _ = write!(html, "<span>{window} {}</span>", format_uptime(uptime));
+            }
+            html.push_str("</div>\n<div class=\"bars\">");
+
+            for day in (0..BAR_DAYS).rev() {
+                let end = now - Duration::days(day);
+                let start = end - Duration::days(1);
+                let uptime = uptime(entries, start, end);
+                let class = match uptime {
+                    None => "none",
+                    Some(uptime) if uptime >= 100.0 => "up",
+                    Some(uptime) if uptime > 0.0 => "partial",
+                    Some(_) => "down",
+                };
+                let _ = write!(
+                    html,
+                    "<span class=\"{class}\" title=\"{}: {}\"></span>",
+                    start.format("%Y-%m-%d"),
+                    format_uptime(uptime)
+                );
+            }
+            html.push_str("</div>\n");
+        }
+
+        html.push_str("</div>\n");
+    }
+}
+
+async fn status_page(State(page): State<Arc<StatusPage>>) -> Result<Html<String>, StatusCode> {
+    // Rendering queries the history, which waits on disk
+    tokio::task::spawn_blocking(move || page.render(Utc::now()))
+        .await
+        .map(Html)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+fn render_incidents(html: &mut String, mut incidents: Vec<Incident>) {
+    html.push_str("<section>\n<h2>Recent incidents</h2>\n");
+    if incidents.is_empty() {
+        html.push_str("<p class=\"empty\">No incidents in the last 30 days</p>\n");
+    }
+
+    incidents.sort_by_key(|incident| std::cmp::Reverse(incident.start));
+    for incident in incidents.iter().take(RECENT_INCIDENTS) {
+        let period = match incident.end {
+            Some(end) => format!(
+                "{} to {}",
+                incident.start.format("%Y-%m-%d %H:%M"),
+                end.format("%Y-%m-%d %H:%M UTC")
+            ),
+            None => format!(
+                "Since {}, ongoing",
+                incident.start.format("%Y-%m-%d %H:%M UTC")
+            ),
+        };
+        let _ = write!(
+            html,
+            "<div class=\"incident\"><span class=\"name\">{}</span> <span class=\"period\">{period}</span>",
+            escape(&incident.monitor)
+        );
+        if let Some(error) = &incident.error {
+            let _ = write!(html, "<div class=\"error\">{}</div>", escape(error));
+        }
+        html.push_str("</div>\n");
+    }
+    html.push_str("</section>\n");
+}
+
+/// Monitors in page order: by `order`, then unordered ones by name, with
+/// each group listed where its first monitor falls
+fn ordered(monitors: &[MonitorConfig]) -> Vec<&MonitorConfig> {
+    let mut ordered: Vec<&MonitorConfig> = monitors.iter().collect();
+    ordered.sort_by(|a, b| {
+        (a.order.is_none(), a.order, &a.name).cmp(&(b.order.is_none(), b.order, &b.name))
+    });
+
+    let mut groups: Vec<&Option<String>> = Vec::new();
+    for monitor in &ordered {
+        if !groups.contains(&&monitor.group) {
+            groups.push(&monitor.group);
+        }
+    }
+    // Stable, so monitors keep their order within a group
+    ordered.sort_by_key(|monitor| groups.iter().position(|group| **group == monitor.group));
+    ordered
+}
+
+/// Percentage of successful checks from `start` up to `end`, `None`
+/// without any checks
+fn uptime(entries: &[HistoryEntry], start: DateTime<Utc>, end: DateTime<Utc>) -> Option<f64> {
+    let (checks, successes) = entries
+        .iter()
+        .filter(|entry| entry.timestamp >= start && entry.timestamp < end)
+        .fold((0u64, 0u64), |(checks, successes), entry| {
+            (
+                checks + u64::from(entry.checks),
+                successes + u64::from(entry.successes),
+            )
+        });
+
+    (checks > 0).then(|| successes as f64 * 100.0 / checks as f64)
+}
+
+fn format_uptime(uptime: Option<f64>) -> String {
+    match uptime {
+        Some(uptime) => format!("{uptime:.2}%"),
+        None => "no data".to_string(),
+    }
+}
+
+/// Runs of entries with failed checks, oldest first
+fn find_incidents(monitor: &str, entries: &[HistoryEntry]) -> Vec<Incident> {
+    let mut incidents = Vec::new();
+    let mut open: Option<Incident> = None;
+
+    for entry in entries {
+        let failed = entry.successes < entry.checks;
+        match (&mut open, failed) {
+            (None, true) => {
+                open = Some(Incident {
+                    monitor: monitor.to_string(),
+                    start: entry.timestamp,
+                    end: None,
+                    error: entry.error.clone(),
+                })
+            }
+            (Some(_), false) => {
+                let mut incident = open.take().unwrap();
+                incident.end = Some(entry.timestamp);
+                incidents.push(incident);
+            }
+            _ => {}
+        }
+    }
+
+    incidents.extend(open);
+    incidents
+}
+
+/// Escape text for use in HTML content and attribute values
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+const STYLE: &str = "\
+body{margin:0;background:#f5f6f8;color:#1f2328;font:15px/1.5 system-ui,sans-serif}\
+main{max-width:860px;margin:0 auto;padding:24px 16px}\
+h1{font-size:26px;margin:0 0 16px}h2{font-size:18px;margin:24px 0 8px}\
+.summary{padding:12px 16px;border-radius:6px;color:#fff;font-weight:600}\
+.summary.up{background:#1a7f37}.summary.down{background:#cf222e}\
+.monitor,.incident{background:#fff;border:1px solid #d0d7de;border-radius:6px;padding:12px 16px;margin:8px 0}\
+.row{display:flex;justify-content:space-between}.name{font-weight:600}\
+.state.up{color:#1a7f37}.state.down{color:#cf222e}.state.degraded{color:#9a6700}\
+.state.paused,.state.unknown,.empty,footer,.period,.uptime{color:#656d76}\
+.uptime{font-size:13px;display:flex;gap:16px}\
+.bars{display:flex;gap:2px;margin-top:6px}.bars span{flex:1;height:24px;border-radius:2px}\
+.bars .up{background:#2da44e}.bars .partial{background:#d4a72c}.bars .down{background:#cf222e}.bars .none{background:#d0d7de}\
+.error{font-size:13px;color:#cf222e}footer{margin-top:24px;font-size:13px}";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::CheckError;
+    use crate::settings::HistorySettings;
+    use crate::transitions::Status;
+    use crate::worker::MonitorResult;
+    use std::str::FromStr;
+    use tempfile::TempDir;
+
+    const SETTINGS: &str = r#"
+[status_page]
+enabled = true
+title = "Example & Co Status"
+
+[[monitors]]
+id = "550e8400-e29b-41d4-a716-446655440000"
+name = "Website"
+url = "https://internal.example.com/health"
+interval = "1m"
+enabled = true
+group = "Public"
+order = 2
+
+[[monitors]]
+id = "550e8400-e29b-41d4-a716-446655440001"
+name = "<API>"
+url = "https://api.example.com"
+interval = "1m"
+enabled = true
+group = "Public"
+order = 1
+
+[[monitors]]
+id = "550e8400-e29b-41d4-a716-446655440002"
+name = "Database"
+url = "https://db.example.com"
+interval = "1m"
+enabled = true
+group = "Internal"
+"#;
+
+    fn result(
+        monitor_id: &str,
+        timestamp: DateTime<Utc>,
+        error: Option<CheckError>,
+    ) -> MonitorResult {
+        MonitorResult {
+            status_code: Some(200),
+            timestamp,
+            ..MonitorResult::new(monitor_id.parse().unwrap(), error)
+        }
+    }
+
+    fn page(history: Option<Arc<HistoryStore>>) -> (StatusPage, Arc<MonitorStates>) {
+        let (_, settings) = watch::channel(Settings::from_str(SETTINGS).unwrap());
+        let states = Arc::new(MonitorStates::new());
+        let page = StatusPage::new(settings, states.clone());
+        let page = match history {
+            Some(history) => page.with_history(history),
+            None => page,
+        };
+        (page, states)
+    }
+
+    #[test]
+    fn test_ordered_by_group_and_order() {
+        let settings = Settings::from_str(SETTINGS).unwrap();
+        let names: Vec<&str> = ordered(&settings.monitors)
+            .iter()
+            .map(|monitor| monitor.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["<API>", "Website", "Database"]);
+    }
+
+    #[test]
+    fn test_render_without_history() {
+        let (page, states) = page(None);
+        let failed = result(
+            "550e8400-e29b-41d4-a716-446655440002",
+            Utc::now(),
+            Some(CheckError::HttpStatus(503)),
+        );
+        states.record(&failed, Status::of(&failed));
+
+        let html = page.render(Utc::now());
+        assert!(html.contains("<title>Example &amp; Co Status</title>"));
+        assert!(html.contains("1 service is down"));
+        assert!(html.contains("<h2>Public</h2>"));
+        assert!(html.contains("&lt;API&gt;"));
+        assert!(!html.contains("<API>"));
+        assert!(!html.contains("internal.example.com"));
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("Recent incidents"));
+        assert!(html.find("Website").unwrap() < html.find("Database").unwrap());
+    }
+
+    #[test]
+    fn test_render_uptime_and_incidents() {
+        let dir = TempDir::new().unwrap();
+        let history = Arc::new(
+            HistoryStore::open(&HistorySettings::new(dir.path().join("history.db"))).unwrap(),
+        );
+        let now = Utc::now();
+        let website = "550e8400-e29b-41d4-a716-446655440000";
+        for minutes in [40, 30, 20, 10] {
+            let error = (minutes == 30 || minutes == 20).then_some(CheckError::HttpStatus(502));
+            history
+                .record(&result(website, now - Duration::minutes(minutes), error))
+                .unwrap();
+        }
+
+        let (page, _) = page(Some(history));
+        let html = page.render(now);
+        assert!(html.contains("24h 50.00%"));
+        assert!(html.contains("Recent incidents"));
+        assert!(html.contains("HTTP 502"));
+        assert!(html.contains("class=\"partial\""));
+        assert!(html.contains("All systems operational"));
+    }
+
+    #[tokio::test]
+    async fn test_incidents_leave_out_urls() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!(
+            "http://127.0.0.1:{}/?token=s3cret",
+            listener.local_addr().unwrap().port()
+        );
+        drop(listener);
+        let error = reqwest::get(&url).await.expect_err("nothing is listening");
+
+        let dir = TempDir::new().unwrap();
+        let history = Arc::new(
+            HistoryStore::open(&HistorySettings::new(dir.path().join("history.db"))).unwrap(),
+        );
+        let website = "550e8400-e29b-41d4-a716-446655440000";
+        let now = Utc::now();
+        history
+            .record(&result(
+                website,
+                now - Duration::minutes(1),
+                Some(CheckError::from_reqwest(error)),
+            ))
+            .unwrap();
+
+        let (page, _) = page(Some(history));
+        let html = page.render(now);
+        assert!(html.contains("Recent incidents"));
+        assert!(!html.contains("s3cret"), "{html}");
+        assert!(!html.contains("127.0.0.1"), "{html}");
+    }
+
+    #[test]
+    fn test_find_incidents() {
+        let start: DateTime<Utc> = "2024-01-01T10:00:00Z".parse().unwrap();
+        let entries: Vec<HistoryEntry> = [None, Some(503), Some(503), None, Some(500)]
+            .into_iter()
+            .enumerate()
+            .map(|(minute, status)| {
+                HistoryEntry::from(&result(
+                    "550e8400-e29b-41d4-a716-446655440000",
+                    start + Duration::minutes(minute as i64),
+                    status.map(CheckError::HttpStatus),
+                ))
+            })
+            .collect();
+
+        let incidents = find_incidents("Website", &entries);
+        assert_eq!(incidents.len(), 2);
+        assert_eq!(incidents[0].start, start + Duration::minutes(1));
+        assert_eq!(incidents[0].end, Some(start + Duration::minutes(3)));
+        assert_eq!(incidents[0].error.as_deref(), Some("HTTP 503"));
+        assert_eq!(incidents[1].end, None);
+
+        assert!(find_incidents("Website", &[]).is_empty());
+    }
+}
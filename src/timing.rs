@@ -0,0 +1,166 @@
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::lookup_host;
+
+/// A stage of a check request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Phase {
+    Dns,
+    Connect,
+    Ttfb,
+    Download,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 4] = [Phase::Dns, Phase::Connect, Phase::Ttfb, Phase::Download];
+
+    /// Value of the `phase` metrics label
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Phase::Dns => "dns",
+            Phase::Connect => "connect",
+            Phase::Ttfb => "ttfb",
+            Phase::Download => "download",
+        }
+    }
+}
+
+/// How long each phase of a check took; `None` for phases that didn't run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PhaseTimings {
+    /// Resolving the host name
+    pub dns: Option<Duration>,
+    /// Opening the TCP connection; only TCP checks time it
+    pub connect: Option<Duration>,
+    /// From sending the request to receiving the response headers
+    pub ttfb: Option<Duration>,
+    /// Reading the response body
+    pub download: Option<Duration>,
+}
+
+impl PhaseTimings {
+    pub fn get(&self, phase: Phase) -> Option<Duration> {
+        match phase {
+            Phase::Dns => self.dns,
+            Phase::Connect => self.connect,
+            Phase::Ttfb => self.ttfb,
+            Phase::Download => self.download,
+        }
+    }
+}
+
+tokio::task_local! {
+    /// Time the current request spent resolving host names, if it did
+    static LOOKUPS: Arc<Mutex<Option<Duration>>>;
+}
+
+/// DNS resolver that times the lookups of the request it resolves for.
+///
+/// The HTTP client only resolves a host when it has no idle connection to
+/// reuse, so a lookup during [`track_new_connections`] means the request
+/// opened a new connection. Hosts written as IP addresses skip resolution
+/// and are always reported as reused.
+pub struct TrackingResolver;
+
+impl Resolve for TrackingResolver {
+    fn resolve(&self, name: hyper::client::connect::dns::Name) -> Resolving {
+        // The lookup may finish on another task once the request has its
+        // connection, so hold on to the request's total
+        let lookups = LOOKUPS.try_with(Arc::clone).ok();
+        let host = name.as_str().to_string();
+
+        Box::pin(async move {
+            let start = Instant::now();
+            let addresses = lookup_host((host.as_str(), 0)).await;
+            if let Some(lookups) = lookups {
+                let mut total = lookups.lock().unwrap();
+                *total = Some(total.unwrap_or_default() + start.elapsed());
+            }
+
+            let addresses: Vec<SocketAddr> = addresses?.collect();
+            Ok(Box::new(addresses.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Run `future`, reporting how long it spent resolving host names through a
+/// client built with [`TrackingResolver`], or `None` if it opened no new
+/// connection
+pub async fn track_new_connections<F: Future>(future: F) -> (F::Output, Option<Duration>) {
+    let lookups = Arc::new(Mutex::new(None));
+    let output = LOOKUPS.scope(lookups.clone(), future).await;
+    let resolved = *lookups.lock().unwrap();
+    (output, resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_phase_timings() {
+        let timings = PhaseTimings {
+            dns: Some(Duration::from_millis(5)),
+            connect: None,
+            ttfb: Some(Duration::from_millis(100)),
+            download: Some(Duration::from_millis(20)),
+        };
+
+        assert_eq!(timings.get(Phase::Ttfb), Some(Duration::from_millis(100)));
+        assert_eq!(timings.get(Phase::Connect), None);
+    }
+
+    /// Serve `200 ok` to every request, keeping connections open
+    async fn keep_alive_server() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut buf = [0; 1024];
+                    let mut request = Vec::new();
+                    while let Ok(read) = stream.read(&mut buf).await {
+                        if read == 0 {
+                            break;
+                        }
+                        request.extend_from_slice(&buf[..read]);
+                        if request.windows(4).any(|window| window == b"\r\n\r\n") {
+                            request.clear();
+                            let response = b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nok";
+                            if stream.write_all(response).await.is_err() {
+                                break;
+                            }
+                        }
+                    }
+                });
+            }
+        });
+
+        port
+    }
+
+    #[tokio::test]
+    async fn test_track_new_connections() {
+        let url = format!("http://localhost:{}/", keep_alive_server().await);
+
+        let client = reqwest::Client::builder()
+            .dns_resolver(std::sync::Arc::new(TrackingResolver))
+            .build()
+            .unwrap();
+
+        let (response, resolved) = track_new_connections(client.get(&url).send()).await;
+        response.unwrap().bytes().await.unwrap();
+        assert!(resolved.is_some());
+
+        // The idle connection is reused for the second request
+        let (response, resolved) = track_new_connections(client.get(&url).send()).await;
+        response.unwrap().bytes().await.unwrap();
+        assert!(resolved.is_none());
+    }
+}
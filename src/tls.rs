@@ -0,0 +1,658 @@
+use openssl::asn1::{Asn1Time, Asn1TimeRef};
+use openssl::ssl::{SslConnector, SslFiletype, SslMethod, SslVerifyMode};
+use openssl::x509::{X509NameRef, X509Ref, X509VerifyResult};
+use reqwest::Url;
+use std::collections::HashMap;
+use std::fmt;
+use std::os::raw::c_int;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio_openssl::SslStream;
+
+use crate::settings::TlsConfig;
+
+/// A certificate from the chain presented by a server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CertificateInfo {
+    pub subject: String,
+    pub issuer: String,
+    /// Unix timestamp after which the certificate is no longer valid
+    pub not_after: i64,
+}
+
+/// Verification results told apart by [`TlsVerifyError`]
+const KNOWN_VERIFY_RESULTS: [c_int; 9] = [
+    openssl_sys::X509_V_ERR_CERT_HAS_EXPIRED,
+    openssl_sys::X509_V_ERR_CERT_NOT_YET_VALID,
+    openssl_sys::X509_V_ERR_HOSTNAME_MISMATCH,
+    openssl_sys::X509_V_ERR_IP_ADDRESS_MISMATCH,
+    openssl_sys::X509_V_ERR_UNABLE_TO_GET_ISSUER_CERT,
+    openssl_sys::X509_V_ERR_UNABLE_TO_GET_ISSUER_CERT_LOCALLY,
+    openssl_sys::X509_V_ERR_UNABLE_TO_VERIFY_LEAF_SIGNATURE,
+    openssl_sys::X509_V_ERR_DEPTH_ZERO_SELF_SIGNED_CERT,
+    openssl_sys::X509_V_ERR_SELF_SIGNED_CERT_IN_CHAIN,
+];
+
+/// Verification failures worth telling apart, most actionable first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TlsErrorKind {
+    Expired,
+    NotYetValid,
+    HostnameMismatch,
+    Untrusted,
+    Other,
+}
+
+/// Why a server's certificate chain failed verification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsVerifyError {
+    pub kind: TlsErrorKind,
+    pub message: String,
+}
+
+impl TlsVerifyError {
+    /// Value of the `error_type` metrics label for this failure
+    pub fn error_type(&self) -> &'static str {
+        match self.kind {
+            TlsErrorKind::Expired => "tls_cert_expired",
+            TlsErrorKind::NotYetValid => "tls_cert_not_yet_valid",
+            TlsErrorKind::HostnameMismatch => "tls_hostname_mismatch",
+            TlsErrorKind::Untrusted => "tls_untrusted",
+            TlsErrorKind::Other => "tls_error",
+        }
+    }
+
+    /// Recognise the verification failure in the message of a failed
+    /// handshake.
+    ///
+    /// The HTTP client only passes the failure on as text, which ends with
+    /// OpenSSL's description of the verification result in parentheses.
+    pub fn from_handshake_message(message: &str) -> Option<Self> {
+        KNOWN_VERIFY_RESULTS
+            .into_iter()
+            // SAFETY: every code is a verification result OpenSSL defines
+            .map(|code| unsafe { X509VerifyResult::from_raw(code) })
+            .find(|result| message.contains(&format!("({})", result.error_string())))
+            .map(Self::from_verify_result)
+    }
+
+    fn from_verify_result(result: X509VerifyResult) -> Self {
+        let kind = match result.as_raw() {
+            openssl_sys::X509_V_ERR_CERT_HAS_EXPIRED => TlsErrorKind::Expired,
+            openssl_sys::X509_V_ERR_CERT_NOT_YET_VALID => TlsErrorKind::NotYetValid,
+            openssl_sys::X509_V_ERR_HOSTNAME_MISMATCH
+            | openssl_sys::X509_V_ERR_IP_ADDRESS_MISMATCH => TlsErrorKind::HostnameMismatch,
+            openssl_sys::X509_V_ERR_UNABLE_TO_GET_ISSUER_CERT
+            | openssl_sys::X509_V_ERR_UNABLE_TO_GET_ISSUER_CERT_LOCALLY
+            | openssl_sys::X509_V_ERR_UNABLE_TO_VERIFY_LEAF_SIGNATURE
+            | openssl_sys::X509_V_ERR_DEPTH_ZERO_SELF_SIGNED_CERT
+            | openssl_sys::X509_V_ERR_SELF_SIGNED_CERT_IN_CHAIN => TlsErrorKind::Untrusted,
+            _ => TlsErrorKind::Other,
+        };
+
+        Self {
+            kind,
+            message: result.error_string().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for TlsVerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TLS verification failed: {}", self.message)
+    }
+}
+
+/// What a TLS handshake with a monitor's host revealed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsInspection {
+    /// Certificates presented by the server, leaf first
+    pub chain: Vec<CertificateInfo>,
+    /// The most significant verification failure, if the chain isn't valid
+    pub verify_error: Option<TlsVerifyError>,
+}
+
+impl TlsInspection {
+    /// Expiry of the certificate in the chain that expires first
+    pub fn earliest_expiry(&self) -> Option<i64> {
+        self.chain.iter().map(|cert| cert.not_after).min()
+    }
+
+    /// Whether a certificate in the chain expires within `threshold` of
+    /// `now`, a unix timestamp
+    pub fn expires_within(&self, threshold: Duration, now: i64) -> bool {
+        self.earliest_expiry()
+            .is_some_and(|not_after| not_after - now <= threshold.as_secs() as i64)
+    }
+}
+
+/// Complete a TLS handshake over `stream` and report the certificate chain
+/// the server presents.
+///
+/// Verification failures are recorded rather than aborting the handshake,
+/// so the chain of an untrusted or expired certificate can still be read.
+pub async fn handshake(
+    stream: TcpStream,
+    host: &str,
+    config: &TlsConfig,
+) -> Result<TlsInspection, String> {
+    let mut builder =
+        SslConnector::builder(SslMethod::tls()).map_err(|e| format!("TLS setup failed: {e}"))?;
+
+    if let Some(path) = &config.ca_bundle {
+        builder
+            .set_ca_file(path)
+            .map_err(|e| format!("Invalid ca_bundle {}: {e}", path.display()))?;
+    }
+
+    if let (Some(cert_path), Some(key_path)) = (&config.client_cert, &config.client_key) {
+        builder
+            .set_certificate_chain_file(cert_path)
+            .map_err(|e| format!("Invalid client_cert {}: {e}", cert_path.display()))?;
+        builder
+            .set_private_key_file(key_path, SslFiletype::PEM)
+            .map_err(|e| format!("Invalid client_key {}: {e}", key_path.display()))?;
+    }
+
+    let mut ssl = builder
+        .build()
+        .configure()
+        .and_then(|configuration| configuration.into_ssl(host))
+        .map_err(|e| format!("TLS setup failed: {e}"))?;
+
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let seen = errors.clone();
+    ssl.set_verify_callback(SslVerifyMode::PEER, move |verified, context| {
+        if !verified {
+            seen.lock().unwrap().push(context.error());
+        }
+        true
+    });
+
+    let mut stream = SslStream::new(ssl, stream).map_err(|e| format!("TLS setup failed: {e}"))?;
+    Pin::new(&mut stream)
+        .connect()
+        .await
+        .map_err(|e| format!("TLS handshake failed: {e}"))?;
+
+    let chain = stream
+        .ssl()
+        .peer_cert_chain()
+        .map(|chain| chain.iter().map(certificate_info).collect())
+        .unwrap_or_default();
+
+    let verify_error = errors
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|result| TlsVerifyError::from_verify_result(*result))
+        .min_by_key(|error| error.kind);
+
+    Ok(TlsInspection {
+        chain,
+        verify_error,
+    })
+}
+
+/// Share of its max age after which an inspection is repeated, so a check
+/// that comes round a moment before its interval is up still repeats it
+const REFRESH_AFTER: f64 = 0.9;
+
+/// Certificate inspections shared between checks.
+///
+/// The HTTP client doesn't expose the chain it received, so it's read from
+/// a separate handshake. Monitors of the same host share an inspection, and
+/// it's repeated once it's nearly as old as the checking monitor's
+/// interval, so a host sees about one extra handshake per interval rather
+/// than one per check of each monitor.
+#[derive(Default)]
+pub struct InspectionCache {
+    inspections: Mutex<HashMap<(String, u16, TlsConfig), Inspection>>,
+}
+
+/// An inspection of a host, kept for the max age of the check that made it
+struct Inspection {
+    started: Instant,
+    max_age: Duration,
+    result: Result<TlsInspection, String>,
+}
+
+impl Inspection {
+    fn is_fresh(&self, max_age: Duration) -> bool {
+        self.started.elapsed() < max_age.mul_f64(REFRESH_AFTER)
+    }
+}
+
+impl InspectionCache {
+    /// Connect to the host of an HTTPS `url` and report the certificate
+    /// chain it presents; `None` for other URLs. An inspection of the same
+    /// host within `max_age` is reused.
+    pub async fn inspect(
+        &self,
+        url: &str,
+        config: &TlsConfig,
+        max_age: Duration,
+        timeout: Duration,
+    ) -> Result<Option<TlsInspection>, String> {
+        let Some((host, port)) = https_host(url)? else {
+            return Ok(None);
+        };
+        let key = (host, port, config.clone());
+
+        if let Some(inspection) = self.inspections.lock().unwrap().get(&key) {
+            if inspection.is_fresh(max_age) {
+                return inspection.result.clone().map(Some);
+            }
+        }
+
+        let started = Instant::now();
+        let result = inspect_host(&key.0, port, config, timeout).await;
+
+        let mut inspections = self.inspections.lock().unwrap();
+        inspections.retain(|_, inspection| inspection.is_fresh(inspection.max_age));
+        inspections.insert(
+            key,
+            Inspection {
+                started,
+                max_age,
+                result: result.clone(),
+            },
+        );
+        result.map(Some)
+    }
+}
+
+/// Host and port of an HTTPS `url`; `None` for other URLs
+fn https_host(url: &str) -> Result<Option<(String, u16)>, String> {
+    let url = Url::parse(url).map_err(|e| format!("invalid URL: {e}"))?;
+    if url.scheme() != "https" {
+        return Ok(None);
+    }
+
+    // IPv6 hosts come back bracketed, as written in the URL
+    let host = url
+        .host_str()
+        .ok_or("URL has no host")?
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_string();
+    let port = url.port_or_known_default().unwrap_or(443);
+
+    Ok(Some((host, port)))
+}
+
+async fn inspect_host(
+    host: &str,
+    port: u16,
+    config: &TlsConfig,
+    timeout: Duration,
+) -> Result<TlsInspection, String> {
+    let inspecting = async {
+        let stream = TcpStream::connect((host, port))
+            .await
+            .map_err(|e| format!("Failed to connect to {host}:{port}: {e}"))?;
+        handshake(stream, host, config).await
+    };
+
+    tokio::time::timeout(timeout, inspecting)
+        .await
+        .map_err(|_| "Certificate inspection timed out".to_string())?
+}
+
+fn certificate_info(cert: &X509Ref) -> CertificateInfo {
+    CertificateInfo {
+        subject: format_name(cert.subject_name()),
+        issuer: format_name(cert.issuer_name()),
+        not_after: unix_timestamp(cert.not_after()),
+    }
+}
+
+/// Format a distinguished name as `CN=example.com,O=Example`
+fn format_name(name: &X509NameRef) -> String {
+    name.entries()
+        .map(|entry| {
+            let key = entry.object().nid().short_name().unwrap_or("?");
+            let value = entry
+                .data()
+                .as_utf8()
+                .map(|value| value.to_string())
+                .unwrap_or_default();
+            format!("{key}={value}")
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn unix_timestamp(time: &Asn1TimeRef) -> i64 {
+    Asn1Time::from_unix(0)
+        .and_then(|epoch| epoch.diff(time))
+        .map(|diff| i64::from(diff.days) * 86_400 + i64::from(diff.secs))
+        .unwrap_or(0)
+}
+
+/// Certificates for tests that need a TLS server
+#[cfg(test)]
+pub(crate) mod test_support {
+    use openssl::asn1::Asn1Time;
+    use openssl::bn::BigNum;
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::hash::MessageDigest;
+    use openssl::nid::Nid;
+    use openssl::pkey::{PKey, Private};
+    use openssl::ssl::{Ssl, SslAcceptor, SslMethod};
+    use openssl::x509::extension::SubjectAlternativeName;
+    use openssl::x509::{X509NameBuilder, X509};
+    use std::pin::Pin;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+    use tokio_openssl::SslStream;
+
+    /// Serve TLS handshakes with `cert` on localhost, returning the port
+    pub async fn serve(cert: &X509, key: &PKey<Private>) -> u16 {
+        serve_counting(cert, key).await.0
+    }
+
+    /// Like [`serve`], also counting the connections accepted
+    pub async fn serve_counting(cert: &X509, key: &PKey<Private>) -> (u16, Arc<AtomicUsize>) {
+        let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+        acceptor.set_certificate(cert).unwrap();
+        acceptor.set_private_key(key).unwrap();
+        let acceptor = acceptor.build();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let connections = Arc::new(AtomicUsize::new(0));
+        let accepted = connections.clone();
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                accepted.fetch_add(1, Ordering::SeqCst);
+                let ssl = Ssl::new(acceptor.context()).unwrap();
+                let mut stream = SslStream::new(ssl, stream).unwrap();
+                tokio::spawn(async move {
+                    if Pin::new(&mut stream).accept().await.is_ok() {
+                        let mut buf = [0; 1];
+                        let _ = stream.read(&mut buf).await;
+                    }
+                });
+            }
+        });
+
+        (port, connections)
+    }
+
+    /// Self-signed certificate for `name`, valid between the given unix times
+    pub fn self_signed(name: &str, not_before: i64, not_after: i64) -> (X509, PKey<Private>) {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+
+        let mut subject = X509NameBuilder::new().unwrap();
+        subject.append_entry_by_nid(Nid::COMMONNAME, name).unwrap();
+        let subject = subject.build();
+
+        let mut builder = X509::builder().unwrap();
+        builder.set_version(2).unwrap();
+        let serial = BigNum::from_u32(1).unwrap().to_asn1_integer().unwrap();
+        builder.set_serial_number(&serial).unwrap();
+        builder.set_subject_name(&subject).unwrap();
+        builder.set_issuer_name(&subject).unwrap();
+        builder.set_pubkey(&key).unwrap();
+        builder
+            .set_not_before(&Asn1Time::from_unix(not_before).unwrap())
+            .unwrap();
+        builder
+            .set_not_after(&Asn1Time::from_unix(not_after).unwrap())
+            .unwrap();
+        let san = SubjectAlternativeName::new()
+            .dns(name)
+            .build(&builder.x509v3_context(None, None))
+            .unwrap();
+        builder.append_extension(san).unwrap();
+        builder.sign(&key, MessageDigest::sha256()).unwrap();
+
+        (builder.build(), key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_support::{self_signed, serve, serve_counting};
+    use super::*;
+    use openssl::x509::X509;
+    use std::io::Write;
+    use std::sync::atomic::Ordering;
+    use tempfile::NamedTempFile;
+
+    const DAY: i64 = 24 * 60 * 60;
+
+    fn now() -> i64 {
+        chrono::Utc::now().timestamp()
+    }
+
+    fn ca_bundle(cert: &X509) -> NamedTempFile {
+        let mut file = NamedTempFile::new().expect("Failed to create temp file");
+        file.write_all(&cert.to_pem().unwrap())
+            .expect("Failed to write to temp file");
+        file
+    }
+
+    async fn inspect_local(port: u16, config: &TlsConfig) -> TlsInspection {
+        let stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        handshake(stream, "localhost", config)
+            .await
+            .expect("handshake should complete")
+    }
+
+    #[tokio::test]
+    async fn test_handshake_trusted_chain() {
+        let not_after = now() + 90 * DAY;
+        let (cert, key) = self_signed("localhost", now() - DAY, not_after);
+        let port = serve(&cert, &key).await;
+        let bundle = ca_bundle(&cert);
+
+        let config = TlsConfig {
+            ca_bundle: Some(bundle.path().to_path_buf()),
+            ..Default::default()
+        };
+        let inspection = inspect_local(port, &config).await;
+
+        assert_eq!(inspection.verify_error, None);
+        assert_eq!(
+            inspection.chain,
+            vec![CertificateInfo {
+                subject: "CN=localhost".to_string(),
+                issuer: "CN=localhost".to_string(),
+                not_after,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_inspect() {
+        let (cert, key) = self_signed("localhost", now() - DAY, now() + 90 * DAY);
+        let port = serve(&cert, &key).await;
+        let cache = InspectionCache::default();
+        let interval = Duration::from_secs(60);
+        let timeout = Duration::from_secs(5);
+
+        let inspection = cache
+            .inspect(
+                &format!("https://localhost:{port}/"),
+                &TlsConfig::default(),
+                interval,
+                timeout,
+            )
+            .await
+            .expect("inspection should complete")
+            .expect("HTTPS URLs are inspected");
+        assert_eq!(inspection.chain.len(), 1);
+
+        let plain = cache
+            .inspect(
+                &format!("http://localhost:{port}/"),
+                &TlsConfig::default(),
+                interval,
+                timeout,
+            )
+            .await
+            .unwrap();
+        assert_eq!(plain, None);
+    }
+
+    #[tokio::test]
+    async fn test_inspection_cache() {
+        let (cert, key) = self_signed("localhost", now() - DAY, now() + 90 * DAY);
+        let (port, connections) = serve_counting(&cert, &key).await;
+        let url = format!("https://localhost:{port}/");
+        let cache = InspectionCache::default();
+        let timeout = Duration::from_secs(5);
+        let interval = Duration::from_secs(60);
+
+        let first = cache
+            .inspect(&url, &TlsConfig::default(), interval, timeout)
+            .await
+            .unwrap();
+        let again = cache
+            .inspect(&url, &TlsConfig::default(), interval, timeout)
+            .await
+            .unwrap();
+        assert!(first.is_some());
+        assert_eq!(first, again);
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+
+        // Other paths of the host share the inspection
+        cache
+            .inspect(
+                &format!("{url}health"),
+                &TlsConfig::default(),
+                interval,
+                timeout,
+            )
+            .await
+            .unwrap();
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+
+        // Inspections of monitors checked more often don't evict it
+        let (other_port, _) = serve_counting(&cert, &key).await;
+        cache
+            .inspect(
+                &format!("https://localhost:{other_port}/"),
+                &TlsConfig::default(),
+                Duration::ZERO,
+                timeout,
+            )
+            .await
+            .unwrap();
+        cache
+            .inspect(&url, &TlsConfig::default(), interval, timeout)
+            .await
+            .unwrap();
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+
+        // Once nearly as old as the interval, the host is inspected again
+        cache
+            .inspect(&url, &TlsConfig::default(), Duration::ZERO, timeout)
+            .await
+            .unwrap();
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_verify_error_from_handshake_message() {
+        // SAFETY: a verification result OpenSSL defines
+        let expired =
+            unsafe { X509VerifyResult::from_raw(openssl_sys::X509_V_ERR_CERT_HAS_EXPIRED) };
+        let message = format!(
+            "error:0A000086:SSL routines:tls_post_process_server_certificate:certificate verify failed ({})",
+            expired.error_string()
+        );
+
+        let error = TlsVerifyError::from_handshake_message(&message).expect("verify failure");
+        assert_eq!(error.kind, TlsErrorKind::Expired);
+        assert_eq!(error.message, expired.error_string());
+
+        assert_eq!(
+            TlsVerifyError::from_handshake_message("unexpected EOF during handshake"),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handshake_untrusted_chain() {
+        let (cert, key) = self_signed("localhost", now() - DAY, now() + 90 * DAY);
+        let port = serve(&cert, &key).await;
+
+        let inspection = inspect_local(port, &TlsConfig::default()).await;
+
+        assert_eq!(inspection.chain.len(), 1);
+        let error = inspection.verify_error.expect("self-signed is untrusted");
+        assert_eq!(error.error_type(), "tls_untrusted");
+    }
+
+    #[tokio::test]
+    async fn test_handshake_expired_certificate() {
+        let (cert, key) = self_signed("localhost", now() - 30 * DAY, now() - DAY);
+        let port = serve(&cert, &key).await;
+        let bundle = ca_bundle(&cert);
+
+        let config = TlsConfig {
+            ca_bundle: Some(bundle.path().to_path_buf()),
+            ..Default::default()
+        };
+        let inspection = inspect_local(port, &config).await;
+
+        let error = inspection.verify_error.expect("certificate has expired");
+        assert_eq!(error.kind, TlsErrorKind::Expired);
+        assert_eq!(error.error_type(), "tls_cert_expired");
+    }
+
+    #[tokio::test]
+    async fn test_handshake_hostname_mismatch() {
+        let (cert, key) = self_signed("other.example.com", now() - DAY, now() + 90 * DAY);
+        let port = serve(&cert, &key).await;
+        let bundle = ca_bundle(&cert);
+
+        let config = TlsConfig {
+            ca_bundle: Some(bundle.path().to_path_buf()),
+            ..Default::default()
+        };
+        let inspection = inspect_local(port, &config).await;
+
+        let error = inspection.verify_error.expect("name doesn't match");
+        assert_eq!(error.error_type(), "tls_hostname_mismatch");
+    }
+
+    #[test]
+    fn test_expires_within() {
+        let now = now();
+        let inspection = TlsInspection {
+            chain: vec![
+                CertificateInfo {
+                    subject: "CN=leaf".to_string(),
+                    issuer: "CN=intermediate".to_string(),
+                    not_after: now + 10 * DAY,
+                },
+                CertificateInfo {
+                    subject: "CN=intermediate".to_string(),
+                    issuer: "CN=root".to_string(),
+                    not_after: now + 5 * DAY,
+                },
+            ],
+            verify_error: None,
+        };
+
+        assert_eq!(inspection.earliest_expiry(), Some(now + 5 * DAY));
+        assert!(inspection.expires_within(Duration::from_secs(7 * DAY as u64), now));
+        assert!(!inspection.expires_within(Duration::from_secs(3 * DAY as u64), now));
+
+        let empty = TlsInspection {
+            chain: vec![],
+            verify_error: None,
+        };
+        assert!(!empty.expires_within(Duration::from_secs(7 * DAY as u64), now));
+    }
+}
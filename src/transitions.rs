@@ -0,0 +1,265 @@
+use std::collections::{HashMap, VecDeque};
+use uuid::Uuid;
+
+use crate::settings::FlappingSettings;
+use crate::worker::MonitorResult;
+
+/// A monitor's reported status, which only changes once enough checks in a
+/// row agree
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Status {
+    pub up: bool,
+    /// Changing state too often for its status to mean much
+    pub flapping: bool,
+}
+
+impl Status {
+    /// The status a single check reports on its own
+    pub fn of(result: &MonitorResult) -> Self {
+        Self {
+            up: result.success,
+            flapping: false,
+        }
+    }
+}
+
+/// A monitor's status after a check, and what about it changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatusUpdate {
+    pub status: Status,
+    /// The monitor went up or down
+    pub changed: bool,
+    /// The monitor started or stopped flapping
+    pub flapping_changed: bool,
+}
+
+/// Status of one monitor and the checks leading up to it
+#[derive(Debug, Default)]
+struct Tracked {
+    /// `None` until the first check
+    status: Option<Status>,
+    /// Consecutive checks disagreeing with the status
+    streak: u32,
+    /// Outcomes of the most recent checks, oldest first
+    outcomes: VecDeque<bool>,
+}
+
+/// Decides when monitors go up or down and when they're flapping.
+///
+/// A monitor's first check sets its status. After that it goes down once
+/// `failure_threshold` checks in a row fail and back up once
+/// `success_threshold` checks in a row pass. With flap detection enabled, a
+/// monitor is flapping once at least `high_threshold` of its last `window`
+/// checks changed outcome, and stops once at most `low_threshold` did.
+#[derive(Debug)]
+pub struct StatusTracker {
+    flapping: FlappingSettings,
+    monitors: HashMap<Uuid, Tracked>,
+}
+
+impl StatusTracker {
+    pub fn new(flapping: &FlappingSettings) -> Self {
+        Self {
+            flapping: flapping.clone(),
+            monitors: HashMap::new(),
+        }
+    }
+
+    /// Switch to changed flap detection settings, keeping each monitor's
+    /// status
+    pub fn configure(&mut self, flapping: &FlappingSettings) {
+        self.flapping = flapping.clone();
+        if !flapping.enabled {
+            for tracked in self.monitors.values_mut() {
+                tracked.outcomes.clear();
+                if let Some(status) = &mut tracked.status {
+                    status.flapping = false;
+                }
+            }
+        }
+    }
+
+    /// Start a monitor from its status before a restart, unless it was
+    /// checked since
+    pub fn restore(&mut self, monitor_id: Uuid, up: bool) {
+        self.monitors
+            .entry(monitor_id)
+            .or_default()
+            .status
+            .get_or_insert(Status {
+                up,
+                flapping: false,
+            });
+    }
+
+    /// Track a finished check of a monitor with the given thresholds
+    pub fn observe(
+        &mut self,
+        result: &MonitorResult,
+        failure_threshold: u32,
+        success_threshold: u32,
+    ) -> StatusUpdate {
+        let tracked = self.monitors.entry(result.monitor_id).or_default();
+
+        let mut flapping_changed = false;
+        if self.flapping.enabled {
+            tracked.outcomes.push_back(result.success);
+            while tracked.outcomes.len() > self.flapping.window {
+                tracked.outcomes.pop_front();
+            }
+        }
+
+        let Some(status) = &mut tracked.status else {
+            let status = Status::of(result);
+            tracked.status = Some(status);
+            return StatusUpdate {
+                status,
+                changed: false,
+                flapping_changed,
+            };
+        };
+
+        if self.flapping.enabled && tracked.outcomes.len() == self.flapping.window {
+            let rate = change_rate(&tracked.outcomes);
+            let flapping = if status.flapping {
+                rate > self.flapping.low_threshold
+            } else {
+                rate >= self.flapping.high_threshold
+            };
+            flapping_changed = flapping != status.flapping;
+            status.flapping = flapping;
+        }
+
+        let mut changed = false;
+        if result.success == status.up {
+            tracked.streak = 0;
+        } else {
+            tracked.streak += 1;
+            let threshold = if status.up {
+                failure_threshold
+            } else {
+                success_threshold
+            };
+            if tracked.streak >= threshold {
+                status.up = result.success;
+                tracked.streak = 0;
+                changed = true;
+            }
+        }
+
+        StatusUpdate {
+            status: *status,
+            changed,
+            flapping_changed,
+        }
+    }
+
+    /// A monitor's current status, `None` until its first check
+    pub fn status(&self, monitor_id: Uuid) -> Option<Status> {
+        self.monitors.get(&monitor_id)?.status
+    }
+
+    /// Forget a removed monitor
+    pub fn remove(&mut self, monitor_id: Uuid) {
+        self.monitors.remove(&monitor_id);
+    }
+}
+
+/// Fraction of consecutive outcomes that differ
+fn change_rate(outcomes: &VecDeque<bool>) -> f64 {
+    if outcomes.len() < 2 {
+        return 0.0;
+    }
+
+    let changes = outcomes
+        .iter()
+        .zip(outcomes.iter().skip(1))
+        .filter(|(a, b)| a != b)
+        .count();
+    changes as f64 / (outcomes.len() - 1) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::CheckError;
+
+    fn result(monitor_id: Uuid, success: bool) -> MonitorResult {
+        MonitorResult::new(monitor_id, (!success).then_some(CheckError::ReadTimeout))
+    }
+
+    #[test]
+    fn test_thresholds() {
+        let mut tracker = StatusTracker::new(&FlappingSettings::default());
+        let monitor_id = Uuid::new_v4();
+        let mut observe = |success| tracker.observe(&result(monitor_id, success), 3, 2);
+
+        // The first check sets the status right away
+        let update = observe(true);
+        assert!(update.status.up);
+        assert!(!update.changed);
+
+        // Two failures, then a pass, start the count again
+        assert!(observe(false).status.up);
+        assert!(observe(false).status.up);
+        assert!(observe(true).status.up);
+        assert!(observe(false).status.up);
+        assert!(observe(false).status.up);
+
+        let update = observe(false);
+        assert!(!update.status.up);
+        assert!(update.changed);
+
+        assert!(!observe(true).status.up);
+        let update = observe(true);
+        assert!(update.status.up);
+        assert!(update.changed);
+    }
+
+    #[test]
+    fn test_restore() {
+        let mut tracker = StatusTracker::new(&FlappingSettings::default());
+        let monitor_id = Uuid::new_v4();
+        tracker.restore(monitor_id, false);
+
+        let update = tracker.observe(&result(monitor_id, true), 1, 2);
+        assert!(!update.status.up);
+        assert!(tracker.observe(&result(monitor_id, true), 1, 2).changed);
+    }
+
+    #[test]
+    fn test_flapping() {
+        let mut tracker = StatusTracker::new(&FlappingSettings {
+            enabled: true,
+            window: 5,
+            high_threshold: 0.75,
+            low_threshold: 0.25,
+        });
+        let monitor_id = Uuid::new_v4();
+        let mut observe = |success| tracker.observe(&result(monitor_id, success), 1, 1);
+
+        for success in [true, false, true, false] {
+            assert!(!observe(success).status.flapping);
+        }
+        // Four changes in five checks
+        let update = observe(true);
+        assert!(update.status.flapping);
+        assert!(update.flapping_changed);
+
+        // Flapping until at most a quarter of the checks changed outcome
+        assert!(observe(true).status.flapping);
+        assert!(observe(true).status.flapping);
+        let update = observe(true);
+        assert!(!update.status.flapping);
+        assert!(update.flapping_changed);
+        assert!(update.status.up);
+    }
+
+    #[test]
+    fn test_change_rate() {
+        assert_eq!(change_rate(&VecDeque::from([true])), 0.0);
+        assert_eq!(change_rate(&VecDeque::from([true, true, true])), 0.0);
+        assert_eq!(change_rate(&VecDeque::from([true, false, true])), 1.0);
+        assert_eq!(change_rate(&VecDeque::from([true, false, false])), 0.5);
+    }
+}
@@ -2,24 +2,24 @@ use std::time::Duration;
 use tokio::time;
 use uuid::Uuid;
 
-use sammy_monitor::metrics::{init_metrics, MonitorMetadata, METRICS_REGISTRY};
+use sammy_monitor::checks::ping::PingStats;
+use sammy_monitor::metrics::{init_metrics, MetricsRegistry, MonitorMetadata, METRICS_REGISTRY};
+use sammy_monitor::recorder::MetricsRecorder;
+use sammy_monitor::settings::MonitorType;
 
 /// Single comprehensive integration test for Prometheus metrics
 /// This test validates that metrics are correctly generated, formatted, and contain accurate values
 #[tokio::test]
 async fn test_metrics_integration() {
     // Set up Prometheus exporter FIRST before doing anything with metrics
-    let handle = metrics_exporter_prometheus::PrometheusBuilder::new()
+    let handle = MetricsRecorder::builder()
         .add_global_label("app", "sammy_monitor_test")
-        .set_buckets_for_metric(
-            metrics_exporter_prometheus::Matcher::Full(
-                "http_monitor_response_time_seconds".to_string(),
-            ),
+        .set_buckets(
+            "http_monitor_response_time_seconds",
             &[0.05, 0.1, 0.2, 0.5, 1.0, 2.0, 5.0, 10.0, 20.0, 30.0],
         )
-        .expect("Failed to set histogram buckets")
-        .install_recorder()
-        .expect("Failed to install Prometheus recorder");
+        .install()
+        .expect("Failed to install metrics recorder");
 
     // Initialize metrics system AFTER setting up the exporter
     init_metrics();
@@ -30,14 +30,16 @@ async fn test_metrics_integration() {
 
     let metadata1 = MonitorMetadata {
         name: "Integration Test Site 1".to_string(),
-        url: "https://example.com".to_string(),
-        interval: 60,
+        kind: MonitorType::Http,
+        target: "https://example.com".to_string(),
+        interval: Duration::from_secs(60),
     };
 
     let metadata2 = MonitorMetadata {
         name: "Integration Test Site 2".to_string(),
-        url: "https://httpbin.org/status/404".to_string(),
-        interval: 120,
+        kind: MonitorType::Http,
+        target: "https://httpbin.org/status/404".to_string(),
+        interval: Duration::from_secs(120),
     };
 
     // Register monitors with metrics registry
@@ -106,6 +108,8 @@ async fn test_metrics_integration() {
     assert!(output.contains(&format!("monitor_id=\"{monitor2_id}\"")));
     assert!(output.contains("monitor_name=\"Integration Test Site 1\""));
     assert!(output.contains("monitor_name=\"Integration Test Site 2\""));
+    assert!(output.contains("interval_seconds=\"60\""));
+    assert!(output.contains("interval_seconds=\"120\""));
 
     // Test 4: Check status labels
     assert!(output.contains("status=\"success\""));
@@ -314,6 +318,313 @@ async fn test_metrics_integration() {
     println!("📝 Format compliance: ✓");
 }
 
+/// Unregistered monitors disappear from the exposition while others stay
+#[test]
+fn test_unregister_monitor_metrics() {
+    // A local recorder keeps this test apart from the global one above
+    let recorder = MetricsRecorder::default();
+    let handle = recorder.handle();
+    let registry = MetricsRegistry::new();
+
+    let removed_id = Uuid::new_v4();
+    let kept_id = Uuid::new_v4();
+
+    metrics::with_local_recorder(&recorder, || {
+        registry.register_monitor(
+            removed_id,
+            MonitorMetadata {
+                name: "Removed Site".to_string(),
+                kind: MonitorType::Http,
+                target: "https://removed.example.com".to_string(),
+                interval: Duration::from_secs(60),
+            },
+        );
+        registry.register_monitor(
+            kept_id,
+            MonitorMetadata {
+                name: "Kept Site".to_string(),
+                kind: MonitorType::Http,
+                target: "https://kept.example.com".to_string(),
+                interval: Duration::from_secs(60),
+            },
+        );
+        registry.record_success(removed_id, 100);
+        registry.record_failure(removed_id, 200, "http_error", Some(503));
+        registry.record_success(kept_id, 100);
+    });
+
+    let output = handle.render();
+    assert!(output.contains(&format!("monitor_id=\"{removed_id}\"")));
+
+    registry.unregister_monitor(removed_id);
+
+    let output = handle.render();
+    assert!(
+        !output.contains(&format!("monitor_id=\"{removed_id}\"")),
+        "Removed monitor should not be exported:\n{output}"
+    );
+    assert!(!output.contains("Removed Site"));
+    assert_eq!(
+        extract_metric_value(
+            &output,
+            "http_monitor_requests_total",
+            &[("status", "success"), ("monitor_id", &kept_id.to_string())],
+        ),
+        Some(1.0)
+    );
+
+    // Recording for an unregistered monitor is a no-op
+    metrics::with_local_recorder(&recorder, || registry.record_success(removed_id, 100));
+    let output = handle.render();
+    assert!(!output.contains(&format!("monitor_id=\"{removed_id}\"")));
+}
+
+/// Relabeled monitors are only exported under their new labels
+#[test]
+fn test_update_monitor_metrics() {
+    let recorder = MetricsRecorder::default();
+    let handle = recorder.handle();
+    let registry = MetricsRegistry::new();
+
+    let monitor_id = Uuid::new_v4();
+
+    metrics::with_local_recorder(&recorder, || {
+        registry.register_monitor(
+            monitor_id,
+            MonitorMetadata {
+                name: "Old Name".to_string(),
+                kind: MonitorType::Http,
+                target: "https://old.example.com".to_string(),
+                interval: Duration::from_secs(60),
+            },
+        );
+        registry.record_success(monitor_id, 100);
+        registry.record_failure(monitor_id, 200, "http_error", Some(500));
+
+        registry.update_monitor(
+            monitor_id,
+            MonitorMetadata {
+                name: "New Name".to_string(),
+                kind: MonitorType::Http,
+                target: "https://new.example.com".to_string(),
+                interval: Duration::from_secs(30),
+            },
+        );
+        registry.record_success(monitor_id, 100);
+    });
+
+    let output = handle.render();
+
+    assert!(!output.contains("Old Name"), "Old labels should be gone");
+    assert!(!output.contains("https://old.example.com"));
+    assert!(!output.contains("interval_seconds=\"60\""));
+    assert!(output.contains("monitor_name=\"New Name\""));
+    assert!(output.contains("monitor_url=\"https://new.example.com\""));
+
+    // Metrics start again under the new labels
+    assert_eq!(
+        extract_metric_value(
+            &output,
+            "http_monitor_requests_total",
+            &[("status", "success"), ("monitor_name", "New Name")],
+        ),
+        Some(1.0)
+    );
+    assert_eq!(
+        extract_metric_value(
+            &output,
+            "http_monitor_requests_total",
+            &[("status", "failure"), ("monitor_name", "New Name")],
+        ),
+        Some(0.0)
+    );
+    assert!(!output.contains("http_monitor_failures_total{"));
+    assert_eq!(
+        extract_metric_value(
+            &output,
+            "http_monitor_up",
+            &[("monitor_id", &monitor_id.to_string())],
+        ),
+        Some(1.0)
+    );
+}
+
+/// TCP monitors are labelled with their address instead of a URL
+#[test]
+fn test_tcp_monitor_labels() {
+    let recorder = MetricsRecorder::default();
+    let handle = recorder.handle();
+    let registry = MetricsRegistry::new();
+
+    let monitor_id = Uuid::new_v4();
+
+    metrics::with_local_recorder(&recorder, || {
+        registry.register_monitor(
+            monitor_id,
+            MonitorMetadata {
+                name: "Database".to_string(),
+                kind: MonitorType::Tcp,
+                target: "db.example.com:5432".to_string(),
+                interval: Duration::from_secs(30),
+            },
+        );
+        registry.record_failure(monitor_id, 5000, "connect_timeout", None);
+    });
+
+    let output = handle.render();
+    assert!(output.contains("monitor_address=\"db.example.com:5432\""));
+    assert!(!output.contains("monitor_url="));
+    assert_eq!(
+        extract_metric_value(
+            &output,
+            "http_monitor_failures_total",
+            &[
+                ("monitor_address", "db.example.com:5432"),
+                ("error_type", "connect_timeout"),
+            ],
+        ),
+        Some(1.0)
+    );
+
+    // Removed TCP monitors are dropped like any other
+    registry.unregister_monitor(monitor_id);
+    let output = handle.render();
+    assert!(!output.contains("db.example.com:5432"));
+}
+
+#[test]
+fn test_ping_monitor_metrics() {
+    let recorder = MetricsRecorder::default();
+    let handle = recorder.handle();
+    let registry = MetricsRegistry::new();
+
+    let monitor_id = Uuid::new_v4();
+
+    metrics::with_local_recorder(&recorder, || {
+        registry.register_monitor(
+            monitor_id,
+            MonitorMetadata {
+                name: "Gateway".to_string(),
+                kind: MonitorType::Ping,
+                target: "gateway.example.com".to_string(),
+                interval: Duration::from_secs(30),
+            },
+        );
+        registry.record_ping(
+            monitor_id,
+            &PingStats {
+                sent: 4,
+                received: 3,
+                min: Some(Duration::from_millis(10)),
+                avg: Some(Duration::from_millis(20)),
+                max: Some(Duration::from_millis(40)),
+            },
+        );
+    });
+
+    let output = handle.render();
+    assert!(output.contains("monitor_host=\"gateway.example.com\""));
+    for (stat, seconds) in [("min", 0.01), ("avg", 0.02), ("max", 0.04)] {
+        assert_eq!(
+            extract_metric_value(&output, "http_monitor_ping_rtt_seconds", &[("stat", stat)]),
+            Some(seconds)
+        );
+    }
+    assert_eq!(
+        extract_metric_value(
+            &output,
+            "http_monitor_ping_packet_loss_percent",
+            &[("monitor_host", "gateway.example.com")],
+        ),
+        Some(25.0)
+    );
+}
+
+#[test]
+fn test_dns_monitor_metrics() {
+    let recorder = MetricsRecorder::default();
+    let handle = recorder.handle();
+    let registry = MetricsRegistry::new();
+
+    let monitor_id = Uuid::new_v4();
+
+    metrics::with_local_recorder(&recorder, || {
+        registry.register_monitor(
+            monitor_id,
+            MonitorMetadata {
+                name: "Zone".to_string(),
+                kind: MonitorType::Dns,
+                target: "example.com".to_string(),
+                interval: Duration::from_secs(60),
+            },
+        );
+        registry.record_dns_resolution(monitor_id, Duration::from_millis(20));
+        registry.record_failure(monitor_id, 20, "nxdomain", None);
+    });
+
+    let output = handle.render();
+    assert!(output.contains("monitor_query=\"example.com\""));
+    assert_eq!(
+        extract_metric_value(
+            &output,
+            "http_monitor_dns_resolution_seconds_count",
+            &[("monitor_query", "example.com")],
+        ),
+        Some(1.0)
+    );
+    assert_eq!(
+        extract_metric_value(
+            &output,
+            "http_monitor_failures_total",
+            &[("error_type", "nxdomain")],
+        ),
+        Some(1.0)
+    );
+}
+
+#[test]
+fn test_heartbeat_monitor_metrics() {
+    let recorder = MetricsRecorder::default();
+    let handle = recorder.handle();
+    let registry = MetricsRegistry::new();
+
+    let monitor_id = Uuid::new_v4();
+    let endpoint = format!("/heartbeat/{monitor_id}");
+
+    metrics::with_local_recorder(&recorder, || {
+        registry.register_monitor(
+            monitor_id,
+            MonitorMetadata {
+                name: "Nightly Backup".to_string(),
+                kind: MonitorType::Heartbeat,
+                target: endpoint.clone(),
+                interval: Duration::from_secs(86_400),
+            },
+        );
+        registry.record_job_duration(monitor_id, Duration::from_secs(90));
+        registry.record_failure(monitor_id, 0, "missed_heartbeat", None);
+    });
+
+    let output = handle.render();
+    assert!(output.contains(&format!("monitor_endpoint=\"{endpoint}\"")));
+    assert_eq!(
+        extract_metric_value(
+            &output,
+            "http_monitor_heartbeat_job_duration_seconds",
+            &[("monitor_endpoint", &endpoint)],
+        ),
+        Some(90.0)
+    );
+    assert_eq!(
+        extract_metric_value(
+            &output,
+            "http_monitor_failures_total",
+            &[("error_type", "missed_heartbeat")],
+        ),
+        Some(1.0)
+    );
+}
+
 // Helper functions for metric extraction and validation
 
 fn extract_metric_value(output: &str, metric_name: &str, labels: &[(&str, &str)]) -> Option<f64> {